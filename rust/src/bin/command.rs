@@ -1,357 +1,371 @@
 // ============================================================
-// Command Pattern in Rust — Trade Management with Undo/Redo
+// Command Pattern — Trade Management with Undo/Redo (demo)
 //
-// Two approaches:
-//
-// 1. Enum commands (closed set)
-//    - Commands are pure data, #[derive(Clone)]
-//    - Execute/undo are match arms in free functions
-//    - History is Vec<TradeAction> — trivially cloneable
-//    - Perfect for event sourcing and audit trails
-//
-// 2. Trait objects (open extension)
-//    - Box<dyn Command> with clone_box for value semantics
-//    - New command types don't require modifying existing code
-//    - Slightly more ceremony than enum approach
-//
-// Rust's ownership system gives a unique advantage here:
-// the borrow checker ensures commands can't hold dangling
-// references to the portfolio. Commands store data, not
-// references — the portfolio is passed explicitly to execute/undo.
-// ============================================================
-
-use std::collections::HashMap;
-use std::fmt;
-
-// --- Receiver: Portfolio ---
-
-#[derive(Debug, Clone)]
-struct Portfolio {
-    positions: HashMap<String, i32>,
-    cash: f64,
-}
-
-impl Portfolio {
-    fn new(cash: f64) -> Self {
-        Self {
-            positions: HashMap::new(),
-            cash,
-        }
-    }
-
-    fn buy(&mut self, symbol: &str, qty: i32, price: f64) {
-        *self.positions.entry(symbol.to_string()).or_insert(0) += qty;
-        self.cash -= qty as f64 * price;
-        println!(
-            "  [EXEC] BUY  {} {} @ ${:.2}  (cash: ${:.2})",
-            qty, symbol, price, self.cash
-        );
-    }
-
-    fn sell(&mut self, symbol: &str, qty: i32, price: f64) {
-        *self.positions.entry(symbol.to_string()).or_insert(0) -= qty;
-        self.cash += qty as f64 * price;
-        println!(
-            "  [EXEC] SELL {} {} @ ${:.2}  (cash: ${:.2})",
-            qty, symbol, price, self.cash
-        );
-    }
-
-    fn reverse_buy(&mut self, symbol: &str, qty: i32, price: f64) {
-        *self.positions.entry(symbol.to_string()).or_insert(0) -= qty;
-        self.cash += qty as f64 * price;
-        println!(
-            "  [UNDO] BUY  {} {} @ ${:.2} reversed  (cash: ${:.2})",
-            qty, symbol, price, self.cash
-        );
-    }
-
-    fn reverse_sell(&mut self, symbol: &str, qty: i32, price: f64) {
-        *self.positions.entry(symbol.to_string()).or_insert(0) += qty;
-        self.cash -= qty as f64 * price;
-        println!(
-            "  [UNDO] SELL {} {} @ ${:.2} reversed  (cash: ${:.2})",
-            qty, symbol, price, self.cash
-        );
-    }
-
-    fn print_positions(&self) {
-        println!("  Portfolio:");
-        println!("    Cash: ${:.2}", self.cash);
-        for (sym, qty) in &self.positions {
-            if *qty != 0 {
-                println!("    {}: {} shares", sym, qty);
-            }
-        }
-    }
-}
-
-// ============================================================
-// APPROACH 1: Enum Commands (closed set)
+// The types and pricing/execution logic live in the library
+// module src/command.rs; this binary only drives them.
 // ============================================================
 
-#[derive(Debug, Clone)]
-enum TradeAction {
-    Buy {
-        symbol: String,
-        quantity: i32,
-        price: f64,
-    },
-    Sell {
-        symbol: String,
-        quantity: i32,
-        price: f64,
-    },
-}
-
-impl fmt::Display for TradeAction {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::Buy {
-                symbol,
-                quantity,
-                price,
-            } => write!(f, "BUY {} {} @ ${:.2}", quantity, symbol, price),
-            Self::Sell {
-                symbol,
-                quantity,
-                price,
-            } => write!(f, "SELL {} {} @ ${:.2}", quantity, symbol, price),
-        }
-    }
-}
-
-impl TradeAction {
-    fn execute(&self, portfolio: &mut Portfolio) {
-        match self {
-            Self::Buy {
-                symbol,
-                quantity,
-                price,
-            } => portfolio.buy(symbol, *quantity, *price),
-            Self::Sell {
-                symbol,
-                quantity,
-                price,
-            } => portfolio.sell(symbol, *quantity, *price),
-        }
-    }
-
-    fn undo(&self, portfolio: &mut Portfolio) {
-        match self {
-            Self::Buy {
-                symbol,
-                quantity,
-                price,
-            } => portfolio.reverse_buy(symbol, *quantity, *price),
-            Self::Sell {
-                symbol,
-                quantity,
-                price,
-            } => portfolio.reverse_sell(symbol, *quantity, *price),
-        }
-    }
-}
-
-// --- Command History: Vec of Clone-able values ---
-
-#[derive(Debug, Clone)]
-struct TradeHistory {
-    executed: Vec<TradeAction>,
-    undone: Vec<TradeAction>,
-}
-
-impl TradeHistory {
-    fn new() -> Self {
-        Self {
-            executed: Vec::new(),
-            undone: Vec::new(),
-        }
-    }
-
-    fn execute(&mut self, action: TradeAction, portfolio: &mut Portfolio) {
-        action.execute(portfolio);
-        self.executed.push(action);
-        self.undone.clear();
-    }
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::time::{Duration, UNIX_EPOCH};
 
-    fn undo(&mut self, portfolio: &mut Portfolio) -> bool {
-        if let Some(action) = self.executed.pop() {
-            action.undo(portfolio);
-            self.undone.push(action);
-            true
-        } else {
-            false
-        }
-    }
+use design_patterns::clock::FixedClock;
+use design_patterns::command::*;
+use design_patterns::report::Report;
+use design_patterns::visitor::{Currency, Equity, Instrument};
 
-    fn redo(&mut self, portfolio: &mut Portfolio) -> bool {
-        if let Some(action) = self.undone.pop() {
-            action.execute(portfolio);
-            self.executed.push(action);
-            true
-        } else {
-            false
-        }
-    }
-
-    fn print_history(&self) {
-        println!("  Trade History:");
-        if self.executed.is_empty() {
-            println!("    (empty)");
-        } else {
-            for (i, action) in self.executed.iter().enumerate() {
-                println!("    {}. {}", i + 1, action);
-            }
-        }
-    }
-}
-
-// ============================================================
-// APPROACH 2: Trait Objects (open extension)
-// ============================================================
-
-trait Command: fmt::Debug {
-    fn execute(&self, portfolio: &mut Portfolio);
-    fn undo(&self, portfolio: &mut Portfolio);
-    fn description(&self) -> String;
-    fn clone_box(&self) -> Box<dyn Command>;
-}
-
-impl Clone for Box<dyn Command> {
-    fn clone(&self) -> Self {
-        self.clone_box()
-    }
-}
-
-#[derive(Debug, Clone)]
-struct MarketBuy {
-    symbol: String,
-    quantity: i32,
-    price: f64,
-}
-
-impl Command for MarketBuy {
-    fn execute(&self, portfolio: &mut Portfolio) {
-        portfolio.buy(&self.symbol, self.quantity, self.price);
-    }
+fn main() {
+    println!("=== Rust Command Pattern: Trade Management ===");
+    println!("========== Approach 1: Enum Commands ==========\n");
 
-    fn undo(&self, portfolio: &mut Portfolio) {
-        portfolio.reverse_buy(&self.symbol, self.quantity, self.price);
-    }
+    // Fixed clock keeps the audit stamps in this demo reproducible
+    let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let clock = Rc::new(FixedClock::new(start));
+    let mut portfolio = Portfolio::new(1_000_000.0);
+    let mut history = TradeHistory::with_clock(clock.clone());
 
-    fn description(&self) -> String {
-        format!(
-            "MARKET BUY {} {} @ ${:.2}",
-            self.quantity, self.symbol, self.price
+    println!("--- Executing trades ---");
+    history
+        .execute(
+            TradeAction::Buy {
+                symbol: "AAPL".into(),
+                quantity: 100,
+                price: 185.50,
+                tag: None,
+            },
+            &mut portfolio,
         )
-    }
-
-    fn clone_box(&self) -> Box<dyn Command> {
-        Box::new(self.clone())
-    }
-}
-
-#[derive(Debug, Clone)]
-struct LimitSell {
-    symbol: String,
-    quantity: i32,
-    limit_price: f64,
-}
-
-impl Command for LimitSell {
-    fn execute(&self, portfolio: &mut Portfolio) {
-        portfolio.sell(&self.symbol, self.quantity, self.limit_price);
-    }
+        .expect("trade within available cash");
+    clock.advance(Duration::from_millis(1_250));
+    history
+        .execute(
+            TradeAction::Buy {
+                symbol: "GOOGL".into(),
+                quantity: 50,
+                price: 140.25,
+                tag: None,
+            },
+            &mut portfolio,
+        )
+        .expect("trade within available cash");
+    clock.advance(Duration::from_millis(1_250));
+    history
+        .execute(
+            TradeAction::Sell {
+                symbol: "MSFT".into(),
+                quantity: 75,
+                price: 420.00,
+                tag: None,
+            },
+            &mut portfolio,
+        )
+        .expect("trade within available cash");
 
-    fn undo(&self, portfolio: &mut Portfolio) {
-        portfolio.reverse_sell(&self.symbol, self.quantity, self.limit_price);
-    }
+    println!();
+    println!("{}", portfolio.report());
+    println!();
+    history.print_history();
 
-    fn description(&self) -> String {
-        format!(
-            "LIMIT SELL {} {} @ ${:.2}",
-            self.quantity, self.symbol, self.limit_price
-        )
+    println!("\n--- Rejected trade (insufficient funds) ---");
+    let too_big = TradeAction::Buy {
+        symbol: "BRK.A".into(),
+        quantity: 10,
+        price: 620_000.00,
+        tag: None,
+    };
+    if let Err(e) = history.execute(too_big, &mut portfolio) {
+        println!("  [REJECT] {}", e);
     }
-
-    fn clone_box(&self) -> Box<dyn Command> {
-        Box::new(self.clone())
+    println!("  History still has {} trades", history.executed.len());
+
+    println!("\n--- Exposure by sector ---");
+    let sectors: HashMap<String, String> = [("AAPL", "Technology"), ("GOOGL", "Communication")]
+        .into_iter()
+        .map(|(sym, sector)| (sym.to_string(), sector.to_string()))
+        .collect();
+    let marks: HashMap<String, f64> = [("AAPL", 188.00), ("GOOGL", 142.00), ("MSFT", 415.00)]
+        .into_iter()
+        .map(|(sym, px)| (sym.to_string(), px))
+        .collect();
+    for (sector, value) in portfolio.exposure_by_sector(&sectors, &marks) {
+        println!("    {}: ${:.2}", sector, value);
     }
-}
-
-// ============================================================
-
-fn main() {
-    println!("=== Rust Command Pattern: Trade Management ===");
-    println!("========== Approach 1: Enum Commands ==========\n");
-
-    let mut portfolio = Portfolio::new(1_000_000.0);
-    let mut history = TradeHistory::new();
+    println!(
+        "  Open: {:?}  gross: ${:.2}  TSLA: {} shares",
+        portfolio.symbols(),
+        portfolio.gross_exposure(&marks),
+        portfolio.position("TSLA")
+    );
+    println!("  Mark-to-market: ${:.2}", portfolio.mark_to_market(&marks));
+    let mut live_marks = marks.clone();
+    let mut feed = VecFeed::new(&[("AAPL", 188.40), ("MSFT", 414.10), ("AAPL", 188.65)]);
+    portfolio.mark_from_feed(&mut feed, &mut live_marks);
+    println!("  Mark-to-market (live): ${:.2}", portfolio.mark_to_market(&live_marks));
+    println!(
+        "  HHI: {:.3}  effective positions: {:.2}",
+        herfindahl_index(&portfolio, &marks),
+        effective_positions(&portfolio, &marks)
+    );
 
-    println!("--- Executing trades ---");
-    history.execute(
+    println!("\n--- P&L: buy 100 @ 10, buy 100 @ 20, sell 100 @ 30 ---");
+    let mut pnl_book = Portfolio::new(10_000.0);
+    let mut pnl_history = TradeHistory::new();
+    for action in [
         TradeAction::Buy {
-            symbol: "AAPL".into(),
+            symbol: "XYZ".into(),
             quantity: 100,
-            price: 185.50,
+            price: 10.0,
+            tag: None,
         },
-        &mut portfolio,
-    );
-    history.execute(
         TradeAction::Buy {
-            symbol: "GOOGL".into(),
-            quantity: 50,
-            price: 140.25,
+            symbol: "XYZ".into(),
+            quantity: 100,
+            price: 20.0,
+            tag: None,
         },
-        &mut portfolio,
-    );
-    history.execute(
         TradeAction::Sell {
-            symbol: "MSFT".into(),
-            quantity: 75,
-            price: 420.00,
+            symbol: "XYZ".into(),
+            quantity: 100,
+            price: 30.0,
+            tag: None,
         },
-        &mut portfolio,
+    ] {
+        pnl_history
+            .execute(action, &mut pnl_book)
+            .expect("trade within available cash");
+    }
+    let xyz_marks = HashMap::from([("XYZ".to_string(), 25.0)]);
+    println!(
+        "  Realized: ${:.2}  Unrealized @ $25: ${:.2}",
+        pnl_book.realized_pnl,
+        pnl_book.unrealized_pnl(&xyz_marks)
     );
-
-    println!();
-    portfolio.print_positions();
-    println!();
+    for action in pnl_history.coalesced() {
+        println!("  Coalesced: {}", action);
+    }
+    pnl_history.undo(&mut pnl_book);
+    pnl_history.undo(&mut pnl_book);
+    println!("{}", pnl_book.report());
+
+    println!("\n--- Weighted average entry: 100 @ 10, 300 @ 14 ---");
+    let mut entry_book = Portfolio::new(10_000.0);
+    entry_book.buy("XYZ", 100, 10.0).expect("trade within available cash");
+    entry_book.buy("XYZ", 300, 14.0).expect("trade within available cash");
+    println!("  Avg price: {:?}", entry_book.avg_price("XYZ"));
+    entry_book.sell("XYZ", 400, 15.0).expect("selling shares held");
+    println!("  After full close: {:?}", entry_book.avg_price("XYZ"));
+
+    // Trim the last trade rather than reversing it outright
+    println!("\n--- Partial undo: 30 of the MSFT sell ---");
+    history
+        .undo_partial(30, &mut portfolio)
+        .expect("reversing less than the trade size");
+    if let Err(e) = history.undo_partial(500, &mut portfolio) {
+        println!("  [REJECT] {}", e);
+    }
     history.print_history();
 
     println!("\n--- Undo last trade ---");
-    history.undo(&mut portfolio);
-    portfolio.print_positions();
+    if let Some(action) = history.undo(&mut portfolio) {
+        println!("  Undid: {}", action);
+    }
+    println!("{}", portfolio.report());
 
     println!("\n--- Undo another ---");
-    history.undo(&mut portfolio);
-    portfolio.print_positions();
+    if let Some(action) = history.undo(&mut portfolio) {
+        println!("  Undid: {}", action);
+    }
+    println!("{}", portfolio.report());
 
     println!("\n--- Redo ---");
-    history.redo(&mut portfolio);
-    portfolio.print_positions();
+    if let Some(action) = history.redo(&mut portfolio) {
+        println!("  Redid: {}", action);
+    }
+    println!("{}", portfolio.report());
 
     // Snapshot: just clone
     println!("\n--- Snapshot history (clone!) ---");
     let snapshot = history.clone();
     println!("  Snapshot has {} trades", snapshot.executed.len());
 
-    // Continue on original
-    history.execute(
+    // Continue on original; the tag is carried into the audit trail
+    history
+        .execute(
+            TradeAction::Sell {
+                symbol: "AAPL".into(),
+                quantity: 50,
+                price: 190.00,
+                tag: None,
+            }
+            .tagged("trim after earnings"),
+            &mut portfolio,
+        )
+        .expect("trade within available cash");
+
+    println!("\n--- Original history ---");
+    history.print_history();
+    println!("  Net cash flow: ${:.2}", history.net_cash_flow());
+
+    println!("\n--- Snapshot unchanged ---");
+    snapshot.print_history();
+
+    // Direct rollback without walking the undo chain
+    println!("\n--- Portfolio snapshot/restore ---");
+    let checkpoint = portfolio.snapshot();
+    portfolio
+        .sell("GOOGL", 50, 142.00)
+        .expect("selling shares held");
+    portfolio
+        .buy("AMZN", 100, 180.00)
+        .expect("trade within available cash");
+    portfolio.restore(checkpoint.clone());
+    println!("  Restored to checkpoint: {}", portfolio.snapshot() == checkpoint);
+    println!("{}", portfolio.report());
+
+    // Cash accounts can't sell shares they don't hold
+    println!("\n--- Short-selling guard ---");
+    let mut cash_account = Portfolio::new(50_000.0).with_short_selling(false);
+    let mut cash_history = TradeHistory::new();
+    for action in [
+        TradeAction::Buy {
+            symbol: "AAPL".into(),
+            quantity: 100,
+            price: 185.50,
+            tag: None,
+        },
         TradeAction::Sell {
             symbol: "AAPL".into(),
-            quantity: 50,
+            quantity: 150,
             price: 190.00,
+            tag: None,
+        },
+    ] {
+        let desc = action.to_string();
+        if let Err(e) = cash_history.execute(action, &mut cash_account) {
+            println!("  [REJECT] {}: {}", desc, e);
+        }
+    }
+    println!("{}", cash_account.report());
+
+    // Hard caps are checked before the portfolio is touched
+    println!("\n--- Position and exposure limits ---");
+    let mut capped_account = Portfolio::new(1_000_000.0)
+        .with_position_limit("TSLA", 500)
+        .with_max_gross_exposure(150_000.0);
+    let limit_trades = [
+        TradeAction::Buy {
+            symbol: "TSLA".into(),
+            quantity: 400,
+            price: 175.00,
+            tag: None,
+        },
+        TradeAction::Buy {
+            symbol: "TSLA".into(),
+            quantity: 200,
+            price: 175.00,
+            tag: None,
         },
-        &mut portfolio,
+        TradeAction::Buy {
+            symbol: "NVDA".into(),
+            quantity: 100,
+            price: 890.50,
+            tag: None,
+        },
+    ];
+    for action in &limit_trades {
+        if let Err(e) = action.execute(&mut capped_account) {
+            println!("  [REJECT] {}: {}", action, e);
+        }
+    }
+    println!("{}", capped_account.report());
+
+    // Commission comes off cash on the fill and is refunded on undo
+    println!("\n--- Commission round trip ---");
+    let mut fee_account = Portfolio::new(100_000.0).with_commission(0.005);
+    let mut fee_history = TradeHistory::new();
+    let cash_before = fee_account.cash;
+    fee_history
+        .execute(
+            TradeAction::Buy {
+                symbol: "AAPL".into(),
+                quantity: 200,
+                price: 185.50,
+                tag: None,
+            },
+            &mut fee_account,
+        )
+        .expect("trade within available cash");
+    println!("  Commission paid: ${:.2}", fee_account.commission(200));
+    fee_history.undo(&mut fee_account);
+    println!(
+        "  Cash restored: {}",
+        (fee_account.cash - cash_before).abs() < 1e-9
     );
 
-    println!("\n--- Original history ---");
-    history.print_history();
+    // Bounded history: only the last two trades stay undoable
+    println!("\n--- History capped at depth 2 ---");
+    let mut capped = TradeHistory::with_max_depth(2);
+    let mut capped_portfolio = Portfolio::new(100_000.0);
+    for (symbol, price) in [("AAPL", 185.50), ("GOOGL", 140.25), ("MSFT", 420.00)] {
+        capped
+            .execute(
+                TradeAction::Buy {
+                    symbol: symbol.into(),
+                    quantity: 10,
+                    price,
+                    tag: None,
+                },
+                &mut capped_portfolio,
+            )
+            .expect("trade within available cash");
+    }
+    capped.print_history();
+    let steps = capped.undo_all(&mut capped_portfolio);
+    println!("  Undid {} trades; AAPL buy is permanent", steps);
+    println!("{}", capped_portfolio.report());
+    let steps = capped.redo_all(&mut capped_portfolio);
+    println!("  Redid {} trades", steps);
+
+    // Each new trade is written out as it executes, one JSON line
+    // apiece; an in-memory buffer stands in for the file here
+    #[cfg(feature = "serde")]
+    {
+        use std::cell::RefCell;
+
+        println!("\n--- Journaling trades as they execute ---");
+        let journal = Rc::new(RefCell::new(FileJournal::new(Vec::new())));
+        let mut journaled = TradeHistory::new().with_journal(journal.clone());
+        let mut book = Portfolio::new(100_000.0);
+        for (symbol, quantity) in [("AAPL", 100), ("MSFT", 50)] {
+            let action = TradeAction::Buy {
+                symbol: symbol.into(),
+                quantity,
+                price: 150.0,
+                tag: None,
+            };
+            journaled
+                .execute(action, &mut book)
+                .expect("trade within available cash");
+        }
+        let written = String::from_utf8_lossy(&journal.borrow().writer).into_owned();
+        for line in written.lines() {
+            println!("  journal: {}", line);
+        }
+    }
 
-    println!("\n--- Snapshot unchanged ---");
-    snapshot.print_history();
+    // The executed log alone is enough to rebuild the portfolio
+    println!("\n--- Replay history into a fresh portfolio ---");
+    #[cfg(feature = "serde")]
+    let history = {
+        let json = history.to_json();
+        println!("  Persisted {} bytes of JSON", json.len());
+        TradeHistory::from_json(&json).expect("history round-trips through JSON")
+    };
+    let opening = Portfolio::new(1_000_000.0);
+    let rebuilt = history.rebuild(&opening);
+    let diff = rebuilt.diff(&portfolio);
+    println!("  Reconciled: {} ({})", diff.is_empty(), diff);
+    println!("  Versus a fresh account: {}", opening.diff(&portfolio));
 
     // ============================================================
     println!("\n========== Approach 2: Trait Objects ==========\n");
@@ -369,11 +383,18 @@ fn main() {
             quantity: 30,
             limit_price: 890.50,
         }),
+        Box::new(LimitBuy {
+            symbol: "AMD".into(),
+            quantity: 100,
+            limit_price: 160.00,
+        }),
     ];
 
     println!("--- Executing trait commands ---");
     for cmd in &commands {
-        cmd.execute(&mut portfolio2);
+        if let Err(e) = cmd.execute(&mut portfolio2) {
+            println!("  [REJECT] {}: {}", cmd.description(), e);
+        }
     }
 
     println!("\n--- Undoing all ---");
@@ -381,7 +402,7 @@ fn main() {
         cmd.undo(&mut portfolio2);
     }
 
-    portfolio2.print_positions();
+    println!("{}", portfolio2.report());
 
     // Commands are cloneable
     println!("\n--- Commands are cloneable ---");
@@ -392,6 +413,342 @@ fn main() {
         println!("    {}", cmd.description());
     }
 
+    println!("\n--- Command history: undo/redo ---");
+    let mut cmd_history = CommandHistory::new();
+    for cmd in commands_copy {
+        cmd_history
+            .execute(cmd, &mut portfolio2)
+            .expect("trade within available cash");
+    }
+    cmd_history.undo(&mut portfolio2);
+    cmd_history.undo(&mut portfolio2);
+    println!("{}", portfolio2.report());
+    cmd_history.redo(&mut portfolio2);
+    println!("{}", portfolio2.report());
+    cmd_history.print_history();
+
+    // Trades are computed up front; nothing moves until they run
+    println!("\n--- Rebalance to 60/40 AAPL/MSFT ---");
+    let mut book = Portfolio::new(100_000.0);
+    book.buy("AAPL", 500, 150.00).expect("enough cash");
+    let marks = HashMap::from([("AAPL".to_string(), 160.00), ("MSFT".to_string(), 400.00)]);
+    let targets = HashMap::from([("AAPL".to_string(), 0.6), ("MSFT".to_string(), 0.4)]);
+    let mut rebalance = CommandHistory::new();
+    for cmd in book.rebalance_to(&targets, &marks) {
+        println!("  {}", cmd.description());
+        rebalance
+            .execute(cmd, &mut book)
+            .expect("sells fund the buys");
+    }
+    println!("{}", book.report());
+
+    // Stops only trade once the market has crossed the trigger
+    println!("\n--- Stop orders ---");
+    let stops = [
+        StopMarket {
+            symbol: "TSLA".into(),
+            quantity: 200,
+            trigger_price: 170.00,
+            side: Side::Sell,
+            market_price: 172.40,
+        },
+        StopMarket {
+            symbol: "TSLA".into(),
+            quantity: 200,
+            trigger_price: 170.00,
+            side: Side::Sell,
+            market_price: 168.90,
+        },
+        StopMarket {
+            symbol: "NVDA".into(),
+            quantity: 10,
+            trigger_price: 900.00,
+            side: Side::Buy,
+            market_price: 890.50,
+        },
+    ];
+    for stop in &stops {
+        let fired = if stop.is_triggered() { "fired" } else { "resting" };
+        println!("  {} -> {}", stop.description(), fired);
+        if let Err(e) = stop.execute(&mut portfolio2) {
+            println!("  [REJECT] {}: {}", stop.description(), e);
+        }
+    }
+    println!("{}", portfolio2.report());
+
+    // A rebalance either lands every leg or none of them
+    println!("\n--- Macro command: all-or-nothing rebalance ---");
+    let rebalance = MacroCommand {
+        name: "REBALANCE".into(),
+        legs: vec![
+            Box::new(LimitSell {
+                symbol: "NVDA".into(),
+                quantity: 10,
+                limit_price: 890.50,
+            }),
+            Box::new(MarketBuy {
+                symbol: "AMZN".into(),
+                quantity: 5_000,
+                price: 180.00,
+            }),
+            Box::new(MarketBuy {
+                symbol: "MSFT".into(),
+                quantity: 20,
+                price: 420.00,
+            }),
+        ],
+    };
+    println!("  {}", rebalance.description());
+    if let Err(e) = rebalance.execute(&mut portfolio2) {
+        println!("  [REJECT] REBALANCE rolled back: {}", e);
+    }
+    println!("{}", portfolio2.report());
+
+    // Redo goes through Command::redo, which MacroCommand overrides
+    let trim = MacroCommand {
+        name: "TRIM".into(),
+        legs: vec![
+            Box::new(LimitSell {
+                symbol: "NVDA".into(),
+                quantity: 10,
+                limit_price: 890.50,
+            }),
+            Box::new(MarketBuy {
+                symbol: "MSFT".into(),
+                quantity: 20,
+                price: 420.00,
+            }),
+        ],
+    };
+    cmd_history
+        .execute(Box::new(trim), &mut portfolio2)
+        .expect("trade within available cash");
+    cmd_history.undo(&mut portfolio2);
+    cmd_history.redo(&mut portfolio2);
+    cmd_history.print_history();
+
+    // Snapshot-based batch: the third order fails, nothing sticks
+    println!("\n--- Transactional apply ---");
+    let mut batch_book = Portfolio::new(50_000.0);
+    let batch: Vec<Box<dyn Command>> = vec![
+        Box::new(MarketBuy {
+            symbol: "AAPL".into(),
+            quantity: 100,
+            price: 185.50,
+        }),
+        Box::new(MarketBuy {
+            symbol: "MSFT".into(),
+            quantity: 50,
+            price: 420.00,
+        }),
+        Box::new(MarketBuy {
+            symbol: "NVDA".into(),
+            quantity: 50,
+            price: 890.50,
+        }),
+        Box::new(LimitSell {
+            symbol: "AAPL".into(),
+            quantity: 50,
+            limit_price: 190.00,
+        }),
+    ];
+    if let Err(e) = batch_book.apply(&batch) {
+        println!("  [REJECT] batch rolled back: {}", e);
+    }
+    println!("{}", batch_book.report());
+
+    // Corporate actions go through the same history as trades
+    println!("\n--- Corporate actions ---");
+    let mut corp_book = Portfolio::new(10_000.0);
+    let mut corp_history = CommandHistory::new();
+    let corp_actions: Vec<Box<dyn Command>> = vec![
+        Box::new(MarketBuy {
+            symbol: "KO".into(),
+            quantity: 100,
+            price: 60.00,
+        }),
+        Box::new(Dividend {
+            symbol: "KO".into(),
+            per_share: 0.50,
+        }),
+        Box::new(Split {
+            symbol: "KO".into(),
+            ratio: 2.0,
+        }),
+    ];
+    for action in corp_actions {
+        corp_history
+            .execute(action, &mut corp_book)
+            .expect("trade within available cash");
+    }
+    println!("{}", corp_book.report());
+    corp_history.undo(&mut corp_book);
+    corp_history.undo(&mut corp_book);
+    println!("{}", corp_book.report());
+
+    // A split between a buy and its undo: reversing 100 shares leaves
+    // the other 100 behind; the captured snapshot rolls back exactly
+    println!("\n--- Snapshot undo across a split ---");
+    let buy = || MarketBuy {
+        symbol: "KO".into(),
+        quantity: 100,
+        price: 60.00,
+    };
+    let commands: [Box<dyn Command>; 2] = [
+        Box::new(buy()),
+        Box::new(SnapshotUndo::new(Box::new(buy()))),
+    ];
+    for cmd in commands {
+        let mut book = Portfolio::new(10_000.0);
+        cmd.execute(&mut book).expect("trade within available cash");
+        book.split("KO", 2.0).expect("positive ratio");
+        cmd.undo(&mut book);
+        println!("  KO after undo: {} shares", book.position("KO"));
+    }
+
+    // Bracket exit on a long: take profit at 190, stop at 165
+    println!("\n--- OCO bracket exits ---");
+    let mut bracket_book = Portfolio::new(100_000.0);
+    bracket_book
+        .buy("TSLA", 100, 175.00)
+        .expect("trade within available cash");
+    let bracket = |market_price: f64| Oco {
+        take_profit: Box::new(LimitSell {
+            symbol: "TSLA".into(),
+            quantity: 100,
+            limit_price: 190.00,
+        }),
+        stop_loss: Box::new(LimitSell {
+            symbol: "TSLA".into(),
+            quantity: 100,
+            limit_price: 165.00,
+        }),
+        take_profit_price: 190.00,
+        stop_price: 165.00,
+        market_price,
+    };
+    for market_price in [192.00, 163.50, 178.00] {
+        let oco = bracket(market_price);
+        println!("  {}", oco.description());
+        oco.execute(&mut bracket_book).expect("closing a held position");
+        oco.undo(&mut bracket_book);
+    }
+    println!("{}", bracket_book.report());
+
+    // Pre-trade checks wrap a command without changing it
+    // Limits rest until a tick makes them marketable
+    println!("\n--- Working orders ---");
+    let mut manager = OrderManager::new();
+    let mut working_book = Portfolio::new(100_000.0);
+    manager.submit(Box::new(LimitSell {
+        symbol: "AAPL".into(),
+        quantity: 50,
+        limit_price: 195.00,
+    }));
+    manager.submit(Box::new(LimitBuy {
+        symbol: "AAPL".into(),
+        quantity: 100,
+        limit_price: 180.00,
+    }));
+    manager.submit(Box::new(LimitSell {
+        symbol: "AAPL".into(),
+        quantity: 50,
+        limit_price: 250.00,
+    }));
+    for price in [188.00, 179.50, 196.25] {
+        let filled = manager.tick(price, &mut working_book);
+        println!(
+            "  tick ${:.2}: {} filled, {} working",
+            price,
+            filled,
+            manager.working.len()
+        );
+    }
+    // Pulling the far limit leaves an audit entry but no trade
+    if let Some(order) = manager.working.pop() {
+        manager
+            .history
+            .execute(
+                Box::new(Cancel {
+                    order_ref: order.description(),
+                }),
+                &mut working_book,
+            )
+            .expect("cancel never touches the portfolio");
+    }
+    manager.history.print_history();
+    println!("{}", working_book.report());
+
+    println!("\n--- Guarded commands ---");
+    let checks: Vec<Rc<dyn RiskCheck>> = vec![
+        Rc::new(MaxNotional { limit: 100_000.0 }),
+        Rc::new(RestrictedList {
+            symbols: HashSet::from(["GME".to_string()]),
+        }),
+    ];
+    let guard = |inner: Box<dyn Command>| GuardedCommand {
+        inner,
+        checks: checks.clone(),
+    };
+    let orders = vec![
+        guard(Box::new(MarketBuy {
+            symbol: "TSLA".into(),
+            quantity: 100,
+            price: 175.00,
+        })),
+        guard(Box::new(MarketBuy {
+            symbol: "TSLA".into(),
+            quantity: 1_000,
+            price: 175.00,
+        })),
+        guard(Box::new(MarketBuy {
+            symbol: "GME".into(),
+            quantity: 10,
+            price: 25.00,
+        })),
+    ];
+    let mut guarded_history = CommandHistory::new();
+    for order in orders {
+        let description = order.description();
+        if let Err(reject) = guarded_history.execute(Box::new(order), &mut portfolio2) {
+            println!("  [REJECT] {}: {}", description, reject);
+        }
+    }
+    guarded_history.undo(&mut portfolio2);
+    println!("{}", portfolio2.report());
+
+    // --- Equity instruments booked as positions ---
+    println!("\n--- Equity instruments as positions ---");
+    let holdings = [
+        Instrument::Equity(Equity {
+            ticker: "AAPL".to_string(),
+            currency: Currency::USD,
+            shares: 200,
+            price: 185.00,
+        }),
+        Instrument::Equity(Equity {
+            ticker: "TSLA".to_string(),
+            currency: Currency::USD,
+            shares: -50,
+            price: 175.00,
+        }),
+    ];
+    let mut book = Portfolio::new(100_000.0);
+    let mut book_history = TradeHistory::new();
+    for inst in &holdings {
+        if let Instrument::Equity(e) = inst {
+            if let Err(err) = book_history.execute(TradeAction::from(e), &mut book) {
+                println!("  [REJECT] {}: {}", inst, err);
+            }
+        }
+    }
+    println!("{}", book.report());
+    let book_marks: HashMap<String, f64> = [("AAPL", 190.00), ("TSLA", 170.00)]
+        .into_iter()
+        .map(|(sym, px)| (sym.to_string(), px))
+        .collect();
+    println!("  Mark-to-market: ${:.2}", book.mark_to_market(&book_marks));
+
     // ============================================================
     // Rust's ownership advantage:
     //
@@ -1,295 +1,339 @@
 // ============================================================
-// Strategy Pattern in Rust — Order Execution Strategies
+// Strategy Pattern — Order Execution Strategies (demo)
 //
-// Rust gives you three natural approaches:
-//
-// 1. Enum dispatch (like std::variant / C tagged union)
-//    - Closed set, zero overhead, exhaustive match
-//    - Clone/Copy for free via derive
-//
-// 2. Trait objects (like C++ type erasure / C function pointers)
-//    - Open set, dynamic dispatch via vtable
-//    - Box<dyn Trait> for owned, &dyn Trait for borrowed
-//    - Clone requires a workaround (CloneStrategy supertrait)
-//
-// 3. Closures (Rust's killer feature for Strategy)
-//    - Any Fn closure IS a strategy — no boilerplate at all
-//    - The most idiomatic Rust approach for simple strategies
-//
-// All three shown below.
+// The types and pricing/execution logic live in the library
+// module src/strategy.rs; this binary only drives them.
 // ============================================================
 
-use std::fmt;
+use design_patterns::clock::SystemClock;
+use design_patterns::report::Report;
+use design_patterns::strategy::*;
 
-// ============================================================
-// APPROACH 1: Enum Dispatch
-// ============================================================
+fn main() -> Result<(), StrategyError> {
+    println!("=== Rust Strategy Pattern: Order Execution ===");
+    println!("========== Approach 1: Enum Dispatch ==========\n");
 
-#[derive(Debug, Clone)]
-enum ExecutionStrategy {
-    Twap { slices: u32 },
-    Vwap { participation_rate: f64 },
-    Iceberg { visible_qty: u32 },
-}
+    let mut order = Order::new("AAPL", 10000, 185.50, ExecutionStrategy::Twap { slices: 5 })
+        .with_commission(0.005);
+    order.send()?;
 
-impl ExecutionStrategy {
-    fn execute(&self, symbol: &str, quantity: u32, price: f64) {
-        match self {
-            Self::Twap { slices } => {
-                let per_slice = quantity / slices;
-                println!(
-                    "[TWAP] Executing {}: {} shares @ ${:.2} across {} slices ({}/slice)",
-                    symbol, quantity, price, slices, per_slice
-                );
-            }
-            Self::Vwap { participation_rate } => {
-                println!(
-                    "[VWAP] Executing {}: {} shares @ ${:.2} with {:.0}% participation",
-                    symbol, quantity, price, participation_rate * 100.0
-                );
-            }
-            Self::Iceberg { visible_qty } => {
-                println!(
-                    "[Iceberg] Executing {}: {} shares @ ${:.2} showing {} at a time",
-                    symbol, quantity, price, visible_qty
-                );
-            }
-        }
-    }
+    println!("\n--- Switching to VWAP ---");
+    order.set_strategy(ExecutionStrategy::Vwap {
+        participation_rate: 0.15,
+    });
+    order.send()?;
 
-    fn name(&self) -> &'static str {
-        match self {
-            Self::Twap { .. } => "TWAP",
-            Self::Vwap { .. } => "VWAP",
-            Self::Iceberg { .. } => "Iceberg",
-        }
+    println!("\n--- Switching to Iceberg ---");
+    order.set_strategy(ExecutionStrategy::Iceberg { visible_qty: 500 });
+    order.send()?;
+
+    println!("\n--- Building an order by name ---");
+    let built = Order::builder()
+        .symbol("NVDA")
+        .quantity(2_000)
+        .price(875.00)
+        .strategy(ExecutionStrategy::Twap { slices: 4 })
+        .commission(0.005)
+        .build();
+    match built {
+        Ok(order) => println!("{}", order.report()),
+        Err(e) => println!("  Build failed: {}", e),
     }
-}
-
-#[derive(Debug, Clone)]
-struct Order {
-    symbol: String,
-    quantity: u32,
-    price: f64,
-    strategy: ExecutionStrategy, // value, not a pointer
-}
-
-impl Order {
-    fn new(symbol: &str, quantity: u32, price: f64, strategy: ExecutionStrategy) -> Self {
-        Self {
-            symbol: symbol.to_string(),
-            quantity,
-            price,
-            strategy,
+    for incomplete in [
+        Order::builder().symbol("NVDA").price(875.00),
+        Order::builder()
+            .symbol("NVDA")
+            .quantity(0)
+            .price(875.00)
+            .strategy(ExecutionStrategy::Twap { slices: 4 }),
+    ] {
+        if let Err(e) = incomplete.build() {
+            println!("  Build rejected: {}", e);
         }
     }
 
-    fn set_strategy(&mut self, strategy: ExecutionStrategy) {
-        self.strategy = strategy;
-    }
+    // Clone is trivial — #[derive(Clone)] does everything
+    println!("\n--- Cloning order ---");
+    let mut order2 = order.clone();
+    order2.set_strategy(ExecutionStrategy::Twap { slices: 10 });
 
-    fn send(&self) {
-        println!(
-            "Order: {} {} shares @ ${:.2} using {}",
-            self.symbol,
-            self.quantity,
-            self.price,
-            self.strategy.name()
-        );
-        self.strategy.execute(&self.symbol, self.quantity, self.price);
-    }
-}
+    println!("Original:");
+    order.send()?;
+    println!("Clone (independent):");
+    order2.send()?;
 
-// ============================================================
-// APPROACH 2: Trait Objects (open extension)
-// ============================================================
+    println!("\n--- Switching to Implementation Shortfall ---");
+    order2.set_strategy(ExecutionStrategy::ImplementationShortfall { urgency: 0.8 });
+    order2.send()?;
 
-// The base trait — Rust's equivalent of an abstract interface.
-// We add a clone_box method to enable cloning of trait objects.
-trait ExecutionStrategyTrait: fmt::Debug {
-    fn execute(&self, symbol: &str, quantity: u32, price: f64);
-    fn name(&self) -> &str;
-    fn clone_box(&self) -> Box<dyn ExecutionStrategyTrait>;
-}
+    println!("\n--- Switching to Peg (mid +5bps) ---");
+    order2.set_strategy(ExecutionStrategy::Peg {
+        reference: PegReference::Mid,
+        offset_bps: 5.0,
+    });
+    order2.send()?;
 
-impl Clone for Box<dyn ExecutionStrategyTrait> {
-    fn clone(&self) -> Self {
-        self.clone_box()
-    }
-}
+    println!("\n--- Switching to Dark pool (min fill 3000) ---");
+    order2.set_strategy(ExecutionStrategy::DarkPool { min_fill: 3000 });
+    order2.send()?;
 
-// Concrete strategies — plain structs, opt into the trait explicitly
-#[derive(Debug, Clone)]
-struct TwapStrategy {
-    slices: u32,
-}
+    println!("\n--- Switching to Sniper (fires at <= $186.00) ---");
+    order2.set_strategy(ExecutionStrategy::Sniper {
+        min_size: 1_000,
+        max_price: 186.00,
+    });
+    order2.send()?;
+    order2.set_strategy(ExecutionStrategy::Sniper {
+        min_size: 1_000,
+        max_price: 185.00,
+    });
+    order2.send()?;
+
+    println!("\n--- Switching to Stealth TWAP ---");
+    order2.set_strategy(ExecutionStrategy::StealthTwap {
+        slices: 5,
+        size_jitter: 0.3,
+        time_jitter: 0.5,
+        seed: 42,
+    });
+    order2.send()?;
+
+    // With a quote, aggressive strategies pay the ask, passive ones mid
+    println!("\n--- Quoted $185.40 / $185.60 ---");
+    let mut quoted = Order::new("AAPL", 10000, 185.50, ExecutionStrategy::Twap { slices: 5 })
+        .with_quote(185.40, 185.60);
+    for strategy in [
+        ExecutionStrategy::Iceberg { visible_qty: 2_000 },
+        ExecutionStrategy::ImplementationShortfall { urgency: 0.8 },
+    ] {
+        quoted.set_strategy(strategy);
+        quoted.send()?;
+    }
 
-impl ExecutionStrategyTrait for TwapStrategy {
-    fn execute(&self, symbol: &str, quantity: u32, price: f64) {
-        let per_slice = quantity / self.slices;
+    println!("\n--- Preview (no side effects) ---");
+    let plan = order2.preview()?;
+    println!("  {} would send {} child orders:", plan.strategy, plan.children.len());
+    for child in &plan.children {
         println!(
-            "[TWAP-trait] Executing {}: {} shares @ ${:.2} across {} slices ({}/slice)",
-            symbol, quantity, price, self.slices, per_slice
+            "    t={:>5.1}%  {} @ ${:.2}",
+            child.time_offset * 100.0,
+            child.quantity,
+            child.price
         );
     }
-
-    fn name(&self) -> &str {
-        "TWAP"
+    let sent = order2.send()?;
+    println!("  Matches sent plan: {}", sent == plan);
+
+    println!("\n--- Timed send ---");
+    order2.send_timed(&SystemClock)?;
+
+    println!("\n--- Simulated partial fill (60%) ---");
+    let twap = ExecutionStrategy::Twap { slices: 4 };
+    let report = twap.execute_with_fill("AAPL", 1_001, 185.50, 0.6)?;
+    let sizes: Vec<String> = report.filled.iter().map(|c| c.quantity.to_string()).collect();
+    println!("  Child fills: {}", sizes.join(", "));
+
+    println!("\n--- Cheapest by estimated cost ---");
+    let candidates = [
+        ExecutionStrategy::Twap { slices: 25 },
+        ExecutionStrategy::Vwap {
+            participation_rate: 0.10,
+        },
+        ExecutionStrategy::Iceberg { visible_qty: 500 },
+    ];
+    for s in &candidates {
+        println!("  {:<8} ${:.2}", s.name(), s.estimated_cost(order2.quantity, order2.price));
     }
-
-    fn clone_box(&self) -> Box<dyn ExecutionStrategyTrait> {
-        Box::new(self.clone())
+    if let Some(best) = order2.cheapest_of(&candidates) {
+        println!("  Cheapest: {}", best.name());
     }
-}
-
-#[derive(Debug, Clone)]
-struct VwapStrategy {
-    participation_rate: f64,
-}
 
-impl ExecutionStrategyTrait for VwapStrategy {
-    fn execute(&self, symbol: &str, quantity: u32, price: f64) {
-        println!(
-            "[VWAP-trait] Executing {}: {} shares @ ${:.2} with {:.0}% participation",
-            symbol, quantity, price, self.participation_rate * 100.0
-        );
+    // Bad parameters are rejected rather than dividing by zero
+    println!("\n--- Invalid TWAP (0 slices) ---");
+    order2.set_strategy(ExecutionStrategy::Twap { slices: 0 });
+    if let Err(e) = order2.send() {
+        println!("  Rejected: {}", e);
     }
 
-    fn name(&self) -> &str {
-        "VWAP"
+    println!("\n--- Invalid VWAP participation ---");
+    for rate in [-0.1, 0.0, 1.5] {
+        order2.set_strategy(ExecutionStrategy::Vwap {
+            participation_rate: rate,
+        });
+        if let Err(e) = order2.preview() {
+            println!("  Rejected: {}", e);
+        }
     }
-
-    fn clone_box(&self) -> Box<dyn ExecutionStrategyTrait> {
-        Box::new(self.clone())
+    let bad_vwap = VwapStrategy {
+        participation_rate: 1.5,
+    };
+    if let Err(e) = bad_vwap.execute("AAPL", 10000, 185.50) {
+        println!("  Rejected (trait): {}", e);
     }
-}
-
-#[derive(Debug)]
-struct TraitOrder {
-    symbol: String,
-    quantity: u32,
-    price: f64,
-    strategy: Box<dyn ExecutionStrategyTrait>,
-}
 
-impl Clone for TraitOrder {
-    fn clone(&self) -> Self {
-        Self {
-            symbol: self.symbol.clone(),
-            quantity: self.quantity,
-            price: self.price,
-            strategy: self.strategy.clone_box(),
+    println!("\n--- Parsing strategies from OMS strings ---");
+    for text in [
+        " TWAP:5 ",
+        "VWAP:0.15",
+        "ICEBERG:500",
+        "PEG:bid,-2",
+        "SNIPER:1000,186.5",
+        "TWAP:five",
+        "POV:0.1",
+    ] {
+        match text.parse::<ExecutionStrategy>() {
+            Ok(s) => println!(
+                "  {:<14} -> {} (round-trips as {})",
+                text,
+                s.description(),
+                s
+            ),
+            Err(e) => println!("  {:<14} -> error: {}", text, e),
         }
     }
-}
 
-impl TraitOrder {
-    fn new(symbol: &str, qty: u32, price: f64, strategy: Box<dyn ExecutionStrategyTrait>) -> Self {
-        Self {
-            symbol: symbol.to_string(),
-            quantity: qty,
-            price,
-            strategy,
-        }
+    println!("\n--- Order lifecycle ---");
+    let mut order3 = Order::new("MSFT", 1000, 415.00, ExecutionStrategy::Twap { slices: 4 });
+    for fill in [400, 400, 400] {
+        order3.apply_fill(fill);
+        println!("  fill {:>4} -> {:?}", fill, order3.status);
     }
-
-    fn set_strategy(&mut self, strategy: Box<dyn ExecutionStrategyTrait>) {
-        self.strategy = strategy;
+    if let Err(e) = order3.send() {
+        println!("  Rejected: {}", e);
     }
-
-    fn send(&self) {
-        println!(
-            "Order: {} {} shares @ ${:.2} using {}",
-            self.symbol,
-            self.quantity,
-            self.price,
-            self.strategy.name()
-        );
-        self.strategy
-            .execute(&self.symbol, self.quantity, self.price);
+    if let Err(e) = order3.cancel() {
+        println!("  Cancel rejected: {}", e);
+    }
+    let mut order4 = Order::new("MSFT", 1000, 415.00, ExecutionStrategy::Twap { slices: 4 });
+    order4.apply_fill(250);
+    if order4.cancel().is_ok() {
+        println!("  cancelled -> {:?}", order4.status);
+    }
+    if let Err(e) = order4.send() {
+        println!("  Rejected: {}", e);
     }
-}
-
-// ============================================================
-// APPROACH 3: Closures (most idiomatic for simple strategies)
-// ============================================================
-
-type StrategyFn = Box<dyn Fn(&str, u32, f64)>;
-
-fn twap_closure(slices: u32) -> StrategyFn {
-    Box::new(move |symbol, qty, price| {
-        let per_slice = qty / slices;
-        println!(
-            "[TWAP-closure] Executing {}: {} shares @ ${:.2} across {} slices ({}/slice)",
-            symbol, qty, price, slices, per_slice
-        );
-    })
-}
-
-fn vwap_closure(participation_rate: f64) -> StrategyFn {
-    Box::new(move |symbol, qty, price| {
-        println!(
-            "[VWAP-closure] Executing {}: {} shares @ ${:.2} with {:.0}% participation",
-            symbol, qty, price, participation_rate * 100.0
-        );
-    })
-}
-
-// ============================================================
-
-fn main() {
-    println!("=== Rust Strategy Pattern: Order Execution ===");
-    println!("========== Approach 1: Enum Dispatch ==========\n");
-
-    let mut order = Order::new("AAPL", 10000, 185.50, ExecutionStrategy::Twap { slices: 5 });
-    order.send();
-
-    println!("\n--- Switching to VWAP ---");
-    order.set_strategy(ExecutionStrategy::Vwap {
-        participation_rate: 0.15,
-    });
-    order.send();
-
-    println!("\n--- Switching to Iceberg ---");
-    order.set_strategy(ExecutionStrategy::Iceberg { visible_qty: 500 });
-    order.send();
-
-    // Clone is trivial — #[derive(Clone)] does everything
-    println!("\n--- Cloning order ---");
-    let mut order2 = order.clone();
-    order2.set_strategy(ExecutionStrategy::Twap { slices: 10 });
-
-    println!("Original:");
-    order.send();
-    println!("Clone (independent):");
-    order2.send();
 
     println!("\n========== Approach 2: Trait Objects ==========\n");
 
     let mut trait_order =
         TraitOrder::new("GOOGL", 5000, 140.25, Box::new(TwapStrategy { slices: 8 }));
-    trait_order.send();
+    trait_order.send()?;
 
     println!("\n--- Switching to VWAP ---");
     trait_order.set_strategy(Box::new(VwapStrategy {
         participation_rate: 0.20,
     }));
-    trait_order.send();
+    trait_order.send()?;
 
     // Cloneable via clone_box
     println!("\n--- Cloning trait order ---");
     let trait_order2 = trait_order.clone();
     println!("Original:");
-    trait_order.send();
+    trait_order.send()?;
     println!("Clone:");
-    trait_order2.send();
+    trait_order2.send()?;
+
+    println!("\n--- Switching to Implementation Shortfall ---");
+    trait_order.set_strategy(Box::new(ImplementationShortfallStrategy { urgency: 0.8 }));
+    trait_order.send()?;
+    println!(
+        "  Estimated cost: ${:.2}",
+        trait_order
+            .strategy
+            .estimated_cost(trait_order.quantity, trait_order.price)
+    );
+
+    println!("\n--- Composite: 40% VWAP then TWAP ---");
+    let composite = CompositeStrategy {
+        stages: vec![
+            Box::new(VwapStrategy {
+                participation_rate: 0.10,
+            }),
+            Box::new(TwapStrategy { slices: 6 }),
+        ],
+        split: 0.4,
+    };
+    let mut composite2 = composite.clone();
+    composite2.split = 0.8;
+    composite.execute("GOOGL", 5000, 140.25)?;
+    println!("Clone with 80% split (independent):");
+    composite2.execute("GOOGL", 5000, 140.25)?;
+
+    println!("\n--- Strategy registry ---");
+    let mut registry = StrategyRegistry::with_defaults();
+    registry.register("IS-Urgent", || {
+        Box::new(ImplementationShortfallStrategy { urgency: 1.0 })
+    });
+    registry.register("Sniper", || {
+        Box::new(SniperStrategy {
+            min_size: 1_000,
+            max_price: 141.00,
+        })
+    });
+    for name in ["Iceberg", "IS-Urgent", "Sniper", "POV"] {
+        match registry.create(name) {
+            Some(strategy) => {
+                trait_order.set_strategy(strategy);
+                trait_order.send()?;
+            }
+            None => println!("  No strategy registered as '{}'", name),
+        }
+    }
+
+    // Strategies configured as the enum drive the trait-object path too
+    println!("\n--- From enum config ---");
+    let configured: ExecutionStrategy = "DARK:1000".parse().expect("valid config string");
+    trait_order.set_strategy(configured.into());
+    trait_order.send()?;
 
     println!("\n========== Approach 3: Closures ==========\n");
 
+    // Closures return the same ChildOrder data as the other approaches
     let strategy = twap_closure(6);
-    strategy("TSLA", 3000, 175.00);
+    let slices = strategy("TSLA", 3000, 175.00);
+    println!("  -> {} child orders of {} shares", slices.len(), slices[0].quantity);
 
     let strategy = vwap_closure(0.25);
+    let slices = strategy("NVDA", 1000, 890.50);
+    println!("  -> {} child orders of {} shares", slices.len(), slices[0].quantity);
+
+    println!("\n--- Sequencing closures: 50% TWAP then VWAP ---");
+    let combined = sequence(twap_closure(4), vwap_closure(0.2), 0.5);
+    let slices = combined("AMZN", 4000, 178.25);
+    let total: u32 = slices.iter().map(|c| c.quantity).sum();
+    println!("  -> {} child orders totalling {} shares", slices.len(), total);
+
+    // Cloning is a refcount bump; both handles run the same closure
+    println!("\n--- Cloning closure strategy ---");
+    let strategy2 = strategy.clone();
     strategy("NVDA", 1000, 890.50);
+    strategy2("AMD", 2000, 160.25);
+
+    #[cfg(feature = "async")]
+    {
+        use async_strategies::{AsyncExecutionStrategy, AsyncTwapStrategy, AsyncVwapStrategy};
+        use std::time::Duration;
+
+        println!("\n========== Async Execution ==========\n");
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("tokio runtime");
+        runtime.block_on(async {
+            let twap = AsyncTwapStrategy {
+                slices: 4,
+                interval: Duration::from_millis(50),
+            };
+            let vwap = AsyncVwapStrategy {
+                participation_rate: 0.25,
+                interval: Duration::from_millis(50),
+            };
+            twap.execute("AAPL", 1000, 185.50).await?;
+            vwap.execute("MSFT", 800, 415.00).await?;
+            Ok::<(), StrategyError>(())
+        })?;
+    }
+
+    Ok(())
 }
 
@@ -1,204 +1,14 @@
 // ============================================================
-// Visitor Pattern in Rust — Financial Instrument Operations
+// Visitor Pattern — Financial Instrument Operations (demo)
 //
-// Rust makes the classic Visitor pattern almost entirely
-// unnecessary. The enum + match combination gives you:
-//
-//   - Exhaustive dispatch (compiler error if you miss a variant)
-//   - No double dispatch indirection
-//   - No accept() boilerplate
-//   - Value semantics via Clone/Copy
-//   - Pattern matching with destructuring (access fields directly)
-//
-// The enum IS the variant. match IS the visit. That's it.
-//
-// We also show a trait-based approach for when you want open
-// extension on the operation side (new instruments without
-// modifying existing operations).
-// ============================================================
-
-use std::fmt;
-
-// --- Instrument types: plain structs ---
-
-#[derive(Debug, Clone)]
-struct Bond {
-    issuer: String,
-    face_value: f64,
-    coupon_rate: f64,
-    maturity_years: u32,
-}
-
-#[derive(Debug, Clone)]
-struct Swap {
-    notional: f64,
-    fixed_rate: f64,
-    tenor_years: u32,
-}
-
-#[derive(Debug, Clone)]
-struct Option {
-    underlying: String,
-    strike: f64,
-    spot: f64,
-    is_call: bool,
-}
-
-// --- The enum IS the polymorphic type ---
-
-#[derive(Debug, Clone)]
-enum Instrument {
-    Bond(Bond),
-    Swap(Swap),
-    Option(Option),
-}
-
-impl fmt::Display for Instrument {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::Bond(b) => write!(
-                f,
-                "Bond({}, {:.0} face, {:.1}% coupon, {}Y)",
-                b.issuer,
-                b.face_value,
-                b.coupon_rate * 100.0,
-                b.maturity_years
-            ),
-            Self::Swap(s) => write!(
-                f,
-                "IRS({:.0} notional, {:.2}% fixed, {}Y)",
-                s.notional,
-                s.fixed_rate * 100.0,
-                s.tenor_years
-            ),
-            Self::Option(o) => write!(
-                f,
-                "{} {}(K={:.2}, S={:.2})",
-                o.underlying,
-                if o.is_call { "Call" } else { "Put" },
-                o.strike,
-                o.spot
-            ),
-        }
-    }
-}
-
-// ============================================================
-// "Visitors" are just functions that match on the enum.
-// No Visitor trait, no accept(), no double dispatch.
-// ============================================================
-
-fn price(inst: &Instrument) -> f64 {
-    match inst {
-        Instrument::Bond(b) => {
-            let mut pv = 0.0;
-            for i in 1..=b.maturity_years {
-                pv += (b.face_value * b.coupon_rate) / 1.05_f64.powi(i as i32);
-            }
-            pv += b.face_value / 1.05_f64.powi(b.maturity_years as i32);
-            pv
-        }
-        Instrument::Swap(s) => {
-            let market_rate = 0.04;
-            s.notional * (s.fixed_rate - market_rate) * s.tenor_years as f64
-        }
-        Instrument::Option(o) => {
-            if o.is_call {
-                (o.spot - o.strike).max(0.0)
-            } else {
-                (o.strike - o.spot).max(0.0)
-            }
-        }
-    }
-}
-
-fn risk_report(inst: &Instrument) {
-    match inst {
-        Instrument::Bond(b) => {
-            let duration = b.maturity_years as f64 * 0.9;
-            let dv01 = b.face_value * duration * 0.0001;
-            println!("  Risk  {:<45}   duration={:.1}, DV01=${:.2}", inst, duration, dv01);
-        }
-        Instrument::Swap(s) => {
-            let dv01 = s.notional * s.tenor_years as f64 * 0.0001;
-            println!("  Risk  {:<45}   DV01=${:.2}", inst, dv01);
-        }
-        Instrument::Option(o) => {
-            let delta = if o.is_call { 0.55 } else { -0.45 };
-            println!("  Risk  {:<45}   delta={:.2}", inst, delta);
-        }
-    }
-}
-
-fn regulatory_report(inst: &Instrument) {
-    let charge = match inst {
-        Instrument::Bond(b) => b.face_value * 0.08,
-        Instrument::Swap(s) => s.notional * 0.05 * s.tenor_years as f64,
-        Instrument::Option(o) => o.spot * 100.0 * 0.10,
-    };
-    println!("  Reg   {:<45}   capital charge=${:.2}", inst, charge);
-}
-
-// ============================================================
-// Trait-based visitor: useful when you want to pass different
-// operations as values (first-class visitors).
+// The types and pricing/execution logic live in the library
+// module src/visitor.rs; this binary only drives them.
 // ============================================================
 
-trait InstrumentVisitor {
-    fn visit_bond(&self, b: &Bond);
-    fn visit_swap(&self, s: &Swap);
-    fn visit_option(&self, o: &Option);
-}
-
-// A single dispatch function replaces accept() on every type
-fn visit(inst: &Instrument, visitor: &dyn InstrumentVisitor) {
-    match inst {
-        Instrument::Bond(b) => visitor.visit_bond(b),
-        Instrument::Swap(s) => visitor.visit_swap(s),
-        Instrument::Option(o) => visitor.visit_option(o),
-    }
-}
-
-struct PricePrinter;
-
-impl InstrumentVisitor for PricePrinter {
-    fn visit_bond(&self, b: &Bond) {
-        let mut pv = 0.0;
-        for i in 1..=b.maturity_years {
-            pv += (b.face_value * b.coupon_rate) / 1.05_f64.powi(i as i32);
-        }
-        pv += b.face_value / 1.05_f64.powi(b.maturity_years as i32);
-        println!(
-            "  [trait] Bond({}, {:.0} face) = ${:.2}",
-            b.issuer, b.face_value, pv
-        );
-    }
-
-    fn visit_swap(&self, s: &Swap) {
-        let npv = s.notional * (s.fixed_rate - 0.04) * s.tenor_years as f64;
-        println!(
-            "  [trait] IRS({:.0} notional, {}Y) = ${:.2} NPV",
-            s.notional, s.tenor_years, npv
-        );
-    }
-
-    fn visit_option(&self, o: &Option) {
-        let intrinsic = if o.is_call {
-            (o.spot - o.strike).max(0.0)
-        } else {
-            (o.strike - o.spot).max(0.0)
-        };
-        println!(
-            "  [trait] {} {}(K={:.2}) = ${:.2} intrinsic",
-            o.underlying,
-            if o.is_call { "Call" } else { "Put" },
-            o.strike,
-            intrinsic
-        );
-    }
-}
+use std::path::Path;
 
-// ============================================================
+use design_patterns::report::Report;
+use design_patterns::visitor::*;
 
 fn main() {
     println!("=== Rust Visitor Pattern: Financial Instruments ===\n");
@@ -207,46 +17,265 @@ fn main() {
     let portfolio = vec![
         Instrument::Bond(Bond {
             issuer: "US-TREASURY".to_string(),
+            currency: Currency::USD,
             face_value: 1_000_000.0,
             coupon_rate: 0.045,
             maturity_years: 10,
+            callable: None,
         }),
         Instrument::Swap(Swap {
+            currency: Currency::USD,
             notional: 5_000_000.0,
             fixed_rate: 0.0375,
             tenor_years: 5,
         }),
+        Instrument::Fra(Fra {
+            currency: Currency::USD,
+            notional: 10_000_000.0,
+            fixed_rate: 0.0425,
+            start_months: 3,
+            end_months: 6,
+        }),
         Instrument::Option(Option {
             underlying: "SPX".to_string(),
+            currency: Currency::USD,
             strike: 4500.0,
             spot: 4550.0,
             is_call: true,
+            vol: 0.18,
+            time_to_expiry: 0.5,
         }),
         Instrument::Option(Option {
             underlying: "AAPL".to_string(),
+            currency: Currency::USD,
             strike: 190.0,
             spot: 185.0,
             is_call: false,
+            vol: 0.25,
+            time_to_expiry: 0.25,
+        }),
+        Instrument::Future(Future {
+            underlying: "ES".to_string(),
+            currency: Currency::USD,
+            contract_price: 4560.0,
+            contract_size: 50.0,
+            num_contracts: 4,
+        }),
+        Instrument::Cds(Cds {
+            reference_entity: "ACME-CORP".to_string(),
+            currency: Currency::USD,
+            notional: 10_000_000.0,
+            spread_bps: 150.0,
+            tenor_years: 5,
+            recovery_rate: 0.4,
+        }),
+        Instrument::FxForward(FxForward {
+            base_ccy: Currency::EUR,
+            quote_ccy: Currency::USD,
+            notional: 2_000_000.0,
+            forward_rate: 1.0850,
+            settle_months: 6,
+        }),
+        Instrument::CapFloor(CapFloor {
+            currency: Currency::USD,
+            notional: 10_000_000.0,
+            strike_rate: 0.045,
+            tenor_years: 5,
+            is_cap: true,
+        }),
+        Instrument::Equity(Equity {
+            ticker: "ACME".to_string(),
+            currency: Currency::USD,
+            shares: 100,
+            price: 50.0,
+        }),
+        Instrument::Warrant(Warrant {
+            underlying: "ACME".to_string(),
+            currency: Currency::USD,
+            strike: 55.0,
+            spot: 50.0,
+            vol: 0.30,
+            time_to_expiry: 3.0,
+            shares_outstanding: 10_000_000,
+            warrants_issued: 2_000_000,
         }),
     ];
 
     // --- Pricing (function that returns a value) ---
     println!("--- Pricing ---");
+    let market = MarketData::demo();
     for inst in &portfolio {
-        let px = price(inst);
-        println!("  Price {:<45} = ${:.2}", inst, px);
+        println!("  Price {}", inst.report());
+    }
+    let matured = Instrument::Bond(Bond {
+        issuer: "MATURED-CO".to_string(),
+        currency: Currency::USD,
+        face_value: 1_000_000.0,
+        coupon_rate: 0.05,
+        maturity_years: 0,
+        callable: None,
+    });
+    println!("  Price {}", matured.report());
+    let pricers: Vec<Box<dyn Pricer>> = vec![
+        Box::new(DiscountCurvePricer { rate: 0.03 }),
+        Box::new(DiscountCurvePricer { rate: 0.07 }),
+    ];
+    for pricer in &pricers {
+        if let Ok(px) = pricer.price(&portfolio[0]) {
+            println!("  Price {} = ${:.2} via {:?}", portfolio[0], px, pricer);
+        }
+    }
+
+    let treasury = &portfolio[0];
+    for days in [0, 90, 180] {
+        if let Ok(dirty) = dirty_price(treasury, &market, days) {
+            let accrued = match treasury {
+                Instrument::Bond(b) => accrued_interest(b, days),
+                _ => 0.0,
+            };
+            println!(
+                "  Dirty {:<45} = ${:.2} ({} days, accrued ${:.2})",
+                treasury, dirty, days, accrued
+            );
+        }
+    }
+
+    match portfolio_value(&portfolio, &market) {
+        Ok(total) => println!("  Portfolio value = ${:.2}", total),
+        Err(e) => println!("  Portfolio value unavailable: {}", e),
+    }
+    let repriced = MarketData {
+        risk_free: 0.06,
+        swap_rate: 0.045,
+        ..MarketData::demo()
+    }
+    .with_spot("SPX", 4_400.0);
+    if let Ok(total) = portfolio_value(&portfolio, &repriced) {
+        println!("  Portfolio value at 6% / 4.5% swaps / SPX 4400 = ${:.2}", total);
+    }
+    let mut flawed = portfolio.clone();
+    flawed.push(matured.clone());
+    if let Instrument::Option(o) = &mut flawed[4] {
+        o.strike = 0.0;
+    }
+    if let Err(errors) = validate_portfolio(&flawed) {
+        for e in &errors {
+            println!("  Invalid {}", e);
+        }
+    }
+
+    let fwd = FxForward {
+        base_ccy: Currency::GBP,
+        quote_ccy: Currency::USD,
+        notional: 1_000_000.0,
+        forward_rate: 1.0,
+        settle_months: 12,
+    };
+    let (spot, quote_rate, base_rate) =
+        fx_forward_market(&fwd, &market).expect("demo market quotes GBP and USD");
+    let at_market = FxForward {
+        forward_rate: implied_forward(spot, quote_rate, base_rate, fwd.years()),
+        ..fwd
+    };
+    println!(
+        "  GBP/USD 12M forward at implied {:.4}: MTM = {:.2}",
+        at_market.forward_rate,
+        fx_forward_mtm(&at_market, spot, quote_rate, base_rate)
+    );
+
+    let cap = portfolio.iter().find_map(|inst| match inst {
+        Instrument::CapFloor(c) => Some(c),
+        _ => None,
+    });
+    if let Some(cap) = cap {
+        for forward in [0.04, 0.05, 0.06] {
+            println!(
+                "  Cap {:.2}% strike at {:.0}% forward = ${:.2}",
+                cap.strike_rate * 100.0,
+                forward * 100.0,
+                cap_floor_pv(cap, forward, market.risk_free)
+            );
+        }
+    }
+
+    // --- Top holdings ---
+    println!("\n--- Top holdings ---");
+    let mut ranked = portfolio.clone();
+    sort_by_price_desc(&mut ranked, &market);
+    for (rank, inst) in ranked.iter().take(3).enumerate() {
+        println!("  #{} {}", rank + 1, inst);
     }
 
     // --- Risk (function with side effects) ---
     println!("\n--- Risk ---");
     for inst in &portfolio {
-        risk_report(inst);
+        println!("  Risk  {:<45}   {}", inst, risk_report(inst, &market));
+    }
+    println!("  Total DV01 = ${:.2}", total_dv01(&portfolio, &market));
+
+    // --- Stress: +100bp rates, -10% spot ---
+    println!("\n--- Stress (+100bp, -10% spot) ---");
+    for inst in &portfolio {
+        let stressed = stress_price(inst, &market, 100.0, -10.0);
+        if let (Ok(base), Ok(stressed)) = (price(inst, &market), stressed) {
+            println!(
+                "  Shock {:<45}   ${:.2} -> ${:.2} ({:+.2})",
+                inst,
+                base,
+                stressed,
+                stressed - base
+            );
+        }
+    }
+
+    // --- Spot bump: options re-marked in place ---
+    println!("\n--- Spot bump (+10%, in place) ---");
+    let mut bumped = portfolio.clone();
+    bump_spot(&mut bumped, 10.0);
+    for (before, after) in portfolio.iter().zip(&bumped) {
+        if before != after {
+            println!("  Bumped {:<45} -> {}", before, after);
+        }
     }
 
     // --- Regulatory ---
     println!("\n--- Regulatory ---");
     for inst in &portfolio {
-        regulatory_report(inst);
+        regulatory_report(inst, &market);
+    }
+    let mut hedged = portfolio.clone();
+    hedged.push(Instrument::Future(Future {
+        underlying: "ES".to_string(),
+        currency: Currency::USD,
+        contract_price: 4560.0,
+        contract_size: 50.0,
+        num_contracts: -3,
+    }));
+    for line in capital_breakdown(&hedged, &market, NettingRule::BySymbol) {
+        if line.net < line.gross {
+            println!(
+                "  Netted {:<10} gross=${:.2} net=${:.2}",
+                line.symbol, line.gross, line.net
+            );
+        }
+    }
+    println!(
+        "  Total capital (with 3 short ES): gross=${:.2}, netted=${:.2}",
+        total_capital_charge(&hedged, &market, NettingRule::None),
+        total_capital_charge(&hedged, &market, NettingRule::BySymbol)
+    );
+
+    // --- Cashflow schedules ---
+    println!("\n--- Cashflows ---");
+    for inst in &portfolio {
+        let flows = cashflows(inst, &market);
+        let total = flows.iter().fold(0.0, |acc, cf| acc + cf.amount);
+        println!("  Flows {:<45}   {} flows, total=${:.2}", inst, flows.len(), total);
+    }
+    if let Some(swap) = portfolio.iter().find(|i| matches!(i, Instrument::Swap(_))) {
+        for cf in cashflows(swap, &market) {
+            println!("    year {}: ${:.2}", cf.period, cf.amount);
+        }
     }
 
     // --- Portfolio is cloneable ---
@@ -254,29 +283,157 @@ fn main() {
     let mut portfolio2 = portfolio.clone();
     portfolio2.push(Instrument::Bond(Bond {
         issuer: "UK-GILT".to_string(),
+        currency: Currency::GBP,
         face_value: 500_000.0,
         coupon_rate: 0.04,
         maturity_years: 5,
+        callable: None,
     }));
     println!("  Original size: {}", portfolio.len());
     println!("  Clone size:    {}", portfolio2.len());
+    let mut doubled = portfolio.clone();
+    doubled.extend(portfolio.iter().take(2).cloned());
+    let before = doubled.len();
+    dedup(&mut doubled);
+    println!("  Dedup:         {} -> {}", before, doubled.len());
+
+    // --- Multi-currency: price natively, convert via FX ---
+    println!("\n--- FX conversion ---");
+    let gilt = &portfolio2[portfolio2.len() - 1];
+    for target in [Currency::GBP, Currency::USD, Currency::EUR, Currency::JPY] {
+        match price_in(gilt, &market, target) {
+            Ok(px) => println!("  Price {:<45} = {:.2} {}", gilt, px, target),
+            Err(e) => println!("  Price {:<45}   in {} unavailable: {}", gilt, target, e),
+        }
+    }
+
+    // --- Slicing by type and issuer ---
+    println!("\n--- Grouping ---");
+    let bonds = filter_bonds(&portfolio2);
+    println!(
+        "  Bonds: {}",
+        bonds.iter().map(|b| b.issuer.as_str()).collect::<Vec<_>>().join(", ")
+    );
+    let counts = count_by_type(&portfolio2);
+    let mut names: Vec<&&str> = counts.keys().collect();
+    names.sort();
+    for name in names {
+        println!("  {:<12} x{}", name, counts[name]);
+    }
+    let groups = group_by_issuer(&portfolio2);
+    let mut keys: Vec<&String> = groups.keys().collect();
+    keys.sort();
+    for key in keys {
+        println!("  {:<12} {} instrument(s)", key, groups[key].len());
+    }
+
+    // --- Portfolio loaded from CSV ---
+    println!("\n--- Loaded from CSV ---");
+    let csv_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/portfolio.csv");
+    match load_portfolio_csv(&csv_path) {
+        Ok(loaded) => {
+            for inst in &loaded {
+                println!("  Loaded {}", inst);
+            }
+        }
+        Err(e) => println!("  {}", e),
+    }
+    for bad in [
+        "Bond,USD,XYZ,1000000,abc,5",
+        "Swaption,USD,1000000",
+        "Swap,USD,5000000,0.04",
+        "Swap,CHF,5000000,0.04,5",
+    ] {
+        if let Err(e) = parse_portfolio_csv(bad) {
+            println!("  Rejected '{}': {}", bad, e);
+        }
+    }
+
+    // --- JSON round trip (serde feature) ---
+    #[cfg(feature = "serde")]
+    {
+        println!("\n--- JSON round trip ---");
+        let json = serde_json::to_string_pretty(&portfolio).expect("instruments serialize");
+        let loaded: Vec<Instrument> = serde_json::from_str(&json).expect("instruments deserialize");
+        println!("  {}", json.lines().take(8).collect::<Vec<_>>().join("\n  "));
+        println!("  ...");
+        println!("  Round trip equal: {}", loaded == portfolio);
+    }
+
+    // --- Spread analytics: straight vs callable ---
+    println!("\n--- Spreads over curve ---");
+    let curve = DiscountCurve {
+        zero_rates: vec![0.040, 0.042, 0.044, 0.045, 0.046],
+    };
+    let straight = Bond {
+        issuer: "ACME-CORP".to_string(),
+        currency: Currency::USD,
+        face_value: 1_000_000.0,
+        coupon_rate: 0.06,
+        maturity_years: 7,
+        callable: None,
+    };
+    let callable = Bond {
+        callable: Some(CallSchedule {
+            first_call_year: 3,
+            call_price: 1_000_000.0,
+        }),
+        ..straight.clone()
+    };
+    let market_price = 1_010_000.0;
+    if let Some(z) = z_spread(&straight, market_price, &curve) {
+        println!("  {} straight   z-spread = {:.1}bp", straight.issuer, z * 10_000.0);
+    }
+    if let Some(oas) = option_adjusted_spread(&straight, market_price, &curve) {
+        println!("  {} straight   OAS      = {:.1}bp", straight.issuer, oas * 10_000.0);
+    }
+    if let Some(oas) = option_adjusted_spread(&callable, market_price, &curve) {
+        println!("  {} callable   OAS      = {:.1}bp", callable.issuer, oas * 10_000.0);
+    }
+    match yield_to_maturity(&straight, market_price) {
+        Ok(ytm) => println!("  {} straight   YTM      = {:.3}%", straight.issuer, ytm * 100.0),
+        Err(e) => println!("  {} straight   YTM      unavailable: {}", straight.issuer, e),
+    }
+    let at_five = bond_pv(&straight, 0.05);
+    if let Ok(ytm) = yield_to_maturity(&straight, at_five) {
+        println!("  Priced at 5.000% (${:.2}), solved YTM = {:.3}%", at_five, ytm * 100.0);
+    }
+    if let Err(e) = yield_to_maturity(&straight, -1.0) {
+        println!("  YTM at a negative price: {}", e);
+    }
 
     // --- Trait-based visitor ---
     println!("\n--- Trait-based visitor ---");
-    let pricer = PricePrinter;
+    let pricer = PricePrinter::default();
     for inst in &portfolio {
         visit(inst, &pricer);
     }
+    println!("  Swaps with the float leg at 5%:");
+    let pricer = PricePrinter {
+        market: MarketData {
+            swap_rate: 0.05,
+            ..MarketData::demo()
+        },
+    };
+    for inst in portfolio.iter().filter(|i| matches!(i, Instrument::Swap(_))) {
+        visit(inst, &pricer);
+    }
+
+    // --- Folding visitor ---
+    println!("\n--- Folding visitor ---");
+    let notional = fold(&portfolio, 0.0, &NotionalTotal);
+    println!("  Total notional = {:.0}", notional);
 
     // --- Exhaustiveness ---
-    // If you add a new variant to the Instrument enum (e.g., FRA)
+    // If you add a new variant to the Instrument enum (as Fra was)
     // and forget to handle it in ANY match, the compiler emits:
-    //   error[E0004]: non-exhaustive patterns: `Instrument::FRA(_)` not covered
+    //   error[E0004]: non-exhaustive patterns: `Instrument::Fra(_)` not covered
     // This is a hard error, not a warning. You cannot ship the code.
 
     println!("\n  sizeof Instrument: {} bytes", std::mem::size_of::<Instrument>());
     println!(
-        "  sizeof Vec<Instrument> (4 items): {} bytes on stack + {} bytes on heap",
+        "  sizeof Vec<Instrument> ({} items): {} bytes on stack + {} bytes on heap",
+        portfolio.len(),
         std::mem::size_of::<Vec<Instrument>>(),
         std::mem::size_of::<Instrument>() * portfolio.len()
     );
@@ -0,0 +1,59 @@
+// ============================================================
+// Clock — injectable time source.
+//
+// Command history stamps trades with it and strategy execution
+// times itself with it. Production code passes SystemClock;
+// tests pass a FixedClock so timestamps and elapsed times are
+// deterministic.
+// ============================================================
+
+use std::cell::Cell;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+pub trait Clock: fmt::Debug {
+    fn now(&self) -> SystemTime;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+// Reports the same instant until explicitly advanced, or moves
+// forward by a fixed step after every reading when built with_step
+#[derive(Debug)]
+pub struct FixedClock {
+    pub now: Cell<SystemTime>,
+    pub step: Duration,
+}
+
+impl FixedClock {
+    pub fn new(now: SystemTime) -> Self {
+        Self {
+            now: Cell::new(now),
+            step: Duration::ZERO,
+        }
+    }
+
+    pub fn with_step(mut self, step: Duration) -> Self {
+        self.step = step;
+        self
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        let now = self.now.get();
+        self.advance(self.step);
+        now
+    }
+}
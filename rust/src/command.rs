@@ -0,0 +1,2073 @@
+// ============================================================
+// Command Pattern in Rust — Trade Management with Undo/Redo
+//
+// Two approaches:
+//
+// 1. Enum commands (closed set)
+//    - Commands are pure data, #[derive(Clone)]
+//    - Execute/undo are match arms in free functions
+//    - History is Vec<TradeAction> — trivially cloneable
+//    - Perfect for event sourcing and audit trails
+//
+// 2. Trait objects (open extension)
+//    - Box<dyn Command> with clone_box for value semantics
+//    - New command types don't require modifying existing code
+//    - Slightly more ceremony than enum approach
+//
+// Rust's ownership system gives a unique advantage here:
+// the borrow checker ensures commands can't hold dangling
+// references to the portfolio. Commands store data, not
+// references — the portfolio is passed explicitly to execute/undo.
+// ============================================================
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+#[cfg(feature = "serde")]
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::clock::{Clock, SystemClock};
+use crate::report::Report;
+use crate::visitor::Equity;
+
+// --- Trade errors: checked before any state is touched ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TradeError {
+    InsufficientFunds { needed: f64, available: f64 },
+    InsufficientShares { held: i32, requested: i32 },
+    NothingToUndo,
+    ExceedsTrade { requested: i32, executed: i32 },
+    LimitBreached(LimitBreach),
+    RiskRejected(RiskReject),
+    InvalidRatio(f64),
+    InvalidQuantity(i32),
+}
+
+impl fmt::Display for TradeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InsufficientFunds { needed, available } => write!(
+                f,
+                "insufficient funds: need ${:.2}, have ${:.2}",
+                needed, available
+            ),
+            Self::InsufficientShares { held, requested } => write!(
+                f,
+                "insufficient shares: selling {}, hold {}",
+                requested, held
+            ),
+            Self::NothingToUndo => write!(f, "no executed trade to undo"),
+            Self::ExceedsTrade {
+                requested,
+                executed,
+            } => write!(
+                f,
+                "cannot reverse {} shares of a {}-share trade",
+                requested, executed
+            ),
+            Self::LimitBreached(breach) => write!(f, "limit breached: {}", breach),
+            Self::RiskRejected(reject) => write!(f, "risk check failed: {}", reject),
+            Self::InvalidRatio(ratio) => write!(f, "invalid split ratio {}", ratio),
+            Self::InvalidQuantity(qty) => write!(f, "quantity must be positive, got {}", qty),
+        }
+    }
+}
+
+impl std::error::Error for TradeError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LimitBreach {
+    Position {
+        symbol: String,
+        limit: i32,
+        resulting: i32,
+    },
+    GrossExposure {
+        limit: f64,
+        resulting: f64,
+    },
+}
+
+impl fmt::Display for LimitBreach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Position {
+                symbol,
+                limit,
+                resulting,
+            } => write!(
+                f,
+                "{} position {} exceeds cap {}",
+                symbol, resulting, limit
+            ),
+            Self::GrossExposure { limit, resulting } => write!(
+                f,
+                "gross exposure ${:.2} exceeds cap ${:.2}",
+                resulting, limit
+            ),
+        }
+    }
+}
+
+// --- Receiver: Portfolio ---
+
+// The symbol's average as it was before a fill and the P&L the
+// fill realized, so undo can restore both exactly. Averages can't
+// be recovered arithmetically once a position is closed, hence the
+// log. Realized P&L is kept as the fill's own share so undoing it
+// leaves P&L realized by other symbols' fills in place.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BasisUndo {
+    pub symbol: String,
+    pub avg_price: Option<f64>,
+    pub realized_pnl: f64,
+    pub commission: f64, // fee charged on the fill, refunded as charged
+}
+
+// Checkpoint of portfolio state, restored directly instead of
+// replaying undos
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub positions: HashMap<String, i32>,
+    pub cash: f64,
+    pub avg_price: HashMap<String, f64>,
+    pub realized_pnl: f64,
+    pub basis_log: Vec<BasisUndo>,
+}
+
+// What would have to change to turn one portfolio into another:
+// per-symbol share deltas (sorted, zeros omitted) and the cash delta
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortfolioDiff {
+    pub positions: Vec<(String, i32)>,
+    pub cash: f64,
+}
+
+impl PortfolioDiff {
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty() && self.cash.abs() < 1e-9
+    }
+}
+
+impl fmt::Display for PortfolioDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no differences");
+        }
+        let mut parts: Vec<String> = self
+            .positions
+            .iter()
+            .map(|(sym, delta)| format!("{} {:+}", sym, delta))
+            .collect();
+        if self.cash.abs() >= 1e-9 {
+            parts.push(format!("cash {:+.2}", self.cash));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Portfolio {
+    pub positions: HashMap<String, i32>,
+    pub cash: f64,
+    pub avg_price: HashMap<String, f64>, // average cost per share of the open position
+    pub realized_pnl: f64,
+    pub basis_log: Vec<BasisUndo>,
+    pub allow_short: bool, // false for cash accounts
+    pub limits: HashMap<String, i32>, // cap on |position| per symbol
+    pub max_gross_exposure: Option<f64>,
+    pub commission_per_share: f64, // charged on every fill, refunded on undo
+}
+
+impl Portfolio {
+    pub fn new(cash: f64) -> Self {
+        Self {
+            positions: HashMap::new(),
+            cash,
+            avg_price: HashMap::new(),
+            realized_pnl: 0.0,
+            basis_log: Vec::new(),
+            allow_short: true,
+            limits: HashMap::new(),
+            max_gross_exposure: None,
+            commission_per_share: 0.0,
+        }
+    }
+
+    pub fn with_commission(mut self, commission_per_share: f64) -> Self {
+        self.commission_per_share = commission_per_share;
+        self
+    }
+
+    pub fn commission(&self, qty: i32) -> f64 {
+        qty.abs() as f64 * self.commission_per_share
+    }
+
+    pub fn with_position_limit(mut self, symbol: &str, max_shares: i32) -> Self {
+        self.limits.insert(symbol.to_string(), max_shares);
+        self
+    }
+
+    pub fn with_max_gross_exposure(mut self, limit: f64) -> Self {
+        self.max_gross_exposure = Some(limit);
+        self
+    }
+
+    // Gross exposure is measured at cost: other holdings at their
+    // average price, the traded symbol's resulting position at the
+    // fill price. Runs before any mutation.
+    pub fn check_limits(
+        &self,
+        symbol: &str,
+        signed_qty: i32,
+        price: f64,
+    ) -> Result<(), TradeError> {
+        let after = self.position(symbol) + signed_qty;
+        if let Some(&limit) = self.limits.get(symbol) {
+            if after.abs() > limit {
+                return Err(TradeError::LimitBreached(LimitBreach::Position {
+                    symbol: symbol.to_string(),
+                    limit,
+                    resulting: after,
+                }));
+            }
+        }
+        if let Some(limit) = self.max_gross_exposure {
+            let others: f64 = self
+                .positions
+                .iter()
+                .filter(|(sym, _)| sym.as_str() != symbol)
+                .map(|(sym, qty)| {
+                    qty.abs() as f64 * self.avg_price.get(sym).copied().unwrap_or(0.0)
+                })
+                .sum();
+            let resulting = others + after.abs() as f64 * price;
+            if resulting > limit {
+                return Err(TradeError::LimitBreached(LimitBreach::GrossExposure {
+                    limit,
+                    resulting,
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn with_short_selling(mut self, allowed: bool) -> Self {
+        self.allow_short = allowed;
+        self
+    }
+
+    // --- Read interface, independent of the field layout ---
+
+    pub fn position(&self, symbol: &str) -> i32 {
+        self.positions.get(symbol).copied().unwrap_or(0)
+    }
+
+    // Blended entry price of the open position; None once flat
+    pub fn avg_price(&self, symbol: &str) -> Option<f64> {
+        self.avg_price.get(symbol).copied()
+    }
+
+    // Symbols with an open position, sorted for stable output
+    pub fn symbols(&self) -> Vec<&str> {
+        let mut symbols: Vec<&str> = self
+            .positions
+            .iter()
+            .filter(|(_, qty)| **qty != 0)
+            .map(|(sym, _)| sym.as_str())
+            .collect();
+        symbols.sort_unstable();
+        symbols
+    }
+
+    // Sum of |position| * mark; symbols without a mark are skipped
+    pub fn gross_exposure(&self, marks: &HashMap<String, f64>) -> f64 {
+        self.symbols()
+            .into_iter()
+            .filter_map(|sym| Some((self.position(sym) as f64 * marks.get(sym)?).abs()))
+            .sum()
+    }
+
+    // Net liquidation value: cash plus every open position at its
+    // mark. A symbol without a mark is carried at its average cost.
+    pub fn mark_to_market(&self, marks: &HashMap<String, f64>) -> f64 {
+        let positions: f64 = self
+            .symbols()
+            .into_iter()
+            .map(|sym| {
+                let mark = marks
+                    .get(sym)
+                    .copied()
+                    .or_else(|| self.avg_price(sym))
+                    .unwrap_or(0.0);
+                self.position(sym) as f64 * mark
+            })
+            .sum();
+        self.cash + positions
+    }
+
+    // Drains every tick the feed has ready into `into`; a later tick
+    // for a symbol overwrites the earlier one. Non-finite prices are
+    // dropped so one bad print can't poison the marks.
+    pub fn mark_from_feed(&self, feed: &mut dyn PriceFeed, into: &mut HashMap<String, f64>) {
+        while let Some((symbol, price)) = feed.next_mark() {
+            if price.is_finite() {
+                into.insert(symbol, price);
+            }
+        }
+    }
+
+    // Deltas are other minus self, so applying them to self yields other
+    pub fn diff(&self, other: &Portfolio) -> PortfolioDiff {
+        let symbols: HashSet<&str> = self
+            .symbols()
+            .into_iter()
+            .chain(other.symbols())
+            .collect();
+        let mut positions: Vec<(String, i32)> = symbols
+            .into_iter()
+            .map(|sym| (sym.to_string(), other.position(sym) - self.position(sym)))
+            .filter(|(_, delta)| *delta != 0)
+            .collect();
+        positions.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        PortfolioDiff {
+            positions,
+            cash: other.cash - self.cash,
+        }
+    }
+
+    // Market orders that move each symbol to its target share of net
+    // liquidation value (mark_to_market). Held symbols without a
+    // target are sold out; symbols without a mark are skipped, as
+    // they can't be sized. Each target share is costed at its mark
+    // plus commission, out of NAV less the commission to close every
+    // marked holding, and counts round toward zero, so the orders
+    // never outspend the account. Sells come first, so their
+    // proceeds fund the buys when executed in order.
+    pub fn rebalance_to(
+        &self,
+        targets: &HashMap<String, f64>,
+        marks: &HashMap<String, f64>,
+    ) -> Vec<Box<dyn Command>> {
+        let nav = self.mark_to_market(marks);
+        let mut symbols: Vec<&str> = targets
+            .keys()
+            .map(String::as_str)
+            .chain(self.symbols())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        symbols.sort_unstable();
+        let fee = self.commission_per_share;
+        let held: i32 = symbols
+            .iter()
+            .filter(|sym| marks.contains_key(**sym))
+            .map(|sym| self.position(sym).abs())
+            .sum();
+        let budget = (nav - fee * held as f64).max(0.0);
+
+        let mut sells: Vec<Box<dyn Command>> = Vec::new();
+        let mut buys: Vec<Box<dyn Command>> = Vec::new();
+        for sym in symbols {
+            let Some(&price) = marks.get(sym) else {
+                continue;
+            };
+            let weight = targets.get(sym).copied().unwrap_or(0.0);
+            let target = (weight * budget / (price + fee)) as i32;
+            let delta = target - self.position(sym);
+            if delta < 0 {
+                sells.push(Box::new(MarketSell {
+                    symbol: sym.to_string(),
+                    quantity: -delta,
+                    price,
+                }));
+            } else if delta > 0 {
+                buys.push(Box::new(MarketBuy {
+                    symbol: sym.to_string(),
+                    quantity: delta,
+                    price,
+                }));
+            }
+        }
+        sells.extend(buys);
+        sells
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            positions: self.positions.clone(),
+            cash: self.cash,
+            avg_price: self.avg_price.clone(),
+            realized_pnl: self.realized_pnl,
+            basis_log: self.basis_log.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snap: Snapshot) {
+        self.positions = snap.positions;
+        self.cash = snap.cash;
+        self.avg_price = snap.avg_price;
+        self.realized_pnl = snap.realized_pnl;
+        self.basis_log = snap.basis_log;
+    }
+
+    // All-or-nothing batch: on the first failure the portfolio is
+    // restored to its state before the batch
+    pub fn apply(&mut self, cmds: &[Box<dyn Command>]) -> Result<(), TradeError> {
+        let checkpoint = self.snapshot();
+        for cmd in cmds {
+            if let Err(e) = cmd.execute(self) {
+                self.restore(checkpoint);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    // Average-cost accounting for a signed fill (buys positive).
+    // Adding to a position blends the average; reducing it realizes
+    // P&L against the average; flipping through flat starts a new
+    // average at the fill price. Call before updating `positions`.
+    pub fn record_fill(&mut self, symbol: &str, signed_qty: i32, price: f64, commission: f64) {
+        let held = self.position(symbol);
+        let avg = self.avg_price.get(symbol).copied();
+        let mut realized = 0.0;
+
+        let after = held + signed_qty;
+        match avg {
+            Some(avg) if held != 0 && held.signum() != signed_qty.signum() => {
+                let closed = signed_qty.abs().min(held.abs());
+                realized = closed as f64 * (price - avg) * held.signum() as f64;
+                self.realized_pnl += realized;
+                if after == 0 {
+                    self.avg_price.remove(symbol);
+                } else if after.signum() != held.signum() {
+                    self.avg_price.insert(symbol.to_string(), price);
+                }
+            }
+            _ => {
+                let old = avg.unwrap_or(0.0) * held.abs() as f64;
+                let blended = (old + price * signed_qty.abs() as f64) / after.abs() as f64;
+                self.avg_price.insert(symbol.to_string(), blended);
+            }
+        }
+        self.basis_log.push(BasisUndo {
+            symbol: symbol.to_string(),
+            avg_price: avg,
+            realized_pnl: realized,
+            commission,
+        });
+    }
+
+    // Reverses the newest fill of `symbol` still in the log and
+    // returns its entry. Fills of other symbols made since are left
+    // alone; with no entry for the symbol the basis is left as it is.
+    pub fn restore_basis(&mut self, symbol: &str) -> Option<BasisUndo> {
+        let i = self.basis_log.iter().rposition(|u| u.symbol == symbol)?;
+        let undo = self.basis_log.remove(i);
+        match undo.avg_price {
+            Some(avg) => self.avg_price.insert(undo.symbol.clone(), avg),
+            None => self.avg_price.remove(&undo.symbol),
+        };
+        self.realized_pnl -= undo.realized_pnl;
+        Some(undo)
+    }
+
+    // Fee to refund when reversing the newest fill of `symbol`: what
+    // that fill was charged, or today's rate if it left no entry
+    pub fn refundable_commission(&self, symbol: &str, qty: i32) -> f64 {
+        self.basis_log
+            .iter()
+            .rfind(|u| u.symbol == symbol)
+            .map_or_else(|| self.commission(qty), |u| u.commission)
+    }
+
+    // Histories claim the entries a command's fills added (everything
+    // past `mark`, the log length before it ran) and hand them back
+    // just before undoing it, so each undo restores its own fills no
+    // matter what else traded on the portfolio in between
+    pub fn take_basis_since(&mut self, mark: usize) -> Vec<BasisUndo> {
+        self.basis_log.split_off(mark.min(self.basis_log.len()))
+    }
+
+    pub fn put_back_basis(&mut self, entries: &[BasisUndo]) {
+        self.basis_log.extend_from_slice(entries);
+    }
+
+    pub fn unrealized_pnl(&self, marks: &HashMap<String, f64>) -> f64 {
+        self.positions
+            .iter()
+            .filter_map(|(sym, qty)| {
+                let mark = marks.get(sym)?;
+                let avg = self.avg_price.get(sym)?;
+                Some(*qty as f64 * (mark - avg))
+            })
+            .sum()
+    }
+
+    pub fn ensure_cash(&self, needed: f64) -> Result<(), TradeError> {
+        if needed > self.cash {
+            return Err(TradeError::InsufficientFunds {
+                needed,
+                available: self.cash,
+            });
+        }
+        Ok(())
+    }
+
+    // Quantities are share counts, so zero and negative fills are
+    // rejected; the side says which way the position moves
+    pub fn buy(&mut self, symbol: &str, qty: i32, price: f64) -> Result<(), TradeError> {
+        if qty <= 0 {
+            return Err(TradeError::InvalidQuantity(qty));
+        }
+        let fee = self.commission(qty);
+        let needed = qty as f64 * price + fee;
+        self.ensure_cash(needed)?;
+        self.check_limits(symbol, qty, price)?;
+        self.record_fill(symbol, qty, price, fee);
+        *self.positions.entry(symbol.to_string()).or_insert(0) += qty;
+        self.cash -= needed;
+        println!(
+            "  [EXEC] BUY  {} {} @ ${:.2}  (cash: ${:.2})",
+            qty, symbol, price, self.cash
+        );
+        Ok(())
+    }
+
+    pub fn sell(&mut self, symbol: &str, qty: i32, price: f64) -> Result<(), TradeError> {
+        if qty <= 0 {
+            return Err(TradeError::InvalidQuantity(qty));
+        }
+        let held = self.position(symbol);
+        if !self.allow_short && qty > held {
+            return Err(TradeError::InsufficientShares {
+                held,
+                requested: qty,
+            });
+        }
+        self.check_limits(symbol, -qty, price)?;
+        let fee = self.commission(qty);
+        self.record_fill(symbol, -qty, price, fee);
+        *self.positions.entry(symbol.to_string()).or_insert(0) -= qty;
+        self.cash += qty as f64 * price - fee;
+        println!(
+            "  [EXEC] SELL {} {} @ ${:.2}  (cash: ${:.2})",
+            qty, symbol, price, self.cash
+        );
+        Ok(())
+    }
+
+    pub fn reverse_buy(&mut self, symbol: &str, qty: i32, price: f64) {
+        let fee = self.refundable_commission(symbol, qty);
+        self.restore_basis(symbol);
+        *self.positions.entry(symbol.to_string()).or_insert(0) -= qty;
+        self.cash += qty as f64 * price + fee;
+        println!(
+            "  [UNDO] BUY  {} {} @ ${:.2} reversed  (cash: ${:.2})",
+            qty, symbol, price, self.cash
+        );
+    }
+
+    pub fn reverse_sell(&mut self, symbol: &str, qty: i32, price: f64) {
+        let fee = self.refundable_commission(symbol, qty);
+        self.restore_basis(symbol);
+        *self.positions.entry(symbol.to_string()).or_insert(0) += qty;
+        self.cash -= qty as f64 * price - fee;
+        println!(
+            "  [UNDO] SELL {} {} @ ${:.2} reversed  (cash: ${:.2})",
+            qty, symbol, price, self.cash
+        );
+    }
+
+    // --- Corporate actions ---
+
+    pub fn pay_dividend(&mut self, symbol: &str, per_share: f64) {
+        let shares = self.position(symbol);
+        self.cash += shares as f64 * per_share;
+        println!(
+            "  [CORP] DIV  {} ${:.2} x {} shares  (cash: ${:.2})",
+            symbol, per_share, shares, self.cash
+        );
+    }
+
+    pub fn reverse_dividend(&mut self, symbol: &str, per_share: f64) {
+        let shares = self.position(symbol);
+        self.cash -= shares as f64 * per_share;
+        println!(
+            "  [UNDO] DIV  {} ${:.2} x {} shares reversed  (cash: ${:.2})",
+            symbol, per_share, shares, self.cash
+        );
+    }
+
+    // Fractional shares are dropped (rounded toward zero); the
+    // reversal rounds away from zero, which recovers the original
+    // count exactly for forward splits (ratio >= 1). Average cost
+    // scales inversely so cost basis is preserved. A ratio that is
+    // not a positive finite number is rejected before any change.
+    pub fn split(&mut self, symbol: &str, ratio: f64) -> Result<(), TradeError> {
+        if !(ratio.is_finite() && ratio > 0.0) {
+            return Err(TradeError::InvalidRatio(ratio));
+        }
+        let Some(qty) = self.positions.get_mut(symbol) else {
+            return Ok(());
+        };
+        *qty = (*qty as f64 * ratio).trunc() as i32;
+        if let Some(avg) = self.avg_price.get_mut(symbol) {
+            *avg /= ratio;
+        }
+        println!("  [CORP] SPLIT {} {}:1 -> {} shares", symbol, ratio, qty);
+        Ok(())
+    }
+
+    pub fn reverse_split(&mut self, symbol: &str, ratio: f64) {
+        let Some(qty) = self.positions.get_mut(symbol) else {
+            return;
+        };
+        let restored = *qty as f64 / ratio;
+        *qty = (restored.abs().ceil() * restored.signum()) as i32;
+        if let Some(avg) = self.avg_price.get_mut(symbol) {
+            *avg *= ratio;
+        }
+        println!("  [UNDO] SPLIT {} {}:1 reversed -> {} shares", symbol, ratio, qty);
+    }
+
+    // Market value per sector; symbols missing from `sectors` land
+    // in "Unknown", symbols without a mark are skipped.
+    pub fn exposure_by_sector(
+        &self,
+        sectors: &HashMap<String, String>,
+        marks: &HashMap<String, f64>,
+    ) -> HashMap<String, f64> {
+        let mut exposure = HashMap::new();
+        for (sym, qty) in &self.positions {
+            let Some(mark) = marks.get(sym) else {
+                continue;
+            };
+            let sector = sectors.get(sym).map_or("Unknown", String::as_str);
+            *exposure.entry(sector.to_string()).or_insert(0.0) += *qty as f64 * mark;
+        }
+        exposure
+    }
+}
+
+// Cash, open positions in symbol order, then realized P&L
+impl Report for Portfolio {
+    fn report(&self) -> String {
+        let mut lines = vec![
+            "  Portfolio:".to_string(),
+            format!("    Cash: ${:.2}", self.cash),
+        ];
+        for sym in self.symbols() {
+            let qty = self.position(sym);
+            lines.push(match self.avg_price(sym) {
+                Some(avg) => format!("    {}: {} shares (avg ${:.2})", sym, qty, avg),
+                None => format!("    {}: {} shares", sym, qty),
+            });
+        }
+        lines.push(format!("    Realized P&L: ${:.2}", self.realized_pnl));
+        lines.join("\n")
+    }
+}
+
+// --- Concentration metrics over marked positions ---
+
+// Herfindahl-Hirschman index: sum of squared weights, where each
+// weight is a position's share of gross market value. Ranges from
+// 1/n for an equal-weighted book to 1.0 for a single name.
+pub fn herfindahl_index(portfolio: &Portfolio, marks: &HashMap<String, f64>) -> f64 {
+    let values: Vec<f64> = portfolio
+        .positions
+        .iter()
+        .filter_map(|(sym, qty)| marks.get(sym).map(|px| (*qty as f64 * px).abs()))
+        .filter(|v| *v > 0.0)
+        .collect();
+    let gross: f64 = values.iter().sum();
+    if gross == 0.0 {
+        return 0.0;
+    }
+    values.iter().map(|v| (v / gross).powi(2)).sum()
+}
+
+// Inverse HHI: the number of equally-weighted positions that would
+// give the same concentration. An empty book has zero.
+pub fn effective_positions(portfolio: &Portfolio, marks: &HashMap<String, f64>) -> f64 {
+    let hhi = herfindahl_index(portfolio, marks);
+    if hhi == 0.0 {
+        0.0
+    } else {
+        1.0 / hhi
+    }
+}
+
+// --- Price feeds: marks streamed in for live P&L ---
+
+// None means nothing more is ready, not necessarily end of stream
+pub trait PriceFeed: fmt::Debug {
+    fn next_mark(&mut self) -> Option<(String, f64)>;
+}
+
+// Replays a fixed list of ticks in order, for tests and demos
+#[derive(Debug, Clone, Default)]
+pub struct VecFeed {
+    pub ticks: VecDeque<(String, f64)>,
+}
+
+impl VecFeed {
+    pub fn new(ticks: &[(&str, f64)]) -> Self {
+        Self {
+            ticks: ticks
+                .iter()
+                .map(|(symbol, price)| (symbol.to_string(), *price))
+                .collect(),
+        }
+    }
+}
+
+impl PriceFeed for VecFeed {
+    fn next_mark(&mut self) -> Option<(String, f64)> {
+        self.ticks.pop_front()
+    }
+}
+
+// ============================================================
+// APPROACH 1: Enum Commands (closed set)
+// ============================================================
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TradeAction {
+    Buy {
+        symbol: String,
+        quantity: i32,
+        price: f64,
+        // Free-text audit reason
+        #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+        tag: Option<String>,
+    },
+    Sell {
+        symbol: String,
+        quantity: i32,
+        price: f64,
+        #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+        tag: Option<String>,
+    },
+}
+
+impl fmt::Display for TradeAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Buy {
+                symbol,
+                quantity,
+                price,
+                ..
+            } => write!(f, "BUY {} {} @ ${:.2}", quantity, symbol, price)?,
+            Self::Sell {
+                symbol,
+                quantity,
+                price,
+                ..
+            } => write!(f, "SELL {} {} @ ${:.2}", quantity, symbol, price)?,
+        }
+        match self.tag() {
+            Some(tag) => write!(f, " (tag: {})", tag),
+            None => Ok(()),
+        }
+    }
+}
+
+// Bridge from the visitor module: an equity holding becomes the
+// trade that opens it, a buy for long shares and a sell for short
+impl From<&Equity> for TradeAction {
+    fn from(e: &Equity) -> Self {
+        let (symbol, quantity, price) = (e.ticker.clone(), e.shares.abs(), e.price);
+        if e.shares >= 0 {
+            TradeAction::Buy {
+                symbol,
+                quantity,
+                price,
+                tag: None,
+            }
+        } else {
+            TradeAction::Sell {
+                symbol,
+                quantity,
+                price,
+                tag: None,
+            }
+        }
+    }
+}
+
+impl TradeAction {
+    pub fn tagged(mut self, reason: &str) -> Self {
+        match &mut self {
+            Self::Buy { tag, .. } | Self::Sell { tag, .. } => *tag = Some(reason.to_string()),
+        }
+        self
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        match self {
+            Self::Buy { tag, .. } | Self::Sell { tag, .. } => tag.as_deref(),
+        }
+    }
+
+    pub fn quantity(&self) -> i32 {
+        match self {
+            Self::Buy { quantity, .. } | Self::Sell { quantity, .. } => *quantity,
+        }
+    }
+
+    pub fn with_quantity(&self, qty: i32) -> Self {
+        let mut action = self.clone();
+        match &mut action {
+            Self::Buy { quantity, .. } | Self::Sell { quantity, .. } => *quantity = qty,
+        }
+        action
+    }
+
+    pub fn execute(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        match self {
+            Self::Buy {
+                symbol,
+                quantity,
+                price,
+                ..
+            } => portfolio.buy(symbol, *quantity, *price),
+            Self::Sell {
+                symbol,
+                quantity,
+                price,
+                ..
+            } => portfolio.sell(symbol, *quantity, *price),
+        }
+    }
+
+    // Folds two same-side fills of one symbol into a single action
+    // at the quantity-weighted average price. Fills with different
+    // tags stay separate so no audit reason is lost.
+    pub fn merge(&self, other: &TradeAction) -> Option<TradeAction> {
+        if self.tag() != other.tag() {
+            return None;
+        }
+        let blend = |q1: i32, p1: f64, q2: i32, p2: f64| {
+            let qty = q1 + q2;
+            (qty, (q1 as f64 * p1 + q2 as f64 * p2) / qty as f64)
+        };
+        match (self, other) {
+            (
+                Self::Buy {
+                    symbol,
+                    quantity: q1,
+                    price: p1,
+                    tag,
+                },
+                Self::Buy {
+                    symbol: other_symbol,
+                    quantity: q2,
+                    price: p2,
+                    ..
+                },
+            ) if symbol == other_symbol => {
+                let (quantity, price) = blend(*q1, *p1, *q2, *p2);
+                Some(Self::Buy {
+                    symbol: symbol.clone(),
+                    quantity,
+                    price,
+                    tag: tag.clone(),
+                })
+            }
+            (
+                Self::Sell {
+                    symbol,
+                    quantity: q1,
+                    price: p1,
+                    tag,
+                },
+                Self::Sell {
+                    symbol: other_symbol,
+                    quantity: q2,
+                    price: p2,
+                    ..
+                },
+            ) if symbol == other_symbol => {
+                let (quantity, price) = blend(*q1, *p1, *q2, *p2);
+                Some(Self::Sell {
+                    symbol: symbol.clone(),
+                    quantity,
+                    price,
+                    tag: tag.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    // Reversing a sell buys the shares back, so it needs the cash
+    pub fn undo(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        match self {
+            Self::Buy {
+                symbol,
+                quantity,
+                price,
+                ..
+            } => portfolio.reverse_buy(symbol, *quantity, *price),
+            Self::Sell {
+                symbol,
+                quantity,
+                price,
+                ..
+            } => {
+                let fee = portfolio.refundable_commission(symbol, *quantity);
+                let cost = *quantity as f64 * price - fee;
+                portfolio.ensure_cash(cost)?;
+                portfolio.reverse_sell(symbol, *quantity, *price);
+            }
+        }
+        Ok(())
+    }
+}
+
+// --- Journal: durable, append-only record of executed trades ---
+
+pub trait JournalWriter: fmt::Debug {
+    fn append(&mut self, action: &TradeAction);
+}
+
+// One JSON line per action. A failed write can't undo the trade it
+// records, so the first I/O error is kept for the caller to check
+// and later appends still try to write.
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub struct FileJournal<W: Write = std::fs::File> {
+    pub writer: W,
+    pub error: Option<io::Error>,
+}
+
+#[cfg(feature = "serde")]
+impl FileJournal {
+    pub fn open(path: &std::path::Path) -> io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self::new(file))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<W: Write> FileJournal<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            error: None,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<W: Write + fmt::Debug> JournalWriter for FileJournal<W> {
+    fn append(&mut self, action: &TradeAction) {
+        let result = serde_json::to_writer(&mut self.writer, action)
+            .map_err(io::Error::from)
+            .and_then(|()| self.writer.write_all(b"\n"))
+            .and_then(|()| self.writer.flush());
+        if let Err(e) = result {
+            self.error.get_or_insert(e);
+        }
+    }
+}
+
+// --- Command History: Vec of Clone-able values ---
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimedAction {
+    pub action: TradeAction,
+    pub executed_at: SystemTime,
+    // Basis entries left by the action's fill, handed back on undo
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub basis: Vec<BasisUndo>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TradeHistory {
+    pub executed: Vec<TimedAction>,
+    pub undone: Vec<TradeAction>, // re-stamped when redone
+    pub max_depth: Option<usize>, // oldest executed actions fall off past this
+    // Actions dropped by max_depth; a rebuild can't include them
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub evicted: usize,
+    // Not persisted; a loaded history stamps with the system clock
+    #[cfg_attr(feature = "serde", serde(skip, default = "system_clock"))]
+    pub clock: Rc<dyn Clock>,
+    // Shared so a clone keeps writing to the same journal
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub journal: Option<Rc<RefCell<dyn JournalWriter>>>,
+}
+
+#[cfg(feature = "serde")]
+fn system_clock() -> Rc<dyn Clock> {
+    Rc::new(SystemClock)
+}
+
+impl Default for TradeHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TradeHistory {
+    pub fn new() -> Self {
+        Self::with_clock(Rc::new(SystemClock))
+    }
+
+    pub fn with_clock(clock: Rc<dyn Clock>) -> Self {
+        Self {
+            executed: Vec::new(),
+            undone: Vec::new(),
+            max_depth: None,
+            evicted: 0,
+            clock,
+            journal: None,
+        }
+    }
+
+    pub fn with_journal(mut self, journal: Rc<RefCell<dyn JournalWriter>>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    pub fn with_max_depth(max_depth: usize) -> Self {
+        Self {
+            max_depth: Some(max_depth),
+            ..Self::new()
+        }
+    }
+
+    // Trimming only touches the bottom of the executed stack; the
+    // redo stack holds newer actions and is unaffected. Evicted
+    // actions take their basis entries with them.
+    pub fn record(&mut self, action: TradeAction, basis: Vec<BasisUndo>) {
+        self.executed.push(TimedAction {
+            action,
+            executed_at: self.clock.now(),
+            basis,
+        });
+        if let Some(max) = self.max_depth {
+            let excess = self.executed.len().saturating_sub(max);
+            self.executed.drain(..excess);
+            self.evicted += excess;
+        }
+    }
+
+    // A rejected action is not recorded and leaves redo intact. New
+    // trades are journaled as they execute; undo and redo are not.
+    pub fn execute(
+        &mut self,
+        action: TradeAction,
+        portfolio: &mut Portfolio,
+    ) -> Result<(), TradeError> {
+        let mark = portfolio.basis_log.len();
+        action.execute(portfolio)?;
+        let basis = portfolio.take_basis_since(mark);
+        if let Some(journal) = &self.journal {
+            journal.borrow_mut().append(&action);
+        }
+        self.record(action, basis);
+        self.undone.clear();
+        Ok(())
+    }
+
+    // Returns the reversed action; an action that can't be
+    // reversed stays executed and None is returned
+    pub fn undo(&mut self, portfolio: &mut Portfolio) -> Option<TradeAction> {
+        let timed = self.executed.pop()?;
+        let mark = portfolio.basis_log.len();
+        portfolio.put_back_basis(&timed.basis);
+        if timed.action.undo(portfolio).is_err() {
+            portfolio.take_basis_since(mark);
+            self.executed.push(timed);
+            return None;
+        }
+        self.undone.push(timed.action.clone());
+        Some(timed.action)
+    }
+
+    // If the portfolio can no longer afford the action it stays
+    // on the redo stack
+    pub fn redo(&mut self, portfolio: &mut Portfolio) -> Option<TradeAction> {
+        let action = self.undone.pop()?;
+        let mark = portfolio.basis_log.len();
+        if action.execute(portfolio).is_err() {
+            self.undone.push(action);
+            return None;
+        }
+        let basis = portfolio.take_basis_since(mark);
+        self.record(action.clone(), basis);
+        Some(action)
+    }
+
+    // Reverses `qty` shares of the last trade. Done as a full undo
+    // followed by re-applying the remainder, so cost basis and
+    // commission come out as if the smaller trade had been the
+    // original; the remainder keeps its place and timestamp. The
+    // reversed slice goes on the redo stack. Any failure restores
+    // the portfolio and leaves the history as it was.
+    pub fn undo_partial(&mut self, qty: i32, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        if qty <= 0 {
+            return Err(TradeError::InvalidQuantity(qty));
+        }
+        let timed = self.executed.last().ok_or(TradeError::NothingToUndo)?;
+        let executed = timed.action.quantity();
+        if qty > executed {
+            return Err(TradeError::ExceedsTrade {
+                requested: qty,
+                executed,
+            });
+        }
+
+        let remainder = timed.action.with_quantity(executed - qty);
+        let checkpoint = portfolio.snapshot();
+        let mark = portfolio.basis_log.len();
+        portfolio.put_back_basis(&timed.basis);
+        let mut result = timed.action.undo(portfolio);
+        if qty < executed {
+            result = result.and_then(|()| remainder.execute(portfolio));
+        }
+        if let Err(e) = result {
+            portfolio.restore(checkpoint);
+            return Err(e);
+        }
+        let basis = portfolio.take_basis_since(mark);
+
+        if qty == executed {
+            let timed = self.executed.pop().expect("checked above");
+            self.undone.push(timed.action);
+        } else {
+            let timed = self.executed.last_mut().expect("checked above");
+            self.undone.push(timed.action.with_quantity(qty));
+            timed.action = remainder;
+            timed.basis = basis;
+        }
+        Ok(())
+    }
+
+    pub fn undo_all(&mut self, portfolio: &mut Portfolio) -> usize {
+        let mut steps = 0;
+        while self.undo(portfolio).is_some() {
+            steps += 1;
+        }
+        steps
+    }
+
+    pub fn redo_all(&mut self, portfolio: &mut Portfolio) -> usize {
+        let mut steps = 0;
+        while self.redo(portfolio).is_some() {
+            steps += 1;
+        }
+        steps
+    }
+
+    // Cash delta from the executed trades at their fill prices,
+    // before commission; undone trades are excluded
+    pub fn net_cash_flow(&self) -> f64 {
+        self.executed
+            .iter()
+            .map(|timed| match &timed.action {
+                TradeAction::Buy {
+                    quantity, price, ..
+                } => -(*quantity as f64 * price),
+                TradeAction::Sell {
+                    quantity, price, ..
+                } => *quantity as f64 * price,
+            })
+            .sum()
+    }
+
+    // Reporting view: adjacent same-symbol, same-side fills merged.
+    // The history itself is left untouched.
+    pub fn coalesced(&self) -> Vec<TradeAction> {
+        let mut merged: Vec<TradeAction> = Vec::new();
+        for timed in &self.executed {
+            match merged.last().and_then(|last| last.merge(&timed.action)) {
+                Some(combined) => *merged.last_mut().expect("merged with last") = combined,
+                None => merged.push(timed.action.clone()),
+            }
+        }
+        merged
+    }
+
+    // Event sourcing: rebuild state by re-running the executed
+    // actions in order against a fresh portfolio
+    pub fn replay(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        for timed in &self.executed {
+            timed.action.execute(portfolio)?;
+        }
+        Ok(())
+    }
+
+    // Reconciliation: the log replayed onto a copy of `template`, the
+    // account as it stood before the first logged trade, so cash,
+    // commission, short selling and limits match the live book. A
+    // replay failure, or actions already evicted by max_depth, are
+    // reported and the partial rebuild returned, so the mismatch
+    // shows up when compared with the live book.
+    pub fn rebuild(&self, template: &Portfolio) -> Portfolio {
+        if self.evicted > 0 {
+            println!("  [BREAK] {} evicted trades can't be replayed", self.evicted);
+        }
+        let mut portfolio = template.clone();
+        if let Err(e) = self.replay(&mut portfolio) {
+            println!("  [BREAK] replay stopped: {}", e);
+        }
+        portfolio
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("trade history is always serializable")
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_json(s: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(s)
+    }
+
+    pub fn print_history(&self) {
+        println!("  Trade History:");
+        if self.executed.is_empty() {
+            println!("    (empty)");
+        } else {
+            for (i, timed) in self.executed.iter().enumerate() {
+                let stamp = timed
+                    .executed_at
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                println!(
+                    "    {}. [{}.{:03}] {}",
+                    i + 1,
+                    stamp.as_secs(),
+                    stamp.subsec_millis(),
+                    timed.action
+                );
+            }
+        }
+    }
+}
+
+// ============================================================
+// APPROACH 2: Trait Objects (open extension)
+// ============================================================
+
+pub trait Command: fmt::Debug {
+    fn execute(&self, portfolio: &mut Portfolio) -> Result<(), TradeError>;
+    fn undo(&self, portfolio: &mut Portfolio);
+    fn description(&self) -> String;
+    fn clone_box(&self) -> Box<dyn Command>;
+
+    // Re-applies an undone command. Defaults to execute; commands
+    // that shouldn't re-run verbatim override it.
+    fn redo(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        self.execute(portfolio)
+    }
+
+    // Order details for pre-trade checks. Commands that don't
+    // trade a symbol keep the defaults.
+    fn symbol(&self) -> Option<&str> {
+        None
+    }
+
+    fn notional(&self) -> f64 {
+        0.0
+    }
+
+    // Whether a working order would fill at this market price.
+    // Market orders and non-trades are always marketable.
+    fn is_marketable(&self, _price: f64) -> bool {
+        true
+    }
+}
+
+impl Clone for Box<dyn Command> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+// Generates the boilerplate `description` and `clone_box` for a
+// Clone command; the body supplies execute/undo and any overrides.
+// Description arguments are field names, formatted in order.
+macro_rules! impl_command {
+    ($name:ident, $fmt:literal, [$($field:ident),* $(,)?], { $($body:tt)* }) => {
+        impl Command for $name {
+            $($body)*
+
+            fn description(&self) -> String {
+                format!($fmt, $(self.$field),*)
+            }
+
+            fn clone_box(&self) -> Box<dyn Command> {
+                Box::new(self.clone())
+            }
+        }
+    };
+}
+
+#[derive(Debug, Clone)]
+pub struct MarketBuy {
+    pub symbol: String,
+    pub quantity: i32,
+    pub price: f64,
+}
+
+impl_command!(MarketBuy, "MARKET BUY {} {} @ ${:.2}", [quantity, symbol, price], {
+    fn execute(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        portfolio.buy(&self.symbol, self.quantity, self.price)
+    }
+
+    fn undo(&self, portfolio: &mut Portfolio) {
+        portfolio.reverse_buy(&self.symbol, self.quantity, self.price);
+    }
+
+    fn symbol(&self) -> Option<&str> {
+        Some(&self.symbol)
+    }
+
+    fn notional(&self) -> f64 {
+        (self.quantity as f64 * self.price).abs()
+    }
+});
+
+#[derive(Debug, Clone)]
+pub struct MarketSell {
+    pub symbol: String,
+    pub quantity: i32,
+    pub price: f64,
+}
+
+impl_command!(MarketSell, "MARKET SELL {} {} @ ${:.2}", [quantity, symbol, price], {
+    fn execute(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        portfolio.sell(&self.symbol, self.quantity, self.price)
+    }
+
+    fn undo(&self, portfolio: &mut Portfolio) {
+        portfolio.reverse_sell(&self.symbol, self.quantity, self.price);
+    }
+
+    fn symbol(&self) -> Option<&str> {
+        Some(&self.symbol)
+    }
+
+    fn notional(&self) -> f64 {
+        (self.quantity as f64 * self.price).abs()
+    }
+});
+
+#[derive(Debug, Clone)]
+pub struct LimitBuy {
+    pub symbol: String,
+    pub quantity: i32,
+    pub limit_price: f64,
+}
+
+impl Command for LimitBuy {
+    fn is_marketable(&self, price: f64) -> bool {
+        price <= self.limit_price
+    }
+
+    fn execute(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        portfolio.buy(&self.symbol, self.quantity, self.limit_price)
+    }
+
+    fn undo(&self, portfolio: &mut Portfolio) {
+        portfolio.reverse_buy(&self.symbol, self.quantity, self.limit_price);
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "LIMIT BUY {} {} @ ${:.2}",
+            self.quantity, self.symbol, self.limit_price
+        )
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn symbol(&self) -> Option<&str> {
+        Some(&self.symbol)
+    }
+
+    fn notional(&self) -> f64 {
+        (self.quantity as f64 * self.limit_price).abs()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LimitSell {
+    pub symbol: String,
+    pub quantity: i32,
+    pub limit_price: f64,
+}
+
+impl_command!(LimitSell, "LIMIT SELL {} {} @ ${:.2}", [quantity, symbol, limit_price], {
+    fn is_marketable(&self, price: f64) -> bool {
+        price >= self.limit_price
+    }
+
+    fn execute(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        portfolio.sell(&self.symbol, self.quantity, self.limit_price)
+    }
+
+    fn undo(&self, portfolio: &mut Portfolio) {
+        portfolio.reverse_sell(&self.symbol, self.quantity, self.limit_price);
+    }
+
+    fn symbol(&self) -> Option<&str> {
+        Some(&self.symbol)
+    }
+
+    fn notional(&self) -> f64 {
+        (self.quantity as f64 * self.limit_price).abs()
+    }
+});
+
+// Audit-only: records the cancellation of a working order without
+// touching the portfolio
+#[derive(Debug, Clone)]
+pub struct Cancel {
+    pub order_ref: String,
+}
+
+impl_command!(Cancel, "CANCEL {}", [order_ref], {
+    fn execute(&self, _portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        Ok(())
+    }
+
+    fn undo(&self, _portfolio: &mut Portfolio) {}
+});
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Buy => write!(f, "BUY"),
+            Self::Sell => write!(f, "SELL"),
+        }
+    }
+}
+
+// Fires only once the market crosses the trigger: a buy stop at or
+// above it, a sell stop at or below. Fills at the observed market
+// price. The price is captured on the command, so undo can tell
+// whether execute actually traded.
+#[derive(Debug, Clone)]
+pub struct StopMarket {
+    pub symbol: String,
+    pub quantity: i32,
+    pub trigger_price: f64,
+    pub side: Side,
+    pub market_price: f64,
+}
+
+impl StopMarket {
+    pub fn is_triggered(&self) -> bool {
+        match self.side {
+            Side::Buy => self.market_price >= self.trigger_price,
+            Side::Sell => self.market_price <= self.trigger_price,
+        }
+    }
+}
+
+impl Command for StopMarket {
+    fn execute(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        if !self.is_triggered() {
+            return Ok(());
+        }
+        match self.side {
+            Side::Buy => portfolio.buy(&self.symbol, self.quantity, self.market_price),
+            Side::Sell => portfolio.sell(&self.symbol, self.quantity, self.market_price),
+        }
+    }
+
+    fn undo(&self, portfolio: &mut Portfolio) {
+        if !self.is_triggered() {
+            return;
+        }
+        match self.side {
+            Side::Buy => portfolio.reverse_buy(&self.symbol, self.quantity, self.market_price),
+            Side::Sell => portfolio.reverse_sell(&self.symbol, self.quantity, self.market_price),
+        }
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "STOP {} {} {} @ ${:.2} (market ${:.2})",
+            self.side, self.quantity, self.symbol, self.trigger_price, self.market_price
+        )
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn symbol(&self) -> Option<&str> {
+        Some(&self.symbol)
+    }
+
+    fn notional(&self) -> f64 {
+        (self.quantity as f64 * self.market_price).abs()
+    }
+}
+
+// --- Corporate actions: change positions without a trade ---
+
+#[derive(Debug, Clone)]
+pub struct Dividend {
+    pub symbol: String,
+    pub per_share: f64,
+}
+
+impl Command for Dividend {
+    fn execute(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        portfolio.pay_dividend(&self.symbol, self.per_share);
+        Ok(())
+    }
+
+    fn undo(&self, portfolio: &mut Portfolio) {
+        portfolio.reverse_dividend(&self.symbol, self.per_share);
+    }
+
+    fn description(&self) -> String {
+        format!("DIVIDEND {} ${:.2}/share", self.symbol, self.per_share)
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn symbol(&self) -> Option<&str> {
+        Some(&self.symbol)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Split {
+    pub symbol: String,
+    pub ratio: f64, // new shares per old share
+}
+
+impl Command for Split {
+    fn execute(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        portfolio.split(&self.symbol, self.ratio)
+    }
+
+    fn undo(&self, portfolio: &mut Portfolio) {
+        portfolio.reverse_split(&self.symbol, self.ratio);
+    }
+
+    fn description(&self) -> String {
+        format!("SPLIT {} {}:1", self.symbol, self.ratio)
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn symbol(&self) -> Option<&str> {
+        Some(&self.symbol)
+    }
+}
+
+// --- One-cancels-other bracket exit ---
+// The legs carry the trades; the trigger levels decide which one
+// fires at the observed market price. A take-profit above the stop
+// brackets a long, below it a short. At most one leg fires and the
+// other is cancelled, so undo only ever reverses that one leg.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OcoLeg {
+    TakeProfit,
+    StopLoss,
+}
+
+#[derive(Debug, Clone)]
+pub struct Oco {
+    pub take_profit: Box<dyn Command>,
+    pub stop_loss: Box<dyn Command>,
+    pub take_profit_price: f64,
+    pub stop_price: f64,
+    pub market_price: f64,
+}
+
+impl Oco {
+    pub fn fired(&self) -> Option<OcoLeg> {
+        let long = self.take_profit_price > self.stop_price;
+        let (hit_profit, hit_stop) = if long {
+            (
+                self.market_price >= self.take_profit_price,
+                self.market_price <= self.stop_price,
+            )
+        } else {
+            (
+                self.market_price <= self.take_profit_price,
+                self.market_price >= self.stop_price,
+            )
+        };
+        if hit_profit {
+            Some(OcoLeg::TakeProfit)
+        } else if hit_stop {
+            Some(OcoLeg::StopLoss)
+        } else {
+            None
+        }
+    }
+
+    pub fn leg(&self, leg: OcoLeg) -> &dyn Command {
+        match leg {
+            OcoLeg::TakeProfit => self.take_profit.as_ref(),
+            OcoLeg::StopLoss => self.stop_loss.as_ref(),
+        }
+    }
+}
+
+impl Command for Oco {
+    fn execute(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        match self.fired() {
+            Some(leg) => self.leg(leg).execute(portfolio),
+            None => Ok(()),
+        }
+    }
+
+    fn undo(&self, portfolio: &mut Portfolio) {
+        if let Some(leg) = self.fired() {
+            self.leg(leg).undo(portfolio);
+        }
+    }
+
+    fn description(&self) -> String {
+        let status = match self.fired() {
+            Some(OcoLeg::TakeProfit) => "take-profit filled, stop cancelled",
+            Some(OcoLeg::StopLoss) => "stop filled, take-profit cancelled",
+            None => "both legs working",
+        };
+        format!(
+            "OCO [TP {} | SL {}] @ market ${:.2}: {}",
+            self.take_profit.description(),
+            self.stop_loss.description(),
+            self.market_price,
+            status
+        )
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn notional(&self) -> f64 {
+        self.fired().map_or(0.0, |leg| self.leg(leg).notional())
+    }
+}
+
+// --- Macro command: several commands as one undoable unit ---
+// All-or-nothing: if a leg fails, the legs already executed are
+// rolled back in reverse before the error is returned.
+
+#[derive(Debug, Clone)]
+pub struct MacroCommand {
+    pub name: String,
+    pub legs: Vec<Box<dyn Command>>,
+}
+
+impl MacroCommand {
+    pub fn run_legs(
+        &self,
+        portfolio: &mut Portfolio,
+        step: fn(&dyn Command, &mut Portfolio) -> Result<(), TradeError>,
+    ) -> Result<(), TradeError> {
+        for (i, leg) in self.legs.iter().enumerate() {
+            if let Err(e) = step(leg.as_ref(), portfolio) {
+                for done in self.legs[..i].iter().rev() {
+                    done.undo(portfolio);
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Command for MacroCommand {
+    fn execute(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        self.run_legs(portfolio, |leg, p| leg.execute(p))
+    }
+
+    // Legs may override redo themselves, so replay through it
+    // rather than execute
+    fn redo(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        println!("  [REDO] {}", self.name);
+        self.run_legs(portfolio, |leg, p| leg.redo(p))
+    }
+
+    fn undo(&self, portfolio: &mut Portfolio) {
+        for leg in self.legs.iter().rev() {
+            leg.undo(portfolio);
+        }
+    }
+
+    fn description(&self) -> String {
+        let legs: Vec<String> = self.legs.iter().map(|leg| leg.description()).collect();
+        format!("{} [{}]", self.name, legs.join("; "))
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn notional(&self) -> f64 {
+        self.legs.iter().map(|leg| leg.notional()).sum()
+    }
+}
+
+// --- Snapshot undo: exact rollback instead of arithmetic reversal ---
+// Reversing a trade assumes nothing else touched the position since;
+// a split or dividend in between makes the reversal wrong. Wrapping
+// the command captures the portfolio just before execute, and undo
+// restores that capture when present, rolling back anything applied
+// after it too. Without a capture it falls back to the inner undo.
+
+#[derive(Debug, Clone)]
+pub struct SnapshotUndo {
+    pub inner: Box<dyn Command>,
+    pub captured: RefCell<Option<Snapshot>>, // filled by execute
+}
+
+impl SnapshotUndo {
+    pub fn new(inner: Box<dyn Command>) -> Self {
+        Self {
+            inner,
+            captured: RefCell::new(None),
+        }
+    }
+}
+
+impl Command for SnapshotUndo {
+    // A failed execute leaves no capture, so a stale one can't be
+    // restored over later state
+    fn execute(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        let before = portfolio.snapshot();
+        self.captured.replace(None);
+        self.inner.execute(portfolio)?;
+        self.captured.replace(Some(before));
+        Ok(())
+    }
+
+    fn undo(&self, portfolio: &mut Portfolio) {
+        match self.captured.take() {
+            Some(snapshot) => {
+                portfolio.restore(snapshot);
+                println!(
+                    "  [UNDO] {} restored from snapshot  (cash: ${:.2})",
+                    self.inner.description(),
+                    portfolio.cash
+                );
+            }
+            None => self.inner.undo(portfolio),
+        }
+    }
+
+    fn description(&self) -> String {
+        self.inner.description()
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn symbol(&self) -> Option<&str> {
+        self.inner.symbol()
+    }
+
+    fn notional(&self) -> f64 {
+        self.inner.notional()
+    }
+
+    fn is_marketable(&self, price: f64) -> bool {
+        self.inner.is_marketable(price)
+    }
+}
+
+// --- Command History for trait objects ---
+// Same undo/redo stacks as TradeHistory; Box<dyn Command> is
+// Clone via clone_box, so the whole history can be snapshotted.
+
+// An executed command and the basis entries its fills left,
+// handed back to the portfolio when it is undone
+#[derive(Debug, Clone)]
+pub struct ExecutedCommand {
+    pub command: Box<dyn Command>,
+    pub basis: Vec<BasisUndo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandHistory {
+    pub executed: Vec<ExecutedCommand>,
+    pub undone: Vec<Box<dyn Command>>,
+}
+
+impl Default for CommandHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommandHistory {
+    pub fn new() -> Self {
+        Self {
+            executed: Vec::new(),
+            undone: Vec::new(),
+        }
+    }
+
+    pub fn execute(
+        &mut self,
+        cmd: Box<dyn Command>,
+        portfolio: &mut Portfolio,
+    ) -> Result<(), TradeError> {
+        let mark = portfolio.basis_log.len();
+        cmd.execute(portfolio)?;
+        self.executed.push(ExecutedCommand {
+            command: cmd,
+            basis: portfolio.take_basis_since(mark),
+        });
+        self.undone.clear();
+        Ok(())
+    }
+
+    pub fn undo(&mut self, portfolio: &mut Portfolio) -> bool {
+        if let Some(done) = self.executed.pop() {
+            portfolio.put_back_basis(&done.basis);
+            done.command.undo(portfolio);
+            self.undone.push(done.command);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn redo(&mut self, portfolio: &mut Portfolio) -> bool {
+        let Some(cmd) = self.undone.pop() else {
+            return false;
+        };
+        let mark = portfolio.basis_log.len();
+        if cmd.redo(portfolio).is_err() {
+            self.undone.push(cmd);
+            return false;
+        }
+        self.executed.push(ExecutedCommand {
+            command: cmd,
+            basis: portfolio.take_basis_since(mark),
+        });
+        true
+    }
+
+    pub fn print_history(&self) {
+        println!("  Command History:");
+        if self.executed.is_empty() {
+            println!("    (empty)");
+        } else {
+            for (i, done) in self.executed.iter().enumerate() {
+                println!("    {}. {}", i + 1, done.command.description());
+            }
+        }
+    }
+}
+
+// --- Working orders: resting until the market reaches them ---
+// Submitted orders wait in `working`; each tick fills the ones that
+// are marketable at the new price and records them in the regular
+// command history, so undo/redo work as usual once filled.
+
+#[derive(Debug, Clone)]
+pub struct OrderManager {
+    pub working: Vec<Box<dyn Command>>,
+    pub history: CommandHistory,
+}
+
+impl Default for OrderManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OrderManager {
+    pub fn new() -> Self {
+        Self {
+            working: Vec::new(),
+            history: CommandHistory::new(),
+        }
+    }
+
+    pub fn submit(&mut self, cmd: Box<dyn Command>) {
+        println!("  [WORK] {}", cmd.description());
+        self.working.push(cmd);
+    }
+
+    // Returns the number of orders filled. A marketable order that
+    // is rejected stays working.
+    pub fn tick(&mut self, price: f64, portfolio: &mut Portfolio) -> usize {
+        let mut filled = 0;
+        let mut still_working = Vec::new();
+        for cmd in std::mem::take(&mut self.working) {
+            if !cmd.is_marketable(price) {
+                still_working.push(cmd);
+                continue;
+            }
+            let description = cmd.description();
+            match self.history.execute(cmd.clone(), portfolio) {
+                Ok(()) => filled += 1,
+                Err(e) => {
+                    println!("  [REJECT] {}: {}", description, e);
+                    still_working.push(cmd);
+                }
+            }
+        }
+        self.working = still_working;
+        filled
+    }
+}
+
+// ============================================================
+// Pre-trade risk: checks are trait objects too, so the rule set
+// is configured at runtime and wrapped around any command.
+// ============================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskReject {
+    NotionalExceeded { notional: f64, limit: f64 },
+    RestrictedSymbol(String),
+}
+
+impl fmt::Display for RiskReject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotionalExceeded { notional, limit } => write!(
+                f,
+                "notional ${:.2} exceeds limit ${:.2}",
+                notional, limit
+            ),
+            Self::RestrictedSymbol(symbol) => write!(f, "{} is on the restricted list", symbol),
+        }
+    }
+}
+
+pub trait RiskCheck: fmt::Debug {
+    fn approve(&self, cmd: &dyn Command, portfolio: &Portfolio) -> Result<(), RiskReject>;
+}
+
+#[derive(Debug, Clone)]
+pub struct MaxNotional {
+    pub limit: f64,
+}
+
+impl RiskCheck for MaxNotional {
+    fn approve(&self, cmd: &dyn Command, _portfolio: &Portfolio) -> Result<(), RiskReject> {
+        let notional = cmd.notional();
+        if notional > self.limit {
+            Err(RiskReject::NotionalExceeded {
+                notional,
+                limit: self.limit,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RestrictedList {
+    pub symbols: HashSet<String>,
+}
+
+impl RiskCheck for RestrictedList {
+    fn approve(&self, cmd: &dyn Command, _portfolio: &Portfolio) -> Result<(), RiskReject> {
+        match cmd.symbol() {
+            Some(symbol) if self.symbols.contains(symbol) => {
+                Err(RiskReject::RestrictedSymbol(symbol.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// Runs every check in order; the first rejection stops the
+// command before it touches the portfolio. Checks are shared, so
+// one rule set can guard many commands and clone with them.
+#[derive(Debug, Clone)]
+pub struct GuardedCommand {
+    pub inner: Box<dyn Command>,
+    pub checks: Vec<Rc<dyn RiskCheck>>,
+}
+
+impl GuardedCommand {
+    fn approve(&self, portfolio: &Portfolio) -> Result<(), TradeError> {
+        for check in &self.checks {
+            check
+                .approve(self.inner.as_ref(), portfolio)
+                .map_err(TradeError::RiskRejected)?;
+        }
+        Ok(())
+    }
+}
+
+impl Command for GuardedCommand {
+    fn execute(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        self.approve(portfolio)?;
+        self.inner.execute(portfolio)
+    }
+
+    fn undo(&self, portfolio: &mut Portfolio) {
+        self.inner.undo(portfolio);
+    }
+
+    fn description(&self) -> String {
+        self.inner.description()
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn redo(&self, portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        self.approve(portfolio)?;
+        self.inner.redo(portfolio)
+    }
+
+    fn symbol(&self) -> Option<&str> {
+        self.inner.symbol()
+    }
+
+    fn notional(&self) -> f64 {
+        self.inner.notional()
+    }
+
+    fn is_marketable(&self, price: f64) -> bool {
+        self.inner.is_marketable(price)
+    }
+}
@@ -0,0 +1,18 @@
+// ============================================================
+// Design patterns in modern Rust, applied to trading systems.
+//
+// Each module is one pattern; the binaries in src/bin/ are demo
+// drivers over these types.
+//
+//   strategy — order execution strategies (enum, trait, closure)
+//   command  — trades with undo/redo, history and order management
+//   visitor  — pricing, risk and reporting over financial instruments
+//   report   — the Report trait the three share for printable summaries
+//   clock    — injectable time source for trade stamps and send timing
+// ============================================================
+
+pub mod clock;
+pub mod command;
+pub mod report;
+pub mod strategy;
+pub mod visitor;
@@ -0,0 +1,11 @@
+// ============================================================
+// Report — one printable summary per domain type.
+//
+// Each pattern module implements this for its main type so
+// callers print `x.report()` instead of formatting fields
+// themselves, and tests can assert on the exact text.
+// ============================================================
+
+pub trait Report {
+    fn report(&self) -> String;
+}
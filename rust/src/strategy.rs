@@ -0,0 +1,1743 @@
+// ============================================================
+// Strategy Pattern in Rust — Order Execution Strategies
+//
+// Rust gives you three natural approaches:
+//
+// 1. Enum dispatch (like std::variant / C tagged union)
+//    - Closed set, zero overhead, exhaustive match
+//    - Clone/Copy for free via derive
+//
+// 2. Trait objects (like C++ type erasure / C function pointers)
+//    - Open set, dynamic dispatch via vtable
+//    - Box<dyn Trait> for owned, &dyn Trait for borrowed
+//    - Clone requires a workaround (CloneStrategy supertrait)
+//
+// 3. Closures (Rust's killer feature for Strategy)
+//    - Any Fn closure IS a strategy — no boilerplate at all
+//    - The most idiomatic Rust approach for simple strategies
+//    - Arc<dyn Fn> makes them cloneable, like the other two
+//
+// All three shown below.
+// ============================================================
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::clock::Clock;
+use crate::report::Report;
+
+// Errors surfaced at execution time instead of panicking
+#[derive(Debug, Clone, PartialEq)]
+pub enum StrategyError {
+    InvalidSlices,
+    InvalidParticipation(f64),
+    OrderNotLive(OrderStatus),
+}
+
+impl fmt::Display for StrategyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSlices => write!(f, "TWAP requires at least one slice"),
+            Self::InvalidParticipation(rate) => {
+                write!(f, "participation rate {} is outside (0, 1]", rate)
+            }
+            Self::OrderNotLive(status) => write!(f, "order is {:?} and cannot be sent", status),
+        }
+    }
+}
+
+impl std::error::Error for StrategyError {}
+
+// One slice of a parent order — the common output of all three
+// approaches. time_offset is the fraction of the execution window
+// at which the slice is released, in [0, 1).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChildOrder {
+    pub quantity: u32,
+    pub price: f64,
+    pub time_offset: f64,
+}
+
+// Equal slices spread evenly over the window; rounding residue
+// goes to the last slice so the total is exact.
+fn even_schedule(quantity: u32, slices: u32, price: f64) -> Vec<ChildOrder> {
+    if slices == 0 {
+        return Vec::new();
+    }
+    let per_slice = quantity / slices;
+    (0..slices)
+        .map(|i| ChildOrder {
+            quantity: if i + 1 == slices {
+                quantity - per_slice * (slices - 1)
+            } else {
+                per_slice
+            },
+            price,
+            time_offset: i as f64 / slices as f64,
+        })
+        .collect()
+}
+
+// Fixed-size chunks with a smaller final chunk. A chunk of zero
+// means no size cap, so the whole order goes as one child.
+pub fn chunked_schedule(quantity: u32, chunk: u32, price: f64) -> Vec<ChildOrder> {
+    let chunk = if chunk == 0 { quantity.max(1) } else { chunk };
+    let n = quantity.div_ceil(chunk);
+    (0..n)
+        .map(|i| ChildOrder {
+            quantity: chunk.min(quantity - i * chunk),
+            price,
+            time_offset: i as f64 / n as f64,
+        })
+        .collect()
+}
+
+// Dark venues only take blocks of at least min_fill, so anything
+// below a whole block is held back as unroutable.
+pub fn dark_routable(quantity: u32, min_fill: u32) -> u32 {
+    if min_fill == 0 {
+        quantity
+    } else {
+        quantity - quantity % min_fill
+    }
+}
+
+// A sniper takes the whole order in one clip once the price is at
+// or under its cap, and sends nothing until then. Orders smaller
+// than min_size aren't worth showing the market and also wait.
+pub fn sniper_fires(quantity: u32, price: f64, min_size: u32, max_price: f64) -> bool {
+    quantity > 0 && quantity >= min_size && price <= max_price
+}
+
+pub fn sniper_schedule(
+    quantity: u32,
+    price: f64,
+    min_size: u32,
+    max_price: f64,
+) -> Vec<ChildOrder> {
+    if !sniper_fires(quantity, price, min_size, max_price) {
+        return Vec::new();
+    }
+    vec![ChildOrder {
+        quantity,
+        price,
+        time_offset: 0.0,
+    }]
+}
+
+fn print_sniper(
+    tag: &str,
+    symbol: &str,
+    quantity: u32,
+    price: f64,
+    max_price: f64,
+    fired: bool,
+) {
+    if fired {
+        println!(
+            "[{}] Executing {}: {} shares @ ${:.2} in one clip (cap ${:.2})",
+            tag, symbol, quantity, price, max_price
+        );
+    } else {
+        println!(
+            "[{}] Waiting on {}: {} shares @ ${:.2} (cap ${:.2})",
+            tag, symbol, quantity, price, max_price
+        );
+    }
+}
+
+// A participation rate is a share of market volume, so only
+// (0, 1] makes sense. NaN fails both comparisons and is rejected.
+pub fn validate_participation(rate: f64) -> Result<(), StrategyError> {
+    if rate > 0.0 && rate <= 1.0 {
+        Ok(())
+    } else {
+        Err(StrategyError::InvalidParticipation(rate))
+    }
+}
+
+// VWAP trades participation_rate of the order per volume interval
+pub fn participation_chunk(quantity: u32, participation_rate: f64) -> u32 {
+    ((quantity as f64 * participation_rate).ceil() as u32).clamp(1, quantity.max(1))
+}
+
+// SplitMix64: tiny deterministic PRNG so jittered schedules
+// are reproducible from a seed without pulling in a crate.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [-1, 1)
+    fn next_signed_unit(&mut self) -> f64 {
+        let unit = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        unit * 2.0 - 1.0
+    }
+}
+
+// Jitters both slice sizes and release times. Sizes are scaled
+// weights of the even split, with rounding residue going to the
+// last slice so the total is exact. Each release time stays inside
+// its own 1/slices bucket, which keeps the schedule ordered.
+pub fn stealth_schedule(
+    quantity: u32,
+    slices: u32,
+    size_jitter: f64,
+    time_jitter: f64,
+    seed: u64,
+    price: f64,
+) -> Vec<ChildOrder> {
+    let mut rng = SplitMix64(seed);
+    let size_jitter = size_jitter.clamp(0.0, 1.0);
+    let time_jitter = time_jitter.clamp(0.0, 1.0);
+    let n = slices as f64;
+
+    let weights: Vec<f64> = (0..slices)
+        .map(|_| 1.0 + size_jitter * rng.next_signed_unit())
+        .collect();
+    let total_weight: f64 = weights.iter().sum();
+
+    let mut remaining = quantity;
+    let mut children = Vec::with_capacity(slices as usize);
+    for (i, w) in weights.iter().enumerate() {
+        let qty = if i + 1 == slices as usize {
+            remaining
+        } else {
+            ((quantity as f64 * w / total_weight) as u32).min(remaining)
+        };
+        remaining -= qty;
+        let centre = i as f64 + 0.5 + 0.5 * time_jitter * rng.next_signed_unit();
+        children.push(ChildOrder {
+            quantity: qty,
+            price,
+            time_offset: centre / n,
+        });
+    }
+    children
+}
+
+// Toy pre-trade cost model, in dollars. Every child order pays a
+// fixed cost to cross the spread; strategies that trade with the
+// volume curve also pay impact proportional to participation.
+// Invalid parameters cost infinity so they never look cheapest.
+pub const SPREAD_COST_PER_CHILD: f64 = 5.0;
+pub const IMPACT_BPS_AT_FULL_PARTICIPATION: f64 = 25.0;
+
+pub fn spread_cost(children: u32) -> f64 {
+    if children == 0 {
+        f64::INFINITY
+    } else {
+        SPREAD_COST_PER_CHILD * children as f64
+    }
+}
+
+pub fn impact_cost(quantity: u32, price: f64, participation: f64) -> f64 {
+    quantity as f64 * price * participation * IMPACT_BPS_AT_FULL_PARTICIPATION / 10_000.0
+}
+
+pub fn vwap_cost(quantity: u32, price: f64, participation_rate: f64) -> f64 {
+    match validate_participation(participation_rate) {
+        Ok(()) => impact_cost(quantity, price, participation_rate),
+        Err(_) => f64::INFINITY,
+    }
+}
+
+pub fn visible_chunks(quantity: u32, visible_qty: u32) -> u32 {
+    if visible_qty == 0 {
+        0
+    } else {
+        quantity.div_ceil(visible_qty)
+    }
+}
+
+// Pegged orders quote off the touch. The order price is taken as
+// mid and the book is assumed QUOTED_HALF_SPREAD_BPS wide each side.
+pub const QUOTED_HALF_SPREAD_BPS: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PegReference {
+    Bid,
+    Ask,
+    Mid,
+}
+
+impl PegReference {
+    pub fn reference_price(self, mid: f64) -> f64 {
+        let half_spread = mid * QUOTED_HALF_SPREAD_BPS / 10_000.0;
+        match self {
+            Self::Bid => mid - half_spread,
+            Self::Ask => mid + half_spread,
+            Self::Mid => mid,
+        }
+    }
+}
+
+impl fmt::Display for PegReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Self::Bid => "BID",
+            Self::Ask => "ASK",
+            Self::Mid => "MID",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+impl FromStr for PegReference {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_uppercase().as_str() {
+            "BID" => Ok(Self::Bid),
+            "ASK" => Ok(Self::Ask),
+            "MID" => Ok(Self::Mid),
+            _ => Err(()),
+        }
+    }
+}
+
+pub fn peg_price(reference: PegReference, offset_bps: f64, mid: f64) -> f64 {
+    reference.reference_price(mid) * (1.0 + offset_bps / 10_000.0)
+}
+
+pub const IS_SLICES: u32 = 5;
+
+// Implementation Shortfall front-loads execution to limit drift
+// from the arrival price. Each slice is (1 - urgency / 2) times
+// the previous one, so urgency 0 is a flat TWAP and urgency 1
+// halves every slice. Rounding residue goes to the first slice.
+pub fn shortfall_schedule(quantity: u32, urgency: f64, price: f64) -> Vec<ChildOrder> {
+    let decay = 1.0 - 0.5 * urgency.clamp(0.0, 1.0);
+    let weights: Vec<f64> = (0..IS_SLICES).map(|i| decay.powi(i as i32)).collect();
+    let total_weight: f64 = weights.iter().sum();
+
+    let mut children: Vec<ChildOrder> = weights
+        .iter()
+        .enumerate()
+        .map(|(i, w)| ChildOrder {
+            quantity: (quantity as f64 * w / total_weight) as u32,
+            price,
+            time_offset: i as f64 / IS_SLICES as f64,
+        })
+        .collect();
+    let allocated: u32 = children.iter().map(|c| c.quantity).sum();
+    children[0].quantity += quantity - allocated;
+    children
+}
+
+// ============================================================
+// APPROACH 1: Enum Dispatch
+// ============================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecutionStrategy {
+    Twap { slices: u32 },
+    Vwap { participation_rate: f64 },
+    Iceberg { visible_qty: u32 },
+    StealthTwap {
+        slices: u32,
+        size_jitter: f64,
+        time_jitter: f64,
+        seed: u64,
+    },
+    ImplementationShortfall { urgency: f64 },
+    Peg {
+        reference: PegReference,
+        offset_bps: f64,
+    },
+    DarkPool { min_fill: u32 },
+    Sniper { min_size: u32, max_price: f64 },
+}
+
+impl ExecutionStrategy {
+    // The child orders this strategy would send, with no output
+    pub fn child_orders(
+        &self,
+        quantity: u32,
+        price: f64,
+    ) -> Result<Vec<ChildOrder>, StrategyError> {
+        match self {
+            Self::Twap { slices } => {
+                if *slices == 0 {
+                    return Err(StrategyError::InvalidSlices);
+                }
+                Ok(even_schedule(quantity, *slices, price))
+            }
+            Self::Vwap { participation_rate } => {
+                validate_participation(*participation_rate)?;
+                Ok(chunked_schedule(
+                    quantity,
+                    participation_chunk(quantity, *participation_rate),
+                    price,
+                ))
+            }
+            Self::Iceberg { visible_qty } => Ok(chunked_schedule(quantity, *visible_qty, price)),
+            Self::StealthTwap {
+                slices,
+                size_jitter,
+                time_jitter,
+                seed,
+            } => {
+                if *slices == 0 {
+                    return Err(StrategyError::InvalidSlices);
+                }
+                Ok(stealth_schedule(
+                    quantity,
+                    *slices,
+                    *size_jitter,
+                    *time_jitter,
+                    *seed,
+                    price,
+                ))
+            }
+            Self::ImplementationShortfall { urgency } => {
+                Ok(shortfall_schedule(quantity, *urgency, price))
+            }
+            Self::Peg {
+                reference,
+                offset_bps,
+            } => Ok(vec![ChildOrder {
+                quantity,
+                price: peg_price(*reference, *offset_bps, price),
+                time_offset: 0.0,
+            }]),
+            Self::DarkPool { min_fill } => Ok(chunked_schedule(
+                dark_routable(quantity, *min_fill),
+                *min_fill,
+                price,
+            )),
+            Self::Sniper {
+                min_size,
+                max_price,
+            } => Ok(sniper_schedule(quantity, price, *min_size, *max_price)),
+        }
+    }
+
+    pub fn execute(
+        &self,
+        symbol: &str,
+        quantity: u32,
+        price: f64,
+    ) -> Result<Vec<ChildOrder>, StrategyError> {
+        let children = self.child_orders(quantity, price)?;
+        match self {
+            Self::Twap { slices } => {
+                let per_slice = quantity / slices;
+                println!(
+                    "[TWAP] Executing {}: {} shares @ ${:.2} across {} slices ({}/slice)",
+                    symbol, quantity, price, slices, per_slice
+                );
+            }
+            Self::Vwap { participation_rate } => {
+                println!(
+                    "[VWAP] Executing {}: {} shares @ ${:.2} with {:.0}% participation",
+                    symbol, quantity, price, participation_rate * 100.0
+                );
+            }
+            Self::Iceberg { visible_qty } => {
+                println!(
+                    "[Iceberg] Executing {}: {} shares @ ${:.2} showing {} at a time",
+                    symbol, quantity, price, visible_qty
+                );
+            }
+            Self::StealthTwap { slices, .. } => {
+                println!(
+                    "[Stealth] Executing {}: {} shares @ ${:.2} across {} jittered slices",
+                    symbol, quantity, price, slices
+                );
+                for child in &children {
+                    println!(
+                        "  t={:>5.1}%  {} shares",
+                        child.time_offset * 100.0,
+                        child.quantity
+                    );
+                }
+            }
+            Self::ImplementationShortfall { urgency } => {
+                let slices: Vec<String> = children.iter().map(|c| c.quantity.to_string()).collect();
+                println!(
+                    "[IS] Executing {}: {} shares @ ${:.2} with urgency {:.2} (slices: {})",
+                    symbol,
+                    quantity,
+                    price,
+                    urgency,
+                    slices.join(", ")
+                );
+            }
+            Self::Peg {
+                reference,
+                offset_bps,
+            } => {
+                println!(
+                    "[Peg] Executing {}: {} shares pegged to {} {:+.1}bps @ ${:.4} (mid ${:.2})",
+                    symbol, quantity, reference, offset_bps, children[0].price, price
+                );
+            }
+            Self::DarkPool { min_fill } => {
+                let routed: u32 = children.iter().map(|c| c.quantity).sum();
+                println!(
+                    "[Dark] Executing {}: {} shares @ ${:.2} in blocks of {}+ \
+                     ({} routed to dark, {} unroutable)",
+                    symbol,
+                    quantity,
+                    price,
+                    min_fill,
+                    routed,
+                    quantity - routed
+                );
+            }
+            Self::Sniper { max_price, .. } => {
+                let fired = !children.is_empty();
+                print_sniper("Sniper", symbol, quantity, price, *max_price, fired);
+            }
+        }
+        Ok(children)
+    }
+
+    // Runs the strategy, then fills each child at `fill_ratio` of its
+    // size (clamped to [0, 1]; NaN fills nothing), rounding down.
+    // Whatever isn't filled is reported as the remainder.
+    pub fn execute_with_fill(
+        &self,
+        symbol: &str,
+        quantity: u32,
+        price: f64,
+        fill_ratio: f64,
+    ) -> Result<FillReport, StrategyError> {
+        let ratio = if fill_ratio.is_nan() {
+            0.0
+        } else {
+            fill_ratio.clamp(0.0, 1.0)
+        };
+        let filled: Vec<ChildOrder> = self
+            .execute(symbol, quantity, price)?
+            .into_iter()
+            .map(|child| ChildOrder {
+                quantity: (child.quantity as f64 * ratio) as u32,
+                ..child
+            })
+            .collect();
+        let done: u32 = filled.iter().map(|c| c.quantity).sum();
+        println!(
+            "  Filled {} of {} at {:.0}% ({} unfilled)",
+            done,
+            quantity,
+            ratio * 100.0,
+            quantity - done
+        );
+        Ok(FillReport {
+            filled,
+            unfilled: quantity - done,
+        })
+    }
+
+    pub fn estimated_cost(&self, quantity: u32, price: f64) -> f64 {
+        match self {
+            Self::Twap { slices } | Self::StealthTwap { slices, .. } => spread_cost(*slices),
+            Self::Vwap { participation_rate } => vwap_cost(quantity, price, *participation_rate),
+            Self::Iceberg { visible_qty } => spread_cost(visible_chunks(quantity, *visible_qty)),
+            Self::ImplementationShortfall { urgency } => {
+                spread_cost(IS_SLICES) + impact_cost(quantity, price, *urgency)
+            }
+            Self::Peg { .. } => spread_cost(1),
+            Self::DarkPool { min_fill } => {
+                spread_cost(visible_chunks(dark_routable(quantity, *min_fill), *min_fill))
+            }
+            // A sniper that would only wait never looks cheapest
+            Self::Sniper {
+                min_size,
+                max_price,
+            } => spread_cost(sniper_fires(quantity, price, *min_size, *max_price) as u32),
+        }
+    }
+
+    // Human-readable form for logs, showing the configured parameters
+    pub fn description(&self) -> String {
+        match self {
+            Self::Twap { slices } => format!("TWAP({} slices)", slices),
+            Self::Vwap { participation_rate } => {
+                format!("VWAP({:.0}% participation)", participation_rate * 100.0)
+            }
+            Self::Iceberg { visible_qty } => format!("Iceberg({} visible)", visible_qty),
+            Self::StealthTwap {
+                slices,
+                size_jitter,
+                time_jitter,
+                seed,
+            } => format!(
+                "Stealth({} slices, {:.0}% size / {:.0}% time jitter, seed {})",
+                slices,
+                size_jitter * 100.0,
+                time_jitter * 100.0,
+                seed
+            ),
+            Self::ImplementationShortfall { urgency } => {
+                format!("IS({:.0}% urgency)", urgency * 100.0)
+            }
+            Self::Peg {
+                reference,
+                offset_bps,
+            } => format!("Peg({} {:+.1}bps)", reference, offset_bps),
+            Self::DarkPool { min_fill } => format!("Dark({} min fill)", min_fill),
+            Self::Sniper {
+                min_size,
+                max_price,
+            } => format!("Sniper({}+ shares at <= ${:.2})", min_size, max_price),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Twap { .. } => "TWAP",
+            Self::Vwap { .. } => "VWAP",
+            Self::Iceberg { .. } => "Iceberg",
+            Self::StealthTwap { .. } => "Stealth",
+            Self::ImplementationShortfall { .. } => "IS",
+            Self::Peg { .. } => "Peg",
+            Self::DarkPool { .. } => "Dark",
+            Self::Sniper { .. } => "Sniper",
+        }
+    }
+
+    // Aggressive strategies take liquidity and pay the ask; passive
+    // ones rest (or match in the dark) and are modelled at mid
+    pub fn crosses_spread(&self) -> bool {
+        match self {
+            Self::Twap { .. }
+            | Self::Vwap { .. }
+            | Self::StealthTwap { .. }
+            | Self::ImplementationShortfall { .. }
+            | Self::Sniper { .. } => true,
+            Self::Iceberg { .. } | Self::Peg { .. } | Self::DarkPool { .. } => false,
+        }
+    }
+}
+
+// Command-string form used by the OMS: NAME:param[,param...].
+// Parameters are written in full precision so FromStr round-trips.
+impl fmt::Display for ExecutionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Twap { slices } => write!(f, "TWAP:{}", slices),
+            Self::Vwap { participation_rate } => write!(f, "VWAP:{}", participation_rate),
+            Self::Iceberg { visible_qty } => write!(f, "ICEBERG:{}", visible_qty),
+            Self::StealthTwap {
+                slices,
+                size_jitter,
+                time_jitter,
+                seed,
+            } => write!(
+                f,
+                "STEALTH:{},{},{},{}",
+                slices, size_jitter, time_jitter, seed
+            ),
+            Self::ImplementationShortfall { urgency } => write!(f, "IS:{}", urgency),
+            Self::Peg {
+                reference,
+                offset_bps,
+            } => write!(f, "PEG:{},{}", reference, offset_bps),
+            Self::DarkPool { min_fill } => write!(f, "DARK:{}", min_fill),
+            Self::Sniper {
+                min_size,
+                max_price,
+            } => write!(f, "SNIPER:{},{}", min_size, max_price),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseStrategyError {
+    UnknownStrategy(String),
+    MissingParameter(String),
+    InvalidParameter { strategy: &'static str, value: String },
+}
+
+impl fmt::Display for ParseStrategyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownStrategy(name) => write!(f, "unknown strategy '{}'", name),
+            Self::MissingParameter(name) => {
+                write!(f, "strategy '{}' needs parameters after ':'", name)
+            }
+            Self::InvalidParameter { strategy, value } => {
+                write!(f, "invalid {} parameter '{}'", strategy, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseStrategyError {}
+
+fn split_params<'a>(
+    strategy: &'static str,
+    raw: &'a str,
+    expected: usize,
+) -> Result<Vec<&'a str>, ParseStrategyError> {
+    let params: Vec<&str> = raw.split(',').map(str::trim).collect();
+    if params.len() != expected || params.iter().any(|p| p.is_empty()) {
+        return Err(ParseStrategyError::InvalidParameter {
+            strategy,
+            value: raw.trim().to_string(),
+        });
+    }
+    Ok(params)
+}
+
+fn parse_param<T: FromStr>(
+    strategy: &'static str,
+    value: &str,
+) -> Result<T, ParseStrategyError> {
+    value
+        .parse()
+        .map_err(|_| ParseStrategyError::InvalidParameter {
+            strategy,
+            value: value.to_string(),
+        })
+}
+
+// Names are case-insensitive and surrounding whitespace is ignored,
+// so " twap : 5 " parses the same as "TWAP:5".
+impl FromStr for ExecutionStrategy {
+    type Err = ParseStrategyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, raw) = s
+            .split_once(':')
+            .ok_or_else(|| ParseStrategyError::MissingParameter(s.to_string()))?;
+        let name = name.trim().to_ascii_uppercase();
+        match name.as_str() {
+            "TWAP" => {
+                let p = split_params("TWAP", raw, 1)?;
+                Ok(Self::Twap {
+                    slices: parse_param("TWAP", p[0])?,
+                })
+            }
+            "VWAP" => {
+                let p = split_params("VWAP", raw, 1)?;
+                Ok(Self::Vwap {
+                    participation_rate: parse_param("VWAP", p[0])?,
+                })
+            }
+            "ICEBERG" => {
+                let p = split_params("ICEBERG", raw, 1)?;
+                Ok(Self::Iceberg {
+                    visible_qty: parse_param("ICEBERG", p[0])?,
+                })
+            }
+            "STEALTH" => {
+                let p = split_params("STEALTH", raw, 4)?;
+                Ok(Self::StealthTwap {
+                    slices: parse_param("STEALTH", p[0])?,
+                    size_jitter: parse_param("STEALTH", p[1])?,
+                    time_jitter: parse_param("STEALTH", p[2])?,
+                    seed: parse_param("STEALTH", p[3])?,
+                })
+            }
+            "IS" => {
+                let p = split_params("IS", raw, 1)?;
+                Ok(Self::ImplementationShortfall {
+                    urgency: parse_param("IS", p[0])?,
+                })
+            }
+            "PEG" => {
+                let p = split_params("PEG", raw, 2)?;
+                Ok(Self::Peg {
+                    reference: parse_param("PEG", p[0])?,
+                    offset_bps: parse_param("PEG", p[1])?,
+                })
+            }
+            "DARK" => {
+                let p = split_params("DARK", raw, 1)?;
+                Ok(Self::DarkPool {
+                    min_fill: parse_param("DARK", p[0])?,
+                })
+            }
+            "SNIPER" => {
+                let p = split_params("SNIPER", raw, 2)?;
+                Ok(Self::Sniper {
+                    min_size: parse_param("SNIPER", p[0])?,
+                    max_price: parse_param("SNIPER", p[1])?,
+                })
+            }
+            _ => Err(ParseStrategyError::UnknownStrategy(name)),
+        }
+    }
+}
+
+// Outcome of a simulated partial execution: the children as
+// filled, and the shares left working
+#[derive(Debug, Clone, PartialEq)]
+pub struct FillReport {
+    pub filled: Vec<ChildOrder>,
+    pub unfilled: u32,
+}
+
+// What an order would send: the strategy and its child orders
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionPlan {
+    pub strategy: &'static str,
+    pub children: Vec<ChildOrder>,
+    pub execution_price: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrderStatus {
+    New,
+    PartiallyFilled { filled: u32 },
+    Filled,
+    Cancelled,
+}
+
+#[derive(Debug, Clone)]
+pub struct Order {
+    pub symbol: String,
+    pub quantity: u32,
+    pub price: f64, // mid
+    pub bid: f64,
+    pub ask: f64,
+    pub strategy: ExecutionStrategy, // value, not a pointer
+    pub status: OrderStatus,
+    pub commission_per_share: f64,
+}
+
+impl Order {
+    pub fn new(symbol: &str, quantity: u32, price: f64, strategy: ExecutionStrategy) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            quantity,
+            price,
+            bid: price,
+            ask: price,
+            strategy,
+            status: OrderStatus::New,
+            commission_per_share: 0.0,
+        }
+    }
+
+    // Quotes the order against a market; price becomes the mid.
+    // Orders built without a quote have a zero-width spread.
+    pub fn with_quote(mut self, bid: f64, ask: f64) -> Self {
+        self.bid = bid;
+        self.ask = ask;
+        self.price = (bid + ask) / 2.0;
+        self
+    }
+
+    pub fn spread(&self) -> f64 {
+        self.ask - self.bid
+    }
+
+    // Price this order is expected to trade at under its strategy
+    pub fn execution_price(&self) -> f64 {
+        if self.strategy.crosses_spread() {
+            self.ask
+        } else {
+            self.price
+        }
+    }
+
+    pub fn with_commission(mut self, commission_per_share: f64) -> Self {
+        self.commission_per_share = commission_per_share;
+        self
+    }
+
+    pub fn commission(&self) -> f64 {
+        self.quantity as f64 * self.commission_per_share
+    }
+
+    pub fn all_in_cost(&self) -> f64 {
+        self.quantity as f64 * self.execution_price() + self.commission()
+    }
+
+    // Fills accumulate up to the order quantity; anything beyond
+    // is clamped. Fills against a closed order are ignored.
+    pub fn apply_fill(&mut self, qty: u32) {
+        let filled = match self.status {
+            OrderStatus::New => 0,
+            OrderStatus::PartiallyFilled { filled } => filled,
+            OrderStatus::Filled | OrderStatus::Cancelled => return,
+        };
+        let filled = filled.saturating_add(qty).min(self.quantity);
+        self.status = if filled == self.quantity {
+            OrderStatus::Filled
+        } else if filled == 0 {
+            OrderStatus::New
+        } else {
+            OrderStatus::PartiallyFilled { filled }
+        };
+    }
+
+    pub fn set_strategy(&mut self, strategy: ExecutionStrategy) {
+        self.strategy = strategy;
+    }
+
+    // Lowest modelled cost for this order's size and price
+    pub fn cheapest_of<'a>(
+        &self,
+        strategies: &'a [ExecutionStrategy],
+    ) -> Option<&'a ExecutionStrategy> {
+        strategies.iter().min_by(|a, b| {
+            a.estimated_cost(self.quantity, self.price)
+                .total_cmp(&b.estimated_cost(self.quantity, self.price))
+        })
+    }
+
+    // Dry run: the plan send() would execute, with no side effects
+    pub fn preview(&self) -> Result<ExecutionPlan, StrategyError> {
+        Ok(ExecutionPlan {
+            strategy: self.strategy.name(),
+            children: self
+                .strategy
+                .child_orders(self.quantity, self.execution_price())?,
+            execution_price: self.execution_price(),
+        })
+    }
+
+    pub fn send(&self) -> Result<ExecutionPlan, StrategyError> {
+        if matches!(self.status, OrderStatus::Filled | OrderStatus::Cancelled) {
+            return Err(StrategyError::OrderNotLive(self.status));
+        }
+        println!("{}", self.report());
+        let plan = ExecutionPlan {
+            strategy: self.strategy.name(),
+            children: self
+                .strategy
+                .execute(&self.symbol, self.quantity, self.execution_price())?,
+            execution_price: self.execution_price(),
+        };
+        println!(
+            "  Plan: {} child orders  Exec: ${:.2} ({})  Commission: ${:.2}  All-in cost: ${:.2}",
+            plan.children.len(),
+            plan.execution_price,
+            if self.strategy.crosses_spread() {
+                "crossing"
+            } else {
+                "mid"
+            },
+            self.commission(),
+            self.all_in_cost()
+        );
+        Ok(plan)
+    }
+
+    // send() bracketed by two clock readings, so pacing can be
+    // compared across strategies. A clock that runs backwards
+    // reports zero rather than failing the send.
+    pub fn send_timed(
+        &self,
+        clock: &dyn Clock,
+    ) -> Result<(ExecutionPlan, Duration), StrategyError> {
+        let start = clock.now();
+        let plan = self.send()?;
+        let elapsed = clock.now().duration_since(start).unwrap_or_default();
+        println!("  Elapsed ({}): {:?}", plan.strategy, elapsed);
+        Ok((plan, elapsed))
+    }
+
+    pub fn builder() -> OrderBuilder {
+        OrderBuilder::default()
+    }
+}
+
+impl Report for Order {
+    fn report(&self) -> String {
+        format!(
+            "Order: {} {} shares @ ${:.2} using {}",
+            self.symbol,
+            self.quantity,
+            self.price,
+            self.strategy.description()
+        )
+    }
+}
+
+// --- Cancellation: live orders can be pulled before completion ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CancelError {
+    AlreadyFilled,
+    AlreadyCancelled,
+}
+
+impl fmt::Display for CancelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlreadyFilled => write!(f, "order is already filled"),
+            Self::AlreadyCancelled => write!(f, "order is already cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for CancelError {}
+
+pub trait Cancelable {
+    fn cancel(&mut self) -> Result<(), CancelError>;
+}
+
+// New and partially filled orders can be cancelled; the filled
+// quantity of a partial stays done, the remainder is dropped
+impl Cancelable for Order {
+    fn cancel(&mut self) -> Result<(), CancelError> {
+        match self.status {
+            OrderStatus::Filled => Err(CancelError::AlreadyFilled),
+            OrderStatus::Cancelled => Err(CancelError::AlreadyCancelled),
+            OrderStatus::New | OrderStatus::PartiallyFilled { .. } => {
+                self.status = OrderStatus::Cancelled;
+                Ok(())
+            }
+        }
+    }
+}
+
+// --- Builder: named setters instead of positional arguments ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    MissingField(&'static str),
+    ZeroQuantity,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingField(field) => write!(f, "order is missing {}", field),
+            Self::ZeroQuantity => write!(f, "order quantity must be positive"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+// Symbol, quantity, price and strategy are required; commission
+// defaults to zero as with Order::new
+#[derive(Debug, Clone, Default)]
+pub struct OrderBuilder {
+    symbol: Option<String>,
+    quantity: Option<u32>,
+    price: Option<f64>,
+    strategy: Option<ExecutionStrategy>,
+    commission_per_share: f64,
+}
+
+impl OrderBuilder {
+    pub fn symbol(mut self, symbol: &str) -> Self {
+        self.symbol = Some(symbol.to_string());
+        self
+    }
+
+    pub fn quantity(mut self, quantity: u32) -> Self {
+        self.quantity = Some(quantity);
+        self
+    }
+
+    pub fn price(mut self, price: f64) -> Self {
+        self.price = Some(price);
+        self
+    }
+
+    pub fn strategy(mut self, strategy: ExecutionStrategy) -> Self {
+        self.strategy = Some(strategy);
+        self
+    }
+
+    pub fn commission(mut self, commission_per_share: f64) -> Self {
+        self.commission_per_share = commission_per_share;
+        self
+    }
+
+    pub fn build(self) -> Result<Order, BuildError> {
+        let symbol = self.symbol.ok_or(BuildError::MissingField("symbol"))?;
+        let quantity = self.quantity.ok_or(BuildError::MissingField("quantity"))?;
+        let price = self.price.ok_or(BuildError::MissingField("price"))?;
+        let strategy = self.strategy.ok_or(BuildError::MissingField("strategy"))?;
+        if quantity == 0 {
+            return Err(BuildError::ZeroQuantity);
+        }
+        let order = Order::new(&symbol, quantity, price, strategy);
+        Ok(order.with_commission(self.commission_per_share))
+    }
+}
+
+// ============================================================
+// APPROACH 2: Trait Objects (open extension)
+// ============================================================
+
+// The base trait — Rust's equivalent of an abstract interface.
+// We add a clone_box method to enable cloning of trait objects.
+pub trait ExecutionStrategyTrait: fmt::Debug {
+    fn execute(&self, symbol: &str, quantity: u32, price: f64)
+        -> Result<Vec<ChildOrder>, StrategyError>;
+    fn estimated_cost(&self, quantity: u32, price: f64) -> f64;
+    fn name(&self) -> &str;
+    fn clone_box(&self) -> Box<dyn ExecutionStrategyTrait>;
+}
+
+impl Clone for Box<dyn ExecutionStrategyTrait> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+// Concrete strategies — plain structs, opt into the trait explicitly
+#[derive(Debug, Clone)]
+pub struct TwapStrategy {
+    pub slices: u32,
+}
+
+impl ExecutionStrategyTrait for TwapStrategy {
+    fn execute(
+        &self,
+        symbol: &str,
+        quantity: u32,
+        price: f64,
+    ) -> Result<Vec<ChildOrder>, StrategyError> {
+        if self.slices == 0 {
+            return Err(StrategyError::InvalidSlices);
+        }
+        let per_slice = quantity / self.slices;
+        println!(
+            "[TWAP-trait] Executing {}: {} shares @ ${:.2} across {} slices ({}/slice)",
+            symbol, quantity, price, self.slices, per_slice
+        );
+        Ok(even_schedule(quantity, self.slices, price))
+    }
+
+    fn estimated_cost(&self, _quantity: u32, _price: f64) -> f64 {
+        spread_cost(self.slices)
+    }
+
+    fn name(&self) -> &str {
+        "TWAP"
+    }
+
+    fn clone_box(&self) -> Box<dyn ExecutionStrategyTrait> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VwapStrategy {
+    pub participation_rate: f64,
+}
+
+impl ExecutionStrategyTrait for VwapStrategy {
+    fn execute(
+        &self,
+        symbol: &str,
+        quantity: u32,
+        price: f64,
+    ) -> Result<Vec<ChildOrder>, StrategyError> {
+        validate_participation(self.participation_rate)?;
+        println!(
+            "[VWAP-trait] Executing {}: {} shares @ ${:.2} with {:.0}% participation",
+            symbol, quantity, price, self.participation_rate * 100.0
+        );
+        let chunk = participation_chunk(quantity, self.participation_rate);
+        Ok(chunked_schedule(quantity, chunk, price))
+    }
+
+    fn estimated_cost(&self, quantity: u32, price: f64) -> f64 {
+        vwap_cost(quantity, price, self.participation_rate)
+    }
+
+    fn name(&self) -> &str {
+        "VWAP"
+    }
+
+    fn clone_box(&self) -> Box<dyn ExecutionStrategyTrait> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IcebergStrategy {
+    pub visible_qty: u32,
+}
+
+impl ExecutionStrategyTrait for IcebergStrategy {
+    fn execute(
+        &self,
+        symbol: &str,
+        quantity: u32,
+        price: f64,
+    ) -> Result<Vec<ChildOrder>, StrategyError> {
+        println!(
+            "[Iceberg-trait] Executing {}: {} shares @ ${:.2} showing {} at a time",
+            symbol, quantity, price, self.visible_qty
+        );
+        Ok(chunked_schedule(quantity, self.visible_qty, price))
+    }
+
+    fn estimated_cost(&self, quantity: u32, _price: f64) -> f64 {
+        spread_cost(visible_chunks(quantity, self.visible_qty))
+    }
+
+    fn name(&self) -> &str {
+        "Iceberg"
+    }
+
+    fn clone_box(&self) -> Box<dyn ExecutionStrategyTrait> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SniperStrategy {
+    pub min_size: u32,
+    pub max_price: f64,
+}
+
+impl ExecutionStrategyTrait for SniperStrategy {
+    fn execute(
+        &self,
+        symbol: &str,
+        quantity: u32,
+        price: f64,
+    ) -> Result<Vec<ChildOrder>, StrategyError> {
+        let children = sniper_schedule(quantity, price, self.min_size, self.max_price);
+        let fired = !children.is_empty();
+        print_sniper("Sniper-trait", symbol, quantity, price, self.max_price, fired);
+        Ok(children)
+    }
+
+    fn estimated_cost(&self, quantity: u32, price: f64) -> f64 {
+        spread_cost(sniper_fires(quantity, price, self.min_size, self.max_price) as u32)
+    }
+
+    fn name(&self) -> &str {
+        "Sniper"
+    }
+
+    fn clone_box(&self) -> Box<dyn ExecutionStrategyTrait> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImplementationShortfallStrategy {
+    pub urgency: f64,
+}
+
+impl ExecutionStrategyTrait for ImplementationShortfallStrategy {
+    fn execute(
+        &self,
+        symbol: &str,
+        quantity: u32,
+        price: f64,
+    ) -> Result<Vec<ChildOrder>, StrategyError> {
+        let children = shortfall_schedule(quantity, self.urgency, price);
+        let slices: Vec<String> = children.iter().map(|c| c.quantity.to_string()).collect();
+        println!(
+            "[IS-trait] Executing {}: {} shares @ ${:.2} with urgency {:.2} (slices: {})",
+            symbol,
+            quantity,
+            price,
+            self.urgency,
+            slices.join(", ")
+        );
+        Ok(children)
+    }
+
+    fn estimated_cost(&self, quantity: u32, price: f64) -> f64 {
+        spread_cost(IS_SLICES) + impact_cost(quantity, price, self.urgency)
+    }
+
+    fn name(&self) -> &str {
+        "IS"
+    }
+
+    fn clone_box(&self) -> Box<dyn ExecutionStrategyTrait> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StealthTwapStrategy {
+    pub slices: u32,
+    pub size_jitter: f64,
+    pub time_jitter: f64,
+    pub seed: u64,
+}
+
+impl ExecutionStrategyTrait for StealthTwapStrategy {
+    fn execute(
+        &self,
+        symbol: &str,
+        quantity: u32,
+        price: f64,
+    ) -> Result<Vec<ChildOrder>, StrategyError> {
+        if self.slices == 0 {
+            return Err(StrategyError::InvalidSlices);
+        }
+        println!(
+            "[Stealth-trait] Executing {}: {} shares @ ${:.2} across {} jittered slices",
+            symbol, quantity, price, self.slices
+        );
+        Ok(stealth_schedule(
+            quantity,
+            self.slices,
+            self.size_jitter,
+            self.time_jitter,
+            self.seed,
+            price,
+        ))
+    }
+
+    fn estimated_cost(&self, _quantity: u32, _price: f64) -> f64 {
+        spread_cost(self.slices)
+    }
+
+    fn name(&self) -> &str {
+        "Stealth"
+    }
+
+    fn clone_box(&self) -> Box<dyn ExecutionStrategyTrait> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PegStrategy {
+    pub reference: PegReference,
+    pub offset_bps: f64,
+}
+
+impl ExecutionStrategyTrait for PegStrategy {
+    fn execute(
+        &self,
+        symbol: &str,
+        quantity: u32,
+        price: f64,
+    ) -> Result<Vec<ChildOrder>, StrategyError> {
+        let pegged = peg_price(self.reference, self.offset_bps, price);
+        println!(
+            "[Peg-trait] Executing {}: {} shares pegged to {} {:+.1}bps @ ${:.4} (mid ${:.2})",
+            symbol, quantity, self.reference, self.offset_bps, pegged, price
+        );
+        Ok(vec![ChildOrder {
+            quantity,
+            price: pegged,
+            time_offset: 0.0,
+        }])
+    }
+
+    fn estimated_cost(&self, _quantity: u32, _price: f64) -> f64 {
+        spread_cost(1)
+    }
+
+    fn name(&self) -> &str {
+        "Peg"
+    }
+
+    fn clone_box(&self) -> Box<dyn ExecutionStrategyTrait> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DarkPoolStrategy {
+    pub min_fill: u32,
+}
+
+impl ExecutionStrategyTrait for DarkPoolStrategy {
+    fn execute(
+        &self,
+        symbol: &str,
+        quantity: u32,
+        price: f64,
+    ) -> Result<Vec<ChildOrder>, StrategyError> {
+        let routed = dark_routable(quantity, self.min_fill);
+        println!(
+            "[Dark-trait] Executing {}: {} shares @ ${:.2} in blocks of {}+ \
+             ({} routed to dark, {} unroutable)",
+            symbol,
+            quantity,
+            price,
+            self.min_fill,
+            routed,
+            quantity - routed
+        );
+        Ok(chunked_schedule(routed, self.min_fill, price))
+    }
+
+    fn estimated_cost(&self, quantity: u32, _price: f64) -> f64 {
+        let routed = dark_routable(quantity, self.min_fill);
+        spread_cost(visible_chunks(routed, self.min_fill))
+    }
+
+    fn name(&self) -> &str {
+        "Dark"
+    }
+
+    fn clone_box(&self) -> Box<dyn ExecutionStrategyTrait> {
+        Box::new(self.clone())
+    }
+}
+
+// Bridge from config (the enum) to code paths that take a trait
+// object: each variant becomes its struct form with the same
+// parameters, so name() and the child orders match
+impl From<ExecutionStrategy> for Box<dyn ExecutionStrategyTrait> {
+    fn from(strategy: ExecutionStrategy) -> Self {
+        match strategy {
+            ExecutionStrategy::Twap { slices } => Box::new(TwapStrategy { slices }),
+            ExecutionStrategy::Vwap { participation_rate } => {
+                Box::new(VwapStrategy { participation_rate })
+            }
+            ExecutionStrategy::Iceberg { visible_qty } => Box::new(IcebergStrategy { visible_qty }),
+            ExecutionStrategy::StealthTwap {
+                slices,
+                size_jitter,
+                time_jitter,
+                seed,
+            } => Box::new(StealthTwapStrategy {
+                slices,
+                size_jitter,
+                time_jitter,
+                seed,
+            }),
+            ExecutionStrategy::ImplementationShortfall { urgency } => {
+                Box::new(ImplementationShortfallStrategy { urgency })
+            }
+            ExecutionStrategy::Peg {
+                reference,
+                offset_bps,
+            } => Box::new(PegStrategy {
+                reference,
+                offset_bps,
+            }),
+            ExecutionStrategy::DarkPool { min_fill } => Box::new(DarkPoolStrategy { min_fill }),
+            ExecutionStrategy::Sniper {
+                min_size,
+                max_price,
+            } => Box::new(SniperStrategy {
+                min_size,
+                max_price,
+            }),
+        }
+    }
+}
+
+// Runs strategies back to back: each stage takes `split` of what
+// is still unexecuted and the last stage takes the remainder, so
+// with two stages the first works the leading `split` fraction and
+// the second finishes the order. Box<dyn ...> is Clone via
+// clone_box, so the derive deep-clones every stage.
+#[derive(Debug, Clone)]
+pub struct CompositeStrategy {
+    pub stages: Vec<Box<dyn ExecutionStrategyTrait>>,
+    pub split: f64,
+}
+
+impl CompositeStrategy {
+    pub fn allocations(&self, quantity: u32) -> Vec<u32> {
+        let split = self.split.clamp(0.0, 1.0);
+        let mut remaining = quantity;
+        let mut out = Vec::with_capacity(self.stages.len());
+        for i in 0..self.stages.len() {
+            let qty = if i + 1 == self.stages.len() {
+                remaining
+            } else {
+                (remaining as f64 * split).round() as u32
+            };
+            remaining -= qty;
+            out.push(qty);
+        }
+        out
+    }
+}
+
+impl ExecutionStrategyTrait for CompositeStrategy {
+    fn execute(
+        &self,
+        symbol: &str,
+        quantity: u32,
+        price: f64,
+    ) -> Result<Vec<ChildOrder>, StrategyError> {
+        // Each stage's schedule is squeezed into its own share of
+        // the window so the combined offsets stay ordered.
+        let n = self.stages.len() as f64;
+        let mut children = Vec::new();
+        for (i, (stage, qty)) in self.stages.iter().zip(self.allocations(quantity)).enumerate() {
+            for child in stage.execute(symbol, qty, price)? {
+                children.push(ChildOrder {
+                    time_offset: (i as f64 + child.time_offset) / n,
+                    ..child
+                });
+            }
+        }
+        Ok(children)
+    }
+
+    fn estimated_cost(&self, quantity: u32, price: f64) -> f64 {
+        self.stages
+            .iter()
+            .zip(self.allocations(quantity))
+            .map(|(stage, qty)| stage.estimated_cost(qty, price))
+            .sum()
+    }
+
+    fn name(&self) -> &str {
+        "Composite"
+    }
+
+    fn clone_box(&self) -> Box<dyn ExecutionStrategyTrait> {
+        Box::new(self.clone())
+    }
+}
+
+// Config-driven routing: strategies are registered by name as
+// factories and instantiated on demand.
+pub type StrategyFactory = Box<dyn Fn() -> Box<dyn ExecutionStrategyTrait>>;
+
+pub struct StrategyRegistry {
+    pub factories: HashMap<String, StrategyFactory>,
+}
+
+impl Default for StrategyRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StrategyRegistry {
+    pub fn new() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register("TWAP", || Box::new(TwapStrategy { slices: 5 }));
+        registry.register("VWAP", || {
+            Box::new(VwapStrategy {
+                participation_rate: 0.15,
+            })
+        });
+        registry.register("Iceberg", || Box::new(IcebergStrategy { visible_qty: 500 }));
+        registry
+    }
+
+    // Re-registering a name replaces the previous factory
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn() -> Box<dyn ExecutionStrategyTrait> + 'static,
+    {
+        self.factories.insert(name.to_string(), Box::new(factory));
+    }
+
+    pub fn create(&self, name: &str) -> Option<Box<dyn ExecutionStrategyTrait>> {
+        self.factories.get(name).map(|factory| factory())
+    }
+}
+
+#[derive(Debug)]
+pub struct TraitOrder {
+    pub symbol: String,
+    pub quantity: u32,
+    pub price: f64,
+    pub strategy: Box<dyn ExecutionStrategyTrait>,
+}
+
+impl Clone for TraitOrder {
+    fn clone(&self) -> Self {
+        Self {
+            symbol: self.symbol.clone(),
+            quantity: self.quantity,
+            price: self.price,
+            strategy: self.strategy.clone_box(),
+        }
+    }
+}
+
+impl TraitOrder {
+    pub fn new(
+        symbol: &str,
+        qty: u32,
+        price: f64,
+        strategy: Box<dyn ExecutionStrategyTrait>,
+    ) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            quantity: qty,
+            price,
+            strategy,
+        }
+    }
+
+    pub fn set_strategy(&mut self, strategy: Box<dyn ExecutionStrategyTrait>) {
+        self.strategy = strategy;
+    }
+
+    pub fn send(&self) -> Result<(), StrategyError> {
+        println!(
+            "Order: {} {} shares @ ${:.2} using {}",
+            self.symbol,
+            self.quantity,
+            self.price,
+            self.strategy.name()
+        );
+        self.strategy
+            .execute(&self.symbol, self.quantity, self.price)?;
+        Ok(())
+    }
+}
+
+// ============================================================
+// Async variant (feature = "async")
+//
+// Same shape as ExecutionStrategyTrait, but execute is an async
+// fn so slices can be paced against an async venue client. Uses
+// native async fn in traits, so it's for static dispatch only.
+// ============================================================
+
+#[cfg(feature = "async")]
+pub mod async_strategies {
+    use super::StrategyError;
+    use std::time::Duration;
+
+    // Resolves to the number of child orders sent. The returned
+    // future carries no Send bound; callers await it on their own
+    // runtime with a concrete strategy type.
+    #[allow(async_fn_in_trait)]
+    pub trait AsyncExecutionStrategy {
+        async fn execute(&self, symbol: &str, quantity: u32, price: f64)
+            -> Result<u32, StrategyError>;
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct AsyncTwapStrategy {
+        pub slices: u32,
+        pub interval: Duration,
+    }
+
+    impl AsyncExecutionStrategy for AsyncTwapStrategy {
+        async fn execute(
+            &self,
+            symbol: &str,
+            quantity: u32,
+            price: f64,
+        ) -> Result<u32, StrategyError> {
+            if self.slices == 0 {
+                return Err(StrategyError::InvalidSlices);
+            }
+            let children = super::even_schedule(quantity, self.slices, price);
+            for (i, child) in children.iter().enumerate() {
+                if i > 0 {
+                    tokio::time::sleep(self.interval).await;
+                }
+                println!(
+                    "[TWAP-async] slice {}/{}: {} {} @ ${:.2}",
+                    i + 1,
+                    self.slices,
+                    child.quantity,
+                    symbol,
+                    child.price
+                );
+            }
+            Ok(self.slices)
+        }
+    }
+
+    // Trades participation_rate of the order per interval until done
+    #[derive(Debug, Clone)]
+    pub struct AsyncVwapStrategy {
+        pub participation_rate: f64,
+        pub interval: Duration,
+    }
+
+    impl AsyncExecutionStrategy for AsyncVwapStrategy {
+        async fn execute(
+            &self,
+            symbol: &str,
+            quantity: u32,
+            price: f64,
+        ) -> Result<u32, StrategyError> {
+            super::validate_participation(self.participation_rate)?;
+            let per_interval = ((quantity as f64 * self.participation_rate).ceil() as u32).max(1);
+            let mut remaining = quantity;
+            let mut sent = 0;
+            while remaining > 0 {
+                if sent > 0 {
+                    tokio::time::sleep(self.interval).await;
+                }
+                let qty = per_interval.min(remaining);
+                remaining -= qty;
+                sent += 1;
+                println!(
+                    "[VWAP-async] interval {}: {} {} @ ${:.2} ({} left)",
+                    sent, qty, symbol, price, remaining
+                );
+            }
+            Ok(sent)
+        }
+    }
+}
+
+// ============================================================
+// APPROACH 3: Closures (most idiomatic for simple strategies)
+// ============================================================
+
+// Arc rather than Box so a closure strategy clones like the
+// other two approaches — copies share the same captured state.
+pub type StrategyFn = Arc<dyn Fn(&str, u32, f64) -> Vec<ChildOrder> + Send + Sync>;
+
+// StrategyFn is infallible, so zero slices yields an empty schedule
+// instead of dividing by zero
+pub fn twap_closure(slices: u32) -> StrategyFn {
+    Arc::new(move |symbol, qty, price| {
+        if slices == 0 {
+            println!("[TWAP-closure] Rejected {}: TWAP requires at least one slice", symbol);
+            return Vec::new();
+        }
+        let per_slice = qty / slices;
+        println!(
+            "[TWAP-closure] Executing {}: {} shares @ ${:.2} across {} slices ({}/slice)",
+            symbol, qty, price, slices, per_slice
+        );
+        even_schedule(qty, slices, price)
+    })
+}
+
+pub fn vwap_closure(participation_rate: f64) -> StrategyFn {
+    Arc::new(move |symbol, qty, price| {
+        println!(
+            "[VWAP-closure] Executing {}: {} shares @ ${:.2} with {:.0}% participation",
+            symbol, qty, price, participation_rate * 100.0
+        );
+        chunked_schedule(qty, participation_chunk(qty, participation_rate), price)
+    })
+}
+
+// Functional counterpart of CompositeStrategy: `first` works the
+// leading `split` fraction, `second` the remainder. The returned
+// closure owns both, and each leg's schedule is squeezed into its
+// half of the window so the combined offsets stay ordered.
+pub fn sequence(first: StrategyFn, second: StrategyFn, split: f64) -> StrategyFn {
+    let split = split.clamp(0.0, 1.0);
+    Arc::new(move |symbol, qty, price| {
+        let lead = (qty as f64 * split).round() as u32;
+        let legs = [(&first, lead), (&second, qty - lead)];
+        let mut children = Vec::new();
+        for (i, (leg, leg_qty)) in legs.into_iter().enumerate() {
+            for child in leg(symbol, leg_qty, price) {
+                children.push(ChildOrder {
+                    time_offset: (i as f64 + child.time_offset) / 2.0,
+                    ..child
+                });
+            }
+        }
+        children
+    })
+}
@@ -0,0 +1,2036 @@
+// ============================================================
+// Visitor Pattern in Rust — Financial Instrument Operations
+//
+// Rust makes the classic Visitor pattern almost entirely
+// unnecessary. The enum + match combination gives you:
+//
+//   - Exhaustive dispatch (compiler error if you miss a variant)
+//   - No double dispatch indirection
+//   - No accept() boilerplate
+//   - Value semantics via Clone/Copy
+//   - Pattern matching with destructuring (access fields directly)
+//
+// The enum IS the variant. match IS the visit. That's it.
+//
+// We also show a trait-based approach for when you want open
+// extension on the operation side (new instruments without
+// modifying existing operations).
+// ============================================================
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::report::Report;
+
+// --- Pricing errors: bad terms are rejected, not priced ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PricingError {
+    ZeroMaturity,
+    NegativeNotional(f64),
+    NegativeRate(f64),
+    NonPositivePrice(f64),
+    InvalidRecovery(f64),
+    MissingFxRate(Currency),
+}
+
+impl fmt::Display for PricingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroMaturity => write!(f, "instrument has no remaining term"),
+            Self::NegativeNotional(n) => write!(f, "notional {:.2} is negative", n),
+            Self::NegativeRate(r) => write!(f, "rate {:.4} is negative", r),
+            Self::NonPositivePrice(p) => write!(f, "price {:.2} must be positive", p),
+            Self::InvalidRecovery(r) => write!(f, "recovery rate {} must be in [0, 1)", r),
+            Self::MissingFxRate(c) => write!(f, "no FX rate for {}", c),
+        }
+    }
+}
+
+impl std::error::Error for PricingError {}
+
+// --- Currencies: every instrument is denominated in one ---
+
+// Variants are spelled as their ISO 4217 codes
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Currency {
+    USD,
+    EUR,
+    GBP,
+    JPY,
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = match self {
+            Self::USD => "USD",
+            Self::EUR => "EUR",
+            Self::GBP => "GBP",
+            Self::JPY => "JPY",
+        };
+        write!(f, "{}", code)
+    }
+}
+
+impl std::str::FromStr for Currency {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "USD" => Ok(Self::USD),
+            "EUR" => Ok(Self::EUR),
+            "GBP" => Ok(Self::GBP),
+            "JPY" => Ok(Self::JPY),
+            _ => Err(()),
+        }
+    }
+}
+
+// --- Instrument types: plain structs ---
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Bond {
+    pub issuer: String,
+    pub currency: Currency,
+    pub face_value: f64,
+    pub coupon_rate: f64,
+    pub maturity_years: u32,
+    pub callable: std::option::Option<CallSchedule>,
+}
+
+// Issuer call: from `first_call_year` onward the bond can be
+// redeemed at `call_price` on any coupon date.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CallSchedule {
+    pub first_call_year: u32,
+    pub call_price: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Swap {
+    pub currency: Currency,
+    pub notional: f64,
+    pub fixed_rate: f64,
+    pub tenor_years: u32,
+}
+
+// Forward rate agreement on a single accrual period, quoted as
+// start x end in months from today ("3x6")
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Fra {
+    pub currency: Currency,
+    pub notional: f64,
+    pub fixed_rate: f64,
+    pub start_months: u32,
+    pub end_months: u32,
+}
+
+impl Fra {
+    // An inverted FRA (end before start) accrues nothing; validate
+    // rejects it before pricing
+    pub fn accrual(&self) -> f64 {
+        self.end_months.saturating_sub(self.start_months) as f64 / 12.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Option {
+    pub underlying: String,
+    pub currency: Currency,
+    pub strike: f64,
+    pub spot: f64,
+    pub is_call: bool,
+    pub vol: f64,            // annualized
+    pub time_to_expiry: f64, // years
+}
+
+// Exchange-traded futures position; contract_size is the dollar
+// value of one index point, negative num_contracts is short
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Future {
+    pub underlying: String,
+    pub currency: Currency,
+    pub contract_price: f64,
+    pub contract_size: f64,
+    pub num_contracts: i32,
+}
+
+impl Future {
+    pub fn notional(&self) -> f64 {
+        self.contract_price * self.contract_size * self.num_contracts as f64
+    }
+
+    pub fn point_value(&self) -> f64 {
+        self.contract_size * self.num_contracts as f64
+    }
+}
+
+// Credit default swap, held as protection buyer. `spread_bps` is
+// the market's running spread for the reference entity; the
+// contract pays the standard CDS_COUPON_BPS.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Cds {
+    pub reference_entity: String,
+    pub currency: Currency,
+    pub notional: f64,
+    pub spread_bps: f64,
+    pub tenor_years: u32,
+    pub recovery_rate: f64,
+}
+
+// Outright FX forward: buy `notional` of base_ccy for quote_ccy at
+// `forward_rate` (quote per base), settling in `settle_months`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FxForward {
+    pub base_ccy: Currency,
+    pub quote_ccy: Currency,
+    pub notional: f64,
+    pub forward_rate: f64,
+    pub settle_months: u32,
+}
+
+impl FxForward {
+    pub fn years(&self) -> f64 {
+        self.settle_months as f64 / 12.0
+    }
+}
+
+// Interest-rate cap (or floor): a strip of annual caplets paying
+// max(rate - strike, 0) (floors: max(strike - rate, 0)) on notional
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CapFloor {
+    pub currency: Currency,
+    pub notional: f64,
+    pub strike_rate: f64,
+    pub tenor_years: u32,
+    pub is_cap: bool,
+}
+
+// Cash equity position; negative shares is short
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Equity {
+    pub ticker: String,
+    pub currency: Currency,
+    pub shares: i32,
+    pub price: f64,
+}
+
+impl Equity {
+    pub fn market_value(&self) -> f64 {
+        self.shares as f64 * self.price
+    }
+}
+
+// Company-issued call on its own stock. Exercise creates new
+// shares, so holders share the payoff with the existing float.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Warrant {
+    pub underlying: String,
+    pub currency: Currency,
+    pub strike: f64,
+    pub spot: f64,
+    pub vol: f64,
+    pub time_to_expiry: f64,
+    pub shares_outstanding: u64,
+    pub warrants_issued: u64,
+}
+
+impl Warrant {
+    // N / (N + M): the share of the firm existing holders keep once
+    // every warrant is exercised. With no shares on record there is
+    // nothing to dilute.
+    pub fn dilution_factor(&self) -> f64 {
+        let total = self.shares_outstanding + self.warrants_issued;
+        if total == 0 {
+            return 1.0;
+        }
+        self.shares_outstanding as f64 / total as f64
+    }
+
+    // The listed call with the same terms
+    pub fn as_option(&self) -> Option {
+        Option {
+            underlying: self.underlying.clone(),
+            currency: self.currency,
+            strike: self.strike,
+            spot: self.spot,
+            is_call: true,
+            vol: self.vol,
+            time_to_expiry: self.time_to_expiry,
+        }
+    }
+}
+
+// --- Eq and Hash, so instruments can be deduplicated in a HashSet ---
+//
+// PartialEq compares f64 terms with ==. Hash has to agree with it,
+// so floats are hashed by bit pattern with -0.0 folded into 0.0.
+// Eq assumes no term is NaN; such an instrument can't be priced.
+
+fn hash_f64<H: Hasher>(x: f64, state: &mut H) {
+    let x = if x == 0.0 { 0.0 } else { x };
+    x.to_bits().hash(state);
+}
+
+macro_rules! impl_eq_hash {
+    ($ty:ident; exact: $($exact:ident),*; float: $($float:ident),*) => {
+        impl Eq for $ty {}
+
+        impl Hash for $ty {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                $(self.$exact.hash(state);)*
+                $(hash_f64(self.$float, state);)*
+            }
+        }
+    };
+}
+
+impl_eq_hash!(
+    Bond;
+    exact: issuer, currency, maturity_years, callable;
+    float: face_value, coupon_rate
+);
+impl_eq_hash!(CallSchedule; exact: first_call_year; float: call_price);
+impl_eq_hash!(Swap; exact: currency, tenor_years; float: notional, fixed_rate);
+impl_eq_hash!(Fra; exact: currency, start_months, end_months; float: notional, fixed_rate);
+impl_eq_hash!(
+    Option;
+    exact: underlying, currency, is_call;
+    float: strike, spot, vol, time_to_expiry
+);
+impl_eq_hash!(
+    Future;
+    exact: underlying, currency, num_contracts;
+    float: contract_price, contract_size
+);
+impl_eq_hash!(
+    Cds;
+    exact: reference_entity, currency, tenor_years;
+    float: notional, spread_bps, recovery_rate
+);
+impl_eq_hash!(FxForward; exact: base_ccy, quote_ccy, settle_months; float: notional, forward_rate);
+impl_eq_hash!(CapFloor; exact: currency, tenor_years, is_cap; float: notional, strike_rate);
+impl_eq_hash!(Equity; exact: ticker, currency, shares; float: price);
+impl_eq_hash!(
+    Warrant;
+    exact: underlying, currency, shares_outstanding, warrants_issued;
+    float: strike, spot, vol, time_to_expiry
+);
+
+// --- Market data: annual zero rates, last tenor extrapolated flat ---
+
+#[derive(Debug, Clone)]
+pub struct DiscountCurve {
+    pub zero_rates: Vec<f64>,
+}
+
+impl DiscountCurve {
+    // None on an empty curve rather than indexing past the end
+    pub fn zero_rate(&self, year: u32) -> std::option::Option<f64> {
+        let last = self.zero_rates.len().checked_sub(1)?;
+        Some(self.zero_rates[(year.max(1) as usize - 1).min(last)])
+    }
+
+    pub fn discount_factor(&self, year: u32, spread: f64) -> std::option::Option<f64> {
+        Some(1.0 / (1.0 + self.zero_rate(year)? + spread).powi(year as i32))
+    }
+
+    // One-year forward rate from `year - 1` to `year`
+    pub fn forward_rate(&self, year: u32) -> std::option::Option<f64> {
+        let prev = if year > 1 {
+            self.discount_factor(year - 1, 0.0)?
+        } else {
+            1.0
+        };
+        Some(prev / self.discount_factor(year, 0.0)? - 1.0)
+    }
+}
+
+// --- The enum IS the polymorphic type ---
+
+// JSON form is internally tagged: { "type": "Bond", ... }
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
+pub enum Instrument {
+    Bond(Bond),
+    Swap(Swap),
+    Fra(Fra),
+    Option(Option),
+    Future(Future),
+    Cds(Cds),
+    FxForward(FxForward),
+    CapFloor(CapFloor),
+    Equity(Equity),
+    Warrant(Warrant),
+}
+
+impl fmt::Display for Instrument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bond(b) => write!(
+                f,
+                "Bond({}, {:.0} {} face, {:.1}% coupon, {}Y)",
+                b.issuer,
+                b.face_value,
+                b.currency,
+                b.coupon_rate * 100.0,
+                b.maturity_years
+            ),
+            Self::Swap(s) => write!(
+                f,
+                "IRS({:.0} {} notional, {:.2}% fixed, {}Y)",
+                s.notional,
+                s.currency,
+                s.fixed_rate * 100.0,
+                s.tenor_years
+            ),
+            Self::Fra(r) => write!(
+                f,
+                "FRA({:.0} {} notional, {:.2}% fixed, {}x{})",
+                r.notional,
+                r.currency,
+                r.fixed_rate * 100.0,
+                r.start_months,
+                r.end_months
+            ),
+            Self::Option(o) => write!(
+                f,
+                "{} {}(K={:.2}, S={:.2} {})",
+                o.underlying,
+                if o.is_call { "Call" } else { "Put" },
+                o.strike,
+                o.spot,
+                o.currency
+            ),
+            Self::Future(fut) => write!(
+                f,
+                "{} Future({} x {:.2}, {:.0} {}/pt)",
+                fut.underlying,
+                fut.num_contracts,
+                fut.contract_price,
+                fut.contract_size,
+                fut.currency
+            ),
+            Self::Cds(c) => write!(
+                f,
+                "CDS({}, {:.0} {} notional, {:.0}bp, {}Y)",
+                c.reference_entity, c.notional, c.currency, c.spread_bps, c.tenor_years
+            ),
+            Self::FxForward(fx) => write!(
+                f,
+                "{}/{} Fwd({:.0} {} @ {:.4}, {}M)",
+                fx.base_ccy,
+                fx.quote_ccy,
+                fx.notional,
+                fx.base_ccy,
+                fx.forward_rate,
+                fx.settle_months
+            ),
+            Self::CapFloor(c) => write!(
+                f,
+                "{}({:.0} {} notional, {:.2}% strike, {}Y)",
+                if c.is_cap { "Cap" } else { "Floor" },
+                c.notional,
+                c.currency,
+                c.strike_rate * 100.0,
+                c.tenor_years
+            ),
+            Self::Equity(e) => write!(
+                f,
+                "{} Equity({} @ {:.2} {})",
+                e.ticker, e.shares, e.price, e.currency
+            ),
+            Self::Warrant(w) => write!(
+                f,
+                "{} Warrant(K={:.2}, S={:.2} {}, {:.1}% dilution)",
+                w.underlying,
+                w.strike,
+                w.spot,
+                w.currency,
+                (1.0 - w.dilution_factor()) * 100.0
+            ),
+        }
+    }
+}
+
+impl Instrument {
+    pub fn currency(&self) -> Currency {
+        match self {
+            Self::Bond(b) => b.currency,
+            Self::Swap(s) => s.currency,
+            Self::Fra(r) => r.currency,
+            Self::Option(o) => o.currency,
+            Self::Future(fut) => fut.currency,
+            Self::Cds(c) => c.currency,
+            // Forward P&L is settled in the quote currency
+            Self::FxForward(fx) => fx.quote_ccy,
+            Self::CapFloor(c) => c.currency,
+            Self::Equity(e) => e.currency,
+            Self::Warrant(w) => w.currency,
+        }
+    }
+
+    // Variant discriminant for reports; exhaustive, so a new
+    // variant can't be left unnamed
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bond(_) => "Bond",
+            Self::Swap(_) => "Swap",
+            Self::Fra(_) => "Fra",
+            Self::Option(_) => "Option",
+            Self::Future(_) => "Future",
+            Self::Cds(_) => "Cds",
+            Self::FxForward(_) => "FxForward",
+            Self::CapFloor(_) => "CapFloor",
+            Self::Equity(_) => "Equity",
+            Self::Warrant(_) => "Warrant",
+        }
+    }
+}
+
+// Terms and default-rate price, or why the instrument can't be priced
+impl Report for Instrument {
+    fn report(&self) -> String {
+        match price_default(self) {
+            Ok(px) => format!("{} = ${:.2}", self, px),
+            Err(e) => format!("{}   rejected: {}", self, e),
+        }
+    }
+}
+
+// ============================================================
+// "Visitors" are just functions that match on the enum.
+// No Visitor trait, no accept(), no double dispatch.
+// ============================================================
+
+// The demo market: the floating-leg rate swaps, FRAs and caps/floors
+// are marked against, and the flat risk-free rate everything is
+// discounted at. Only MarketData::demo reads these.
+pub const PAR_RATE: f64 = 0.04;
+pub const DEFAULT_DISCOUNT_RATE: f64 = 0.05;
+
+// Everything pricing reads from the market. `risk_free` discounts
+// bonds, CDS and caps/floors and is the Black-Scholes rate;
+// `swap_rate` is the float leg of swaps, FRAs and caps/floors;
+// `spot_overrides` re-marks options, warrants, futures and equities
+// by underlying or ticker, and the instrument's own quote stands
+// where there is none; `fx` holds each currency's USD value and
+// annual deposit rate, for FX forwards and currency conversion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketData {
+    pub risk_free: f64,
+    pub swap_rate: f64,
+    pub spot_overrides: HashMap<String, f64>,
+    pub fx: HashMap<Currency, (f64, f64)>,
+}
+
+impl MarketData {
+    pub fn demo() -> Self {
+        Self {
+            risk_free: DEFAULT_DISCOUNT_RATE,
+            swap_rate: PAR_RATE,
+            spot_overrides: HashMap::new(),
+            fx: HashMap::from([
+                (Currency::USD, (1.0, 0.050)),
+                (Currency::EUR, (1.08, 0.035)),
+                (Currency::GBP, (1.27, 0.045)),
+                (Currency::JPY, (0.0067, 0.001)),
+            ]),
+        }
+    }
+
+    pub fn with_spot(mut self, name: &str, spot: f64) -> Self {
+        self.spot_overrides.insert(name.to_string(), spot);
+        self
+    }
+
+    pub fn with_fx(mut self, ccy: Currency, usd_value: f64, deposit_rate: f64) -> Self {
+        self.fx.insert(ccy, (usd_value, deposit_rate));
+        self
+    }
+
+    // USD value of one unit of `ccy` and its annual deposit rate
+    pub fn fx_quote(&self, ccy: Currency) -> Result<(f64, f64), PricingError> {
+        self.fx.get(&ccy).copied().ok_or(PricingError::MissingFxRate(ccy))
+    }
+
+    // Copy of `inst` with its spot replaced by any override
+    pub fn remark(&self, inst: &Instrument) -> Instrument {
+        let spot = |name: &str, quoted: f64| {
+            self.spot_overrides.get(name).copied().unwrap_or(quoted)
+        };
+        let mut inst = inst.clone();
+        match &mut inst {
+            Instrument::Option(o) => o.spot = spot(&o.underlying, o.spot),
+            Instrument::Warrant(w) => w.spot = spot(&w.underlying, w.spot),
+            Instrument::Future(fut) => {
+                fut.contract_price = spot(&fut.underlying, fut.contract_price)
+            }
+            Instrument::Equity(e) => e.price = spot(&e.ticker, e.price),
+            _ => {}
+        }
+        inst
+    }
+}
+
+pub fn bond_pv(b: &Bond, discount_rate: f64) -> f64 {
+    let growth = 1.0 + discount_rate;
+    let mut pv = 0.0;
+    for i in 1..=b.maturity_years {
+        pv += (b.face_value * b.coupon_rate) / growth.powi(i as i32);
+    }
+    pv + b.face_value / growth.powi(b.maturity_years as i32)
+}
+
+// Receive-fixed, undiscounted
+pub fn swap_npv(s: &Swap, market_rate: f64) -> f64 {
+    s.notional * (s.fixed_rate - market_rate) * s.tenor_years as f64
+}
+
+// Receive-fixed FRA: the period's rate difference, discounted from
+// the payment date
+pub fn fra_npv(r: &Fra, market_rate: f64) -> f64 {
+    let df = 1.0 / (1.0 + market_rate).powf(r.end_months as f64 / 12.0);
+    r.notional * (r.fixed_rate - market_rate) * r.accrual() * df
+}
+
+pub fn validate(inst: &Instrument) -> Result<(), PricingError> {
+    let non_negative = |notional: f64| {
+        if notional < 0.0 {
+            Err(PricingError::NegativeNotional(notional))
+        } else {
+            Ok(())
+        }
+    };
+    match inst {
+        Instrument::Bond(b) => {
+            if b.maturity_years == 0 {
+                return Err(PricingError::ZeroMaturity);
+            }
+            if b.coupon_rate < 0.0 {
+                return Err(PricingError::NegativeRate(b.coupon_rate));
+            }
+            non_negative(b.face_value)
+        }
+        Instrument::Swap(s) => {
+            if s.tenor_years == 0 {
+                return Err(PricingError::ZeroMaturity);
+            }
+            non_negative(s.notional)
+        }
+        Instrument::Fra(r) => {
+            if r.end_months <= r.start_months {
+                return Err(PricingError::ZeroMaturity);
+            }
+            non_negative(r.notional)
+        }
+        Instrument::Option(o) => {
+            if o.strike <= 0.0 {
+                return Err(PricingError::NonPositivePrice(o.strike));
+            }
+            if o.spot <= 0.0 {
+                return Err(PricingError::NonPositivePrice(o.spot));
+            }
+            Ok(())
+        }
+        Instrument::Future(fut) => {
+            if fut.contract_price <= 0.0 {
+                return Err(PricingError::NonPositivePrice(fut.contract_price));
+            }
+            Ok(())
+        }
+        Instrument::Cds(c) => {
+            if c.tenor_years == 0 {
+                return Err(PricingError::ZeroMaturity);
+            }
+            if c.spread_bps < 0.0 {
+                return Err(PricingError::NegativeRate(c.spread_bps / 10_000.0));
+            }
+            // Full recovery leaves nothing to insure: the hazard rate
+            // would divide by zero
+            if !(0.0..1.0).contains(&c.recovery_rate) {
+                return Err(PricingError::InvalidRecovery(c.recovery_rate));
+            }
+            non_negative(c.notional)
+        }
+        Instrument::FxForward(fx) => {
+            if fx.settle_months == 0 {
+                return Err(PricingError::ZeroMaturity);
+            }
+            if fx.forward_rate <= 0.0 {
+                return Err(PricingError::NonPositivePrice(fx.forward_rate));
+            }
+            non_negative(fx.notional)
+        }
+        Instrument::CapFloor(c) => {
+            if c.tenor_years == 0 {
+                return Err(PricingError::ZeroMaturity);
+            }
+            if c.strike_rate < 0.0 {
+                return Err(PricingError::NegativeRate(c.strike_rate));
+            }
+            non_negative(c.notional)
+        }
+        Instrument::Equity(e) => {
+            if e.price <= 0.0 {
+                return Err(PricingError::NonPositivePrice(e.price));
+            }
+            Ok(())
+        }
+        Instrument::Warrant(w) => {
+            if w.strike <= 0.0 {
+                return Err(PricingError::NonPositivePrice(w.strike));
+            }
+            if w.spot <= 0.0 {
+                return Err(PricingError::NonPositivePrice(w.spot));
+            }
+            Ok(())
+        }
+    }
+}
+
+// Covered interest parity: the forward that makes holding either
+// currency to settlement equivalent
+pub fn implied_forward(spot: f64, quote_rate: f64, base_rate: f64, years: f64) -> f64 {
+    spot * ((1.0 + quote_rate) / (1.0 + base_rate)).powf(years)
+}
+
+// Value in quote currency of buying base at the contract rate,
+// against the forward implied by `spot` and the two deposit rates
+pub fn fx_forward_mtm(fx: &FxForward, spot: f64, quote_rate: f64, base_rate: f64) -> f64 {
+    let years = fx.years();
+    let implied = implied_forward(spot, quote_rate, base_rate, years);
+    fx.notional * (implied - fx.forward_rate) / (1.0 + quote_rate).powf(years)
+}
+
+// Spot (quote per base) and deposit rates from `market`
+pub fn fx_forward_market(
+    fx: &FxForward,
+    market: &MarketData,
+) -> Result<(f64, f64, f64), PricingError> {
+    let (base_usd, base_rate) = market.fx_quote(fx.base_ccy)?;
+    let (quote_usd, quote_rate) = market.fx_quote(fx.quote_ccy)?;
+    Ok((base_usd / quote_usd, quote_rate, base_rate))
+}
+
+// Absolute (normal) rate volatility for caplets
+pub const CAPLET_VOL: f64 = 0.01;
+
+pub fn norm_pdf(x: f64) -> f64 {
+    (-0.5 * x * x).exp() / (2.0 * std::f64::consts::PI).sqrt()
+}
+
+// Sum of annual caplets (or floorlets) priced with the normal model
+// against a flat `forward_rate`. The caplet paying at year t fixes
+// at t - 1, so the first one is pure intrinsic value.
+pub fn cap_floor_pv(c: &CapFloor, forward_rate: f64, discount_rate: f64) -> f64 {
+    let moneyness = if c.is_cap {
+        forward_rate - c.strike_rate
+    } else {
+        c.strike_rate - forward_rate
+    };
+    let mut pv = 0.0;
+    for t in 1..=c.tenor_years {
+        let std_dev = CAPLET_VOL * ((t - 1) as f64).sqrt();
+        let payoff = if std_dev > 0.0 {
+            let d = moneyness / std_dev;
+            moneyness * norm_cdf(d) + std_dev * norm_pdf(d)
+        } else {
+            moneyness.max(0.0)
+        };
+        pv += c.notional * payoff / (1.0 + discount_rate).powi(t as i32);
+    }
+    pv
+}
+
+// Standard running coupon paid by the protection buyer
+pub const CDS_COUPON_BPS: f64 = 100.0;
+
+// Flat-hazard CDS: default intensity is spread / (1 - recovery).
+// Value to the protection buyer is the protection leg (expected
+// loss in each year) minus the premium leg (coupon paid while the
+// entity survives), with annual periods.
+pub fn cds_pv(c: &Cds, discount_rate: f64, spread_bps: f64) -> f64 {
+    let hazard = spread_bps / 10_000.0 / (1.0 - c.recovery_rate);
+    let survival = |t: u32| (-hazard * t as f64).exp();
+    let mut premium = 0.0;
+    let mut protection = 0.0;
+    for t in 1..=c.tenor_years {
+        let df = 1.0 / (1.0 + discount_rate).powi(t as i32);
+        premium += CDS_COUPON_BPS / 10_000.0 * df * survival(t);
+        protection += (1.0 - c.recovery_rate) * df * (survival(t - 1) - survival(t));
+    }
+    c.notional * (protection - premium)
+}
+
+// Standard normal CDF via the Abramowitz-Stegun erf approximation
+// (7.1.26), accurate to about 1e-7
+pub fn norm_cdf(x: f64) -> f64 {
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.327_591_1 * z);
+    let (a1, a2, a3, a4, a5) = (
+        0.254_829_592,
+        -0.284_496_736,
+        1.421_413_741,
+        -1.453_152_027,
+        1.061_405_429,
+    );
+    let poly = t * (a1 + t * (a2 + t * (a3 + t * (a4 + t * a5))));
+    let erf = 1.0 - poly * (-z * z).exp();
+    if x >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+// European Black-Scholes, no dividends. With no time or no vol
+// left the option is worth its discounted intrinsic value.
+pub fn black_scholes(o: &Option, rate: f64) -> f64 {
+    let df = (-rate * o.time_to_expiry.max(0.0)).exp();
+    if o.time_to_expiry <= 0.0 || o.vol <= 0.0 {
+        return if o.is_call {
+            (o.spot - o.strike * df).max(0.0)
+        } else {
+            (o.strike * df - o.spot).max(0.0)
+        };
+    }
+    let sd = o.vol * o.time_to_expiry.sqrt();
+    let d1 = ((o.spot / o.strike).ln() + (rate + 0.5 * o.vol * o.vol) * o.time_to_expiry) / sd;
+    let d2 = d1 - sd;
+    if o.is_call {
+        o.spot * norm_cdf(d1) - o.strike * df * norm_cdf(d2)
+    } else {
+        o.strike * df * norm_cdf(-d2) - o.spot * norm_cdf(-d1)
+    }
+}
+
+// Black-Scholes delta per unit of underlying. With no time or no
+// vol left it is all or nothing on the discounted strike.
+pub fn black_scholes_delta(o: &Option, rate: f64) -> f64 {
+    let sign = if o.is_call { 1.0 } else { -1.0 };
+    if o.time_to_expiry <= 0.0 || o.vol <= 0.0 {
+        let df = (-rate * o.time_to_expiry.max(0.0)).exp();
+        let in_the_money = sign * (o.spot - o.strike * df) > 0.0;
+        return if in_the_money { sign } else { 0.0 };
+    }
+    let sd = o.vol * o.time_to_expiry.sqrt();
+    let d1 = ((o.spot / o.strike).ln() + (rate + 0.5 * o.vol * o.vol) * o.time_to_expiry) / sd;
+    if o.is_call {
+        norm_cdf(d1)
+    } else {
+        norm_cdf(d1) - 1.0
+    }
+}
+
+// Black-Scholes value of the equivalent call (intrinsic plus time
+// value), scaled by the dilution factor
+pub fn warrant_value(w: &Warrant, rate: f64) -> f64 {
+    w.dilution_factor() * black_scholes(&w.as_option(), rate)
+}
+
+// Spots are re-marked first, so an override that isn't positive is
+// rejected like a bad quote
+pub fn price(inst: &Instrument, market: &MarketData) -> Result<f64, PricingError> {
+    let inst = market.remark(inst);
+    validate(&inst)?;
+    let (risk_free, swap_rate) = (market.risk_free, market.swap_rate);
+    let pv = match &inst {
+        Instrument::Bond(b) => bond_pv(b, risk_free),
+        Instrument::Swap(s) => swap_npv(s, swap_rate),
+        Instrument::Fra(r) => fra_npv(r, swap_rate),
+        Instrument::Option(o) => black_scholes(o, risk_free),
+        Instrument::Future(fut) => fut.notional(),
+        Instrument::Cds(c) => cds_pv(c, risk_free, c.spread_bps),
+        Instrument::FxForward(fx) => {
+            let (spot, quote_rate, base_rate) = fx_forward_market(fx, market)?;
+            fx_forward_mtm(fx, spot, quote_rate, base_rate)
+        }
+        Instrument::CapFloor(c) => cap_floor_pv(c, swap_rate, risk_free),
+        Instrument::Equity(e) => e.market_value(),
+        Instrument::Warrant(w) => warrant_value(w, risk_free),
+    };
+    Ok(pv)
+}
+
+pub fn price_default(inst: &Instrument) -> Result<f64, PricingError> {
+    price(inst, &MarketData::demo())
+}
+
+// --- Pricing models as values: swap the model at runtime ---
+
+pub trait Pricer: fmt::Debug {
+    fn price(&self, inst: &Instrument) -> Result<f64, PricingError>;
+}
+
+// The demo market with its risk-free rate replaced by `rate`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiscountCurvePricer {
+    pub rate: f64,
+}
+
+impl Pricer for DiscountCurvePricer {
+    fn price(&self, inst: &Instrument) -> Result<f64, PricingError> {
+        let market = MarketData {
+            risk_free: self.rate,
+            ..MarketData::demo()
+        };
+        price(inst, &market)
+    }
+}
+
+// Prices in the instrument's own currency, then converts through
+// the USD value of each currency in `market`
+pub fn price_in(
+    inst: &Instrument,
+    market: &MarketData,
+    target: Currency,
+) -> Result<f64, PricingError> {
+    let native = price(inst, market)?;
+    let (native_usd, _) = market.fx_quote(inst.currency())?;
+    let (target_usd, _) = market.fx_quote(target)?;
+    Ok(native * native_usd / target_usd)
+}
+
+// Coupons are annual on a 30/360 basis: one coupon period is
+// 360 days
+pub const DAYS_PER_COUPON: u32 = 360;
+
+pub fn accrued_interest(b: &Bond, days_since_coupon: u32) -> f64 {
+    let days = days_since_coupon.min(DAYS_PER_COUPON) as f64;
+    b.face_value * b.coupon_rate * days / DAYS_PER_COUPON as f64
+}
+
+// Settlement (dirty) price: the clean `price` plus accrued coupon.
+// Only bonds accrue.
+pub fn dirty_price(
+    inst: &Instrument,
+    market: &MarketData,
+    days_since_coupon: u32,
+) -> Result<f64, PricingError> {
+    let accrued = match inst {
+        Instrument::Bond(b) => accrued_interest(b, days_since_coupon),
+        _ => 0.0,
+    };
+    Ok(price(inst, market)? + accrued)
+}
+
+// Scenario repricing: rates move by `rate_shock_bps` for bonds,
+// swaps, FRAs, caps/floors, CDS and the quote leg of FX forwards; option
+// and warrant spot, futures, equity prices and FX spot move by
+// `spot_shock_pct` percent, all from the levels in `market`
+pub fn stress_price(
+    inst: &Instrument,
+    market: &MarketData,
+    rate_shock_bps: f64,
+    spot_shock_pct: f64,
+) -> Result<f64, PricingError> {
+    let inst = market.remark(inst);
+    validate(&inst)?;
+    let shock = rate_shock_bps / 10_000.0;
+    let (risk_free, swap_rate) = (market.risk_free, market.swap_rate);
+    let pv = match &inst {
+        Instrument::Bond(b) => bond_pv(b, risk_free + shock),
+        Instrument::Swap(s) => swap_npv(s, swap_rate + shock),
+        Instrument::Fra(r) => fra_npv(r, swap_rate + shock),
+        Instrument::Option(o) => {
+            let shocked = Option {
+                spot: o.spot * (1.0 + spot_shock_pct / 100.0),
+                ..o.clone()
+            };
+            black_scholes(&shocked, risk_free)
+        }
+        Instrument::Future(fut) => fut.notional() * (1.0 + spot_shock_pct / 100.0),
+        Instrument::Cds(c) => cds_pv(c, risk_free + shock, c.spread_bps),
+        Instrument::FxForward(fx) => {
+            let (spot, quote_rate, base_rate) = fx_forward_market(fx, market)?;
+            let shocked_spot = spot * (1.0 + spot_shock_pct / 100.0);
+            fx_forward_mtm(fx, shocked_spot, quote_rate + shock, base_rate)
+        }
+        Instrument::CapFloor(c) => cap_floor_pv(c, swap_rate + shock, risk_free + shock),
+        Instrument::Equity(e) => e.market_value() * (1.0 + spot_shock_pct / 100.0),
+        Instrument::Warrant(w) => {
+            let shocked = Warrant {
+                spot: w.spot * (1.0 + spot_shock_pct / 100.0),
+                ..w.clone()
+            };
+            warrant_value(&shocked, risk_free)
+        }
+    };
+    Ok(pv)
+}
+
+// --- In-place updates ---
+
+// Mutable counterpart of `visit`: the closure gets the instrument
+// itself, so it can rewrite terms without rebuilding the Vec
+pub fn visit_mut(inst: &mut Instrument, mut f: impl FnMut(&mut Instrument)) {
+    f(inst)
+}
+
+// Re-marks every option's and warrant's spot by `pct` percent;
+// other instruments are left as they are
+pub fn bump_spot(portfolio: &mut [Instrument], pct: f64) {
+    for inst in portfolio.iter_mut() {
+        visit_mut(inst, |inst| match inst {
+            Instrument::Option(o) => o.spot *= 1.0 + pct / 100.0,
+            Instrument::Warrant(w) => w.spot *= 1.0 + pct / 100.0,
+            _ => {}
+        });
+    }
+}
+
+pub fn bond_duration(b: &Bond) -> f64 {
+    b.maturity_years as f64 * 0.9
+}
+
+// Rate sensitivity per basis point; options, futures, CDS, FX
+// forwards, equities and warrants carry none here
+pub fn dv01(inst: &Instrument, market: &MarketData) -> f64 {
+    match inst {
+        Instrument::Bond(b) => b.face_value * bond_duration(b) * 0.0001,
+        Instrument::Swap(s) => s.notional * s.tenor_years as f64 * 0.0001,
+        Instrument::Fra(r) => r.notional * r.accrual() * 0.0001,
+        Instrument::CapFloor(c) => {
+            let (risk_free, swap_rate) = (market.risk_free, market.swap_rate);
+            let base = cap_floor_pv(c, swap_rate, risk_free);
+            (cap_floor_pv(c, swap_rate + 0.0001, risk_free) - base).abs()
+        }
+        Instrument::Option(_)
+        | Instrument::Future(_)
+        | Instrument::Cds(_)
+        | Instrument::FxForward(_)
+        | Instrument::Equity(_)
+        | Instrument::Warrant(_) => 0.0,
+    }
+}
+
+// Sensitivities that apply to an instrument; `None` where a measure
+// doesn't apply. Delta is in units of the underlying: points for
+// futures, base currency for FX forwards.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RiskMetrics {
+    pub duration: std::option::Option<f64>,
+    pub dv01: std::option::Option<f64>,
+    pub cs01: std::option::Option<f64>,
+    pub delta: std::option::Option<f64>,
+}
+
+impl fmt::Display for RiskMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(d) = self.duration {
+            parts.push(format!("duration={:.1}", d));
+        }
+        if let Some(d) = self.dv01 {
+            parts.push(format!("DV01=${:.2}", d));
+        }
+        if let Some(c) = self.cs01 {
+            parts.push(format!("CS01=${:.2}", c));
+        }
+        if let Some(d) = self.delta {
+            parts.push(format!("delta={:.2}", d));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+pub fn risk_report(inst: &Instrument, market: &MarketData) -> RiskMetrics {
+    let inst = market.remark(inst);
+    match &inst {
+        Instrument::Bond(b) => RiskMetrics {
+            duration: Some(bond_duration(b)),
+            dv01: Some(dv01(&inst, market)),
+            ..RiskMetrics::default()
+        },
+        Instrument::Swap(_) | Instrument::Fra(_) | Instrument::CapFloor(_) => RiskMetrics {
+            dv01: Some(dv01(&inst, market)),
+            ..RiskMetrics::default()
+        },
+        Instrument::Option(o) => RiskMetrics {
+            delta: Some(black_scholes_delta(o, market.risk_free)),
+            ..RiskMetrics::default()
+        },
+        Instrument::Future(fut) => RiskMetrics {
+            delta: Some(fut.point_value()),
+            ..RiskMetrics::default()
+        },
+        Instrument::Cds(c) => {
+            // Credit spread sensitivity: value change for +1bp
+            let base = cds_pv(c, market.risk_free, c.spread_bps);
+            let bumped = cds_pv(c, market.risk_free, c.spread_bps + 1.0);
+            RiskMetrics {
+                cs01: Some(bumped - base),
+                ..RiskMetrics::default()
+            }
+        }
+        Instrument::FxForward(fx) => RiskMetrics {
+            delta: Some(fx.notional),
+            ..RiskMetrics::default()
+        },
+        Instrument::Equity(e) => RiskMetrics {
+            dv01: Some(0.0),
+            delta: Some(e.shares as f64),
+            ..RiskMetrics::default()
+        },
+        // The option's delta, shared with the diluted holders
+        Instrument::Warrant(w) => {
+            let delta = black_scholes_delta(&w.as_option(), market.risk_free);
+            RiskMetrics {
+                delta: Some(delta * w.dilution_factor()),
+                ..RiskMetrics::default()
+            }
+        }
+    }
+}
+
+// Capital charge with the position's direction: negative for
+// short futures and equities, so opposite positions can offset when
+// netted. FX forwards are charged at spot from `market`, or at the
+// contract rate when the market has no quote for the pair.
+pub fn signed_capital_charge(inst: &Instrument, market: &MarketData) -> f64 {
+    match inst {
+        Instrument::Bond(b) => b.face_value * 0.08,
+        Instrument::Swap(s) => s.notional * 0.05 * s.tenor_years as f64,
+        Instrument::Fra(r) => r.notional * 0.05 * r.accrual(),
+        Instrument::Option(o) => o.spot * 100.0 * 0.10,
+        Instrument::Future(fut) => fut.notional() * 0.06, // initial margin
+        Instrument::Cds(c) => c.notional * (1.0 - c.recovery_rate) * 0.08,
+        Instrument::FxForward(fx) => {
+            let spot = fx_forward_market(fx, market).map_or(fx.forward_rate, |(spot, ..)| spot);
+            fx.notional * spot * 0.04
+        }
+        Instrument::CapFloor(c) => c.notional * 0.01 * c.tenor_years as f64,
+        Instrument::Equity(e) => e.market_value() * 0.15, // haircut
+        Instrument::Warrant(w) => w.spot * 100.0 * 0.10 * w.dilution_factor(),
+    }
+}
+
+pub fn capital_charge(inst: &Instrument, market: &MarketData) -> f64 {
+    signed_capital_charge(inst, market).abs()
+}
+
+// Charged on the re-marked instrument, so spot overrides move the
+// option, warrant, future and equity charges
+pub fn regulatory_report(inst: &Instrument, market: &MarketData) {
+    let charge = capital_charge(&market.remark(inst), market);
+    println!("  Reg   {:<45}   capital charge=${:.2}", inst, charge);
+}
+
+// --- Portfolio aggregates over the per-instrument functions ---
+
+// Fails on the first instrument that can't be priced rather than
+// reporting a partial total
+pub fn portfolio_value(
+    portfolio: &[Instrument],
+    market: &MarketData,
+) -> Result<f64, PricingError> {
+    portfolio.iter().map(|inst| price(inst, market)).sum()
+}
+
+// A problem found by validate_portfolio, with the position of the
+// offending instrument in the slice
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub index: usize,
+    pub error: PricingError,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "instrument {}: {}", self.index, self.error)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+// Runs `validate` over every instrument and reports all failures
+// at once, unlike portfolio_value which stops at the first
+pub fn validate_portfolio(portfolio: &[Instrument]) -> Result<(), Vec<ValidationError>> {
+    let errors: Vec<ValidationError> = portfolio
+        .iter()
+        .enumerate()
+        .filter_map(|(index, inst)| {
+            validate(inst)
+                .err()
+                .map(|error| ValidationError { index, error })
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+// Largest value first. Instruments that fail to price sort last;
+// total_cmp keeps the order total even if a price is NaN. Each
+// instrument is priced once up front, not on every comparison.
+pub fn sort_by_price_desc(portfolio: &mut [Instrument], market: &MarketData) {
+    let mut priced: Vec<(f64, Instrument)> = portfolio
+        .iter()
+        .map(|inst| (price(inst, market).unwrap_or(f64::NEG_INFINITY), inst.clone()))
+        .collect();
+    priced.sort_by(|a, b| b.0.total_cmp(&a.0));
+    for (slot, (_, inst)) in portfolio.iter_mut().zip(priced) {
+        *slot = inst;
+    }
+}
+
+pub fn total_dv01(portfolio: &[Instrument], market: &MarketData) -> f64 {
+    portfolio.iter().map(|inst| dv01(inst, market)).sum()
+}
+
+// --- Slicing a portfolio for reports ---
+
+pub fn filter_bonds(portfolio: &[Instrument]) -> Vec<&Bond> {
+    portfolio
+        .iter()
+        .filter_map(|inst| match inst {
+            Instrument::Bond(b) => Some(b),
+            _ => None,
+        })
+        .collect()
+}
+
+// Grouping key: a bond's issuer or a CDS's reference entity, so
+// credit exposure to one name lands in one group. Instruments
+// without an issuer get a synthetic key: "IRS" for swaps, "FRA" for
+// FRAs, "CAP"/"FLOOR" for caps and floors, "OPT:<underlying>" for
+// options, "FUT:<underlying>" for futures, "FX:<pair>" for FX
+// forwards, "EQ:<ticker>" for equities and "WRT:<underlying>" for
+// warrants.
+pub fn issuer_key(inst: &Instrument) -> String {
+    match inst {
+        Instrument::Bond(b) => b.issuer.clone(),
+        Instrument::Swap(_) => "IRS".to_string(),
+        Instrument::Fra(_) => "FRA".to_string(),
+        Instrument::Option(o) => format!("OPT:{}", o.underlying),
+        Instrument::Future(fut) => format!("FUT:{}", fut.underlying),
+        Instrument::Cds(c) => c.reference_entity.clone(),
+        Instrument::FxForward(fx) => format!("FX:{}{}", fx.base_ccy, fx.quote_ccy),
+        Instrument::CapFloor(c) => (if c.is_cap { "CAP" } else { "FLOOR" }).to_string(),
+        Instrument::Equity(e) => format!("EQ:{}", e.ticker),
+        Instrument::Warrant(w) => format!("WRT:{}", w.underlying),
+    }
+}
+
+// Drops repeated instruments, keeping the first of each in place
+pub fn dedup(portfolio: &mut Vec<Instrument>) {
+    let mut seen = HashSet::new();
+    portfolio.retain(|inst| seen.insert(inst.clone()));
+}
+
+pub fn count_by_type(portfolio: &[Instrument]) -> HashMap<&'static str, usize> {
+    let mut counts = HashMap::new();
+    for inst in portfolio {
+        *counts.entry(inst.name()).or_insert(0) += 1;
+    }
+    counts
+}
+
+pub fn group_by_issuer(portfolio: &[Instrument]) -> HashMap<String, Vec<&Instrument>> {
+    let mut groups: HashMap<String, Vec<&Instrument>> = HashMap::new();
+    for inst in portfolio {
+        groups.entry(issuer_key(inst)).or_default().push(inst);
+    }
+    groups
+}
+
+// --- Regulatory capital across a portfolio ---
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NettingRule {
+    None,     // every instrument charged on its own
+    BySymbol, // signed charges offset within an issuer_key group
+}
+
+// Charge for one issuer_key group: `gross` ignores offsets, `net` is
+// what the netting rule actually charges
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapitalLine {
+    pub symbol: String,
+    pub gross: f64,
+    pub net: f64,
+}
+
+// One line per symbol, sorted by symbol
+pub fn capital_breakdown(
+    portfolio: &[Instrument],
+    market: &MarketData,
+    netting: NettingRule,
+) -> Vec<CapitalLine> {
+    let mut lines: Vec<CapitalLine> = group_by_issuer(portfolio)
+        .into_iter()
+        .map(|(symbol, insts)| {
+            let gross = insts.iter().map(|inst| capital_charge(inst, market)).sum();
+            let net = match netting {
+                NettingRule::None => gross,
+                NettingRule::BySymbol => insts
+                    .iter()
+                    .map(|inst| signed_capital_charge(inst, market))
+                    .sum::<f64>()
+                    .abs(),
+            };
+            CapitalLine { symbol, gross, net }
+        })
+        .collect();
+    lines.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    lines
+}
+
+pub fn total_capital_charge(
+    portfolio: &[Instrument],
+    market: &MarketData,
+    netting: NettingRule,
+) -> f64 {
+    capital_breakdown(portfolio, market, netting)
+        .iter()
+        .map(|line| line.net)
+        .sum()
+}
+
+// One undiscounted payment; `period` is the payment year
+#[derive(Debug, Clone, PartialEq)]
+pub struct CashFlow {
+    pub period: u32,
+    pub amount: f64,
+}
+
+// Bonds pay a coupon each year and principal as a separate flow at
+// maturity. Swaps pay the net of fixed against a float leg projected
+// at the market swap rate; an FRA settles once, in the year its
+// period ends, as does an FX forward. Caps and floors pay their
+// intrinsic value at the swap rate each year. A CDS pays its running
+// coupon each year (protection payments are contingent, not
+// scheduled). Options, futures, equities and warrants have no
+// scheduled flows.
+pub fn cashflows(inst: &Instrument, market: &MarketData) -> Vec<CashFlow> {
+    let swap_rate = market.swap_rate;
+    match inst {
+        Instrument::Bond(b) => {
+            let coupon = b.face_value * b.coupon_rate;
+            let mut flows: Vec<CashFlow> = (1..=b.maturity_years)
+                .map(|period| CashFlow {
+                    period,
+                    amount: coupon,
+                })
+                .collect();
+            flows.push(CashFlow {
+                period: b.maturity_years,
+                amount: b.face_value,
+            });
+            flows
+        }
+        Instrument::Swap(s) => {
+            let net = s.notional * (s.fixed_rate - swap_rate);
+            (1..=s.tenor_years)
+                .map(|period| CashFlow {
+                    period,
+                    amount: net,
+                })
+                .collect()
+        }
+        Instrument::Fra(r) => vec![CashFlow {
+            period: r.end_months.div_ceil(12),
+            amount: r.notional * (r.fixed_rate - swap_rate) * r.accrual(),
+        }],
+        Instrument::Option(_)
+        | Instrument::Future(_)
+        | Instrument::Equity(_)
+        | Instrument::Warrant(_) => Vec::new(),
+        Instrument::Cds(c) => {
+            let premium = -c.notional * CDS_COUPON_BPS / 10_000.0;
+            (1..=c.tenor_years)
+                .map(|period| CashFlow {
+                    period,
+                    amount: premium,
+                })
+                .collect()
+        }
+        // No quote for the pair: the settlement amount is unknown
+        Instrument::FxForward(fx) => match fx_forward_market(fx, market) {
+            Ok((spot, quote_rate, base_rate)) => {
+                let implied = implied_forward(spot, quote_rate, base_rate, fx.years());
+                vec![CashFlow {
+                    period: fx.settle_months.div_ceil(12),
+                    amount: fx.notional * (implied - fx.forward_rate),
+                }]
+            }
+            Err(_) => Vec::new(),
+        },
+        Instrument::CapFloor(c) => {
+            let intrinsic = if c.is_cap {
+                swap_rate - c.strike_rate
+            } else {
+                c.strike_rate - swap_rate
+            };
+            (1..=c.tenor_years)
+                .map(|period| CashFlow {
+                    period,
+                    amount: c.notional * intrinsic.max(0.0),
+                })
+                .collect()
+        }
+    }
+}
+
+// ============================================================
+// CSV loading: one instrument per row, dispatched on the first
+// column. Blank lines and lines starting with '#' are skipped.
+// Rows and columns in errors are 1-based.
+//
+//   Bond,ccy,issuer,face_value,coupon_rate,maturity_years[,first_call_year,call_price]
+//   Swap,ccy,notional,fixed_rate,tenor_years
+//   Fra,ccy,notional,fixed_rate,start_months,end_months
+//   Option,ccy,underlying,strike,spot,Call|Put,vol,time_to_expiry
+//   Future,ccy,underlying,contract_price,contract_size,num_contracts
+//   Cds,ccy,reference_entity,notional,spread_bps,tenor_years,recovery_rate
+//   FxForward,quote_ccy,base_ccy,notional,forward_rate,settle_months
+//   CapFloor,ccy,Cap|Floor,notional,strike_rate,tenor_years
+//   Equity,ccy,ticker,shares,price
+//   Warrant,ccy,underlying,strike,spot,vol,time_to_expiry,shares_outstanding,warrants_issued
+// ============================================================
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    UnknownType {
+        row: usize,
+        value: String,
+    },
+    MissingColumn {
+        row: usize,
+        column: usize,
+    },
+    InvalidValue {
+        row: usize,
+        column: usize,
+        value: String,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "cannot read portfolio: {}", e),
+            Self::UnknownType { row, value } => {
+                write!(f, "row {}: unknown instrument type '{}'", row, value)
+            }
+            Self::MissingColumn { row, column } => {
+                write!(f, "row {}: missing column {}", row, column)
+            }
+            Self::InvalidValue { row, column, value } => {
+                write!(f, "row {}, column {}: invalid value '{}'", row, column, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<std::io::Error> for LoadError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+// Column cursor over one row, tracking positions for errors
+pub struct CsvRow<'a> {
+    pub row: usize,
+    pub fields: Vec<&'a str>,
+}
+
+impl CsvRow<'_> {
+    pub fn has(&self, column: usize) -> bool {
+        column <= self.fields.len()
+    }
+
+    pub fn text(&self, column: usize) -> Result<&str, LoadError> {
+        self.fields
+            .get(column - 1)
+            .copied()
+            .ok_or(LoadError::MissingColumn {
+                row: self.row,
+                column,
+            })
+    }
+
+    pub fn parse<T: std::str::FromStr>(&self, column: usize) -> Result<T, LoadError> {
+        let value = self.text(column)?;
+        value.parse().map_err(|_| LoadError::InvalidValue {
+            row: self.row,
+            column,
+            value: value.to_string(),
+        })
+    }
+}
+
+pub fn parse_instrument(row: &CsvRow) -> Result<Instrument, LoadError> {
+    match row.text(1)? {
+        "Bond" => Ok(Instrument::Bond(Bond {
+            issuer: row.text(3)?.to_string(),
+            currency: row.parse(2)?,
+            face_value: row.parse(4)?,
+            coupon_rate: row.parse(5)?,
+            maturity_years: row.parse(6)?,
+            callable: if row.has(7) {
+                Some(CallSchedule {
+                    first_call_year: row.parse(7)?,
+                    call_price: row.parse(8)?,
+                })
+            } else {
+                None
+            },
+        })),
+        "Swap" => Ok(Instrument::Swap(Swap {
+            currency: row.parse(2)?,
+            notional: row.parse(3)?,
+            fixed_rate: row.parse(4)?,
+            tenor_years: row.parse(5)?,
+        })),
+        "Fra" => Ok(Instrument::Fra(Fra {
+            currency: row.parse(2)?,
+            notional: row.parse(3)?,
+            fixed_rate: row.parse(4)?,
+            start_months: row.parse(5)?,
+            end_months: row.parse(6)?,
+        })),
+        "Option" => Ok(Instrument::Option(Option {
+            underlying: row.text(3)?.to_string(),
+            currency: row.parse(2)?,
+            strike: row.parse(4)?,
+            spot: row.parse(5)?,
+            is_call: match row.text(6)? {
+                "Call" => true,
+                "Put" => false,
+                other => {
+                    return Err(LoadError::InvalidValue {
+                        row: row.row,
+                        column: 6,
+                        value: other.to_string(),
+                    })
+                }
+            },
+            vol: row.parse(7)?,
+            time_to_expiry: row.parse(8)?,
+        })),
+        "Future" => Ok(Instrument::Future(Future {
+            underlying: row.text(3)?.to_string(),
+            currency: row.parse(2)?,
+            contract_price: row.parse(4)?,
+            contract_size: row.parse(5)?,
+            num_contracts: row.parse(6)?,
+        })),
+        "Cds" => Ok(Instrument::Cds(Cds {
+            reference_entity: row.text(3)?.to_string(),
+            currency: row.parse(2)?,
+            notional: row.parse(4)?,
+            spread_bps: row.parse(5)?,
+            tenor_years: row.parse(6)?,
+            recovery_rate: row.parse(7)?,
+        })),
+        "FxForward" => Ok(Instrument::FxForward(FxForward {
+            base_ccy: row.parse(3)?,
+            quote_ccy: row.parse(2)?,
+            notional: row.parse(4)?,
+            forward_rate: row.parse(5)?,
+            settle_months: row.parse(6)?,
+        })),
+        "CapFloor" => Ok(Instrument::CapFloor(CapFloor {
+            currency: row.parse(2)?,
+            notional: row.parse(4)?,
+            strike_rate: row.parse(5)?,
+            tenor_years: row.parse(6)?,
+            is_cap: match row.text(3)? {
+                "Cap" => true,
+                "Floor" => false,
+                other => {
+                    return Err(LoadError::InvalidValue {
+                        row: row.row,
+                        column: 3,
+                        value: other.to_string(),
+                    })
+                }
+            },
+        })),
+        "Equity" => Ok(Instrument::Equity(Equity {
+            ticker: row.text(3)?.to_string(),
+            currency: row.parse(2)?,
+            shares: row.parse(4)?,
+            price: row.parse(5)?,
+        })),
+        "Warrant" => Ok(Instrument::Warrant(Warrant {
+            underlying: row.text(3)?.to_string(),
+            currency: row.parse(2)?,
+            strike: row.parse(4)?,
+            spot: row.parse(5)?,
+            vol: row.parse(6)?,
+            time_to_expiry: row.parse(7)?,
+            shares_outstanding: row.parse(8)?,
+            warrants_issued: row.parse(9)?,
+        })),
+        other => Err(LoadError::UnknownType {
+            row: row.row,
+            value: other.to_string(),
+        }),
+    }
+}
+
+pub fn parse_portfolio_csv(text: &str) -> Result<Vec<Instrument>, LoadError> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| {
+            parse_instrument(&CsvRow {
+                row: i + 1,
+                fields: line.split(',').map(str::trim).collect(),
+            })
+        })
+        .collect()
+}
+
+pub fn load_portfolio_csv(path: &Path) -> Result<Vec<Instrument>, LoadError> {
+    parse_portfolio_csv(&std::fs::read_to_string(path)?)
+}
+
+// ============================================================
+// Spread measures for bonds.
+//
+// z-spread: constant spread over the zero curve that reprices
+// the bond's cashflows to market.
+//
+// OAS: the same idea, but priced on a short-rate lattice so the
+// issuer's call option is exercised wherever it is in the money.
+// ============================================================
+
+// Absolute one-year short-rate volatility used by the lattice
+pub const LATTICE_VOL: f64 = 0.01;
+
+pub fn bond_pv_over_curve(
+    b: &Bond,
+    curve: &DiscountCurve,
+    spread: f64,
+) -> std::option::Option<f64> {
+    let coupon = b.face_value * b.coupon_rate;
+    let mut pv = 0.0;
+    for i in 1..=b.maturity_years {
+        pv += coupon * curve.discount_factor(i, spread)?;
+    }
+    Some(pv + b.face_value * curve.discount_factor(b.maturity_years, spread)?)
+}
+
+// Ho-Lee style recombining tree: one step per year, the short
+// rate in year i at node j is drift(i) + vol * (2j - i). Each
+// drift is solved by forward induction over the Arrow-Debreu
+// state prices, so the tree reprices the curve's zero-coupon
+// bonds exactly and a bond without a call lands on the curve.
+pub fn calibrate_lattice(curve: &DiscountCurve, steps: usize) -> std::option::Option<Vec<f64>> {
+    let mut drifts = Vec::with_capacity(steps);
+    let mut state = vec![1.0];
+    for step in 0..steps {
+        let target = curve.discount_factor(step as u32 + 1, 0.0)?;
+        let short = |drift: f64, j: usize| drift + LATTICE_VOL * (2.0 * j as f64 - step as f64);
+        let zero_pv = |drift: f64| -> f64 {
+            state
+                .iter()
+                .enumerate()
+                .map(|(j, q)| q / (1.0 + short(drift, j)))
+                .sum()
+        };
+        let (mut lo, mut hi) = (-0.5, 1.0);
+        for _ in 0..200 {
+            let mid = 0.5 * (lo + hi);
+            if zero_pv(mid) > target {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        let drift = 0.5 * (lo + hi);
+
+        let mut next = vec![0.0; step + 2];
+        for (j, q) in state.iter().enumerate() {
+            let half = 0.5 * q / (1.0 + short(drift, j));
+            next[j] += half;
+            next[j + 1] += half;
+        }
+        drifts.push(drift);
+        state = next;
+    }
+    Some(drifts)
+}
+
+// Backward induction over a calibrated tree, with `spread` added
+// to every short rate. Call is checked ex-coupon, so the holder
+// still receives it.
+pub fn callable_pv_on_lattice(
+    b: &Bond,
+    call: &CallSchedule,
+    curve: &DiscountCurve,
+    spread: f64,
+) -> std::option::Option<f64> {
+    let n = b.maturity_years as usize;
+    let drifts = calibrate_lattice(curve, n)?;
+    let coupon = b.face_value * b.coupon_rate;
+    let mut values = vec![b.face_value + coupon; n + 1];
+
+    for step in (0..n).rev() {
+        for j in 0..=step {
+            let short = drifts[step] + LATTICE_VOL * (2.0 * j as f64 - step as f64);
+            let mut v = 0.5 * (values[j] + values[j + 1]) / (1.0 + short + spread);
+            if step > 0 && step as u32 >= call.first_call_year {
+                v = v.min(call.call_price);
+            }
+            values[j] = if step > 0 { v + coupon } else { v };
+        }
+    }
+    Some(values[0])
+}
+
+// Bisection on a PV that decreases monotonically in the spread;
+// None if the price is out of range or the PV can't be computed
+pub fn solve_spread(
+    target: f64,
+    pv_at: impl Fn(f64) -> std::option::Option<f64>,
+) -> std::option::Option<f64> {
+    let (mut lo, mut hi) = (-0.05, 0.50);
+    if pv_at(lo)? < target || pv_at(hi)? > target {
+        return None;
+    }
+    for _ in 0..200 {
+        let mid = 0.5 * (lo + hi);
+        if pv_at(mid)? > target {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    Some(0.5 * (lo + hi))
+}
+
+pub fn z_spread(b: &Bond, market_price: f64, curve: &DiscountCurve) -> std::option::Option<f64> {
+    solve_spread(market_price, |s| bond_pv_over_curve(b, curve, s))
+}
+
+// A bond with no call has no option to strip out, so its OAS is
+// its z-spread
+pub fn option_adjusted_spread(
+    b: &Bond,
+    market_price: f64,
+    curve: &DiscountCurve,
+) -> std::option::Option<f64> {
+    match &b.callable {
+        Some(call) => solve_spread(market_price, |s| callable_pv_on_lattice(b, call, curve, s)),
+        None => z_spread(b, market_price, curve),
+    }
+}
+
+// --- Yield to maturity: the flat rate that reprices the bond ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolveError {
+    NoConvergence,
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoConvergence => write!(f, "solver did not converge"),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+// Newton-Raphson on bond_pv, starting from the coupon rate
+pub fn yield_to_maturity(b: &Bond, market_price: f64) -> Result<f64, SolveError> {
+    let coupon = b.face_value * b.coupon_rate;
+    let mut y = b.coupon_rate;
+    for _ in 0..100 {
+        // d(pv)/dy: each flow at year t contributes -t * cf / (1 + y)^(t + 1)
+        let mut slope = 0.0;
+        for t in 1..=b.maturity_years {
+            let cf = if t == b.maturity_years {
+                coupon + b.face_value
+            } else {
+                coupon
+            };
+            slope -= t as f64 * cf / (1.0 + y).powi(t as i32 + 1);
+        }
+        if slope == 0.0 {
+            break;
+        }
+        let step = (bond_pv(b, y) - market_price) / slope;
+        y -= step;
+        if !y.is_finite() || y <= -1.0 {
+            break;
+        }
+        if step.abs() < 1e-12 {
+            return Ok(y);
+        }
+    }
+    Err(SolveError::NoConvergence)
+}
+
+// ============================================================
+// Trait-based visitor: useful when you want to pass different
+// operations as values (first-class visitors).
+// ============================================================
+
+// The trait and its dispatch are generated from one variant list:
+// a variant missing from the list makes the match in `visit`
+// non-exhaustive, and an impl missing a method fails with E0046,
+// so the two can't drift apart.
+macro_rules! instrument_visitor {
+    ($($variant:ident($ty:ident) => $method:ident),* $(,)?) => {
+        pub trait InstrumentVisitor {
+            $(fn $method(&self, inst: &$ty);)*
+        }
+
+        // A single dispatch function replaces accept() on every type
+        pub fn visit(inst: &Instrument, visitor: &dyn InstrumentVisitor) {
+            match inst {
+                $(Instrument::$variant(x) => visitor.$method(x),)*
+            }
+        }
+    };
+}
+
+instrument_visitor! {
+    Bond(Bond) => visit_bond,
+    Swap(Swap) => visit_swap,
+    Fra(Fra) => visit_fra,
+    Option(Option) => visit_option,
+    Future(Future) => visit_future,
+    Cds(Cds) => visit_cds,
+    FxForward(FxForward) => visit_fx_forward,
+    CapFloor(CapFloor) => visit_cap_floor,
+    Equity(Equity) => visit_equity,
+    Warrant(Warrant) => visit_warrant,
+}
+
+// Every line is priced with `price` against `market`, so the
+// visitor agrees with it, spot overrides and validation included
+pub struct PricePrinter {
+    pub market: MarketData,
+}
+
+impl Default for PricePrinter {
+    fn default() -> Self {
+        Self {
+            market: MarketData::demo(),
+        }
+    }
+}
+
+impl PricePrinter {
+    fn value(&self, inst: Instrument) -> String {
+        match price(&inst, &self.market) {
+            Ok(px) => format!("${:.2}", px),
+            Err(e) => format!("rejected ({})", e),
+        }
+    }
+}
+
+impl InstrumentVisitor for PricePrinter {
+    fn visit_bond(&self, b: &Bond) {
+        println!(
+            "  [trait] Bond({}, {:.0} face) = {}",
+            b.issuer,
+            b.face_value,
+            self.value(Instrument::Bond(b.clone()))
+        );
+    }
+
+    fn visit_swap(&self, s: &Swap) {
+        println!(
+            "  [trait] IRS({:.0} notional, {}Y) = {} NPV",
+            s.notional,
+            s.tenor_years,
+            self.value(Instrument::Swap(s.clone()))
+        );
+    }
+
+    fn visit_fra(&self, r: &Fra) {
+        println!(
+            "  [trait] FRA({:.0} notional, {}x{}) = {} NPV",
+            r.notional,
+            r.start_months,
+            r.end_months,
+            self.value(Instrument::Fra(r.clone()))
+        );
+    }
+
+    fn visit_option(&self, o: &Option) {
+        println!(
+            "  [trait] {} {}(K={:.2}) = {}",
+            o.underlying,
+            if o.is_call { "Call" } else { "Put" },
+            o.strike,
+            self.value(Instrument::Option(o.clone()))
+        );
+    }
+
+    fn visit_future(&self, fut: &Future) {
+        println!(
+            "  [trait] {} Future x{} = {} notional",
+            fut.underlying,
+            fut.num_contracts,
+            self.value(Instrument::Future(fut.clone()))
+        );
+    }
+
+    fn visit_cds(&self, c: &Cds) {
+        println!(
+            "  [trait] CDS {} {}Y @ {:.0}bp = {}",
+            c.reference_entity,
+            c.tenor_years,
+            c.spread_bps,
+            self.value(Instrument::Cds(c.clone()))
+        );
+    }
+
+    // Valued in the quote currency rather than dollars
+    fn visit_fx_forward(&self, fx: &FxForward) {
+        let label = format!(
+            "{}/{} forward {:.0} @ {:.4}",
+            fx.base_ccy, fx.quote_ccy, fx.notional, fx.forward_rate
+        );
+        match price(&Instrument::FxForward(fx.clone()), &self.market) {
+            Ok(mtm) => println!("  [trait] {} = {:.2} {}", label, mtm, fx.quote_ccy),
+            Err(e) => println!("  [trait] {} = rejected ({})", label, e),
+        }
+    }
+
+    fn visit_cap_floor(&self, c: &CapFloor) {
+        println!(
+            "  [trait] {} {:.2}% x {}Y = {}",
+            if c.is_cap { "Cap" } else { "Floor" },
+            c.strike_rate * 100.0,
+            c.tenor_years,
+            self.value(Instrument::CapFloor(c.clone()))
+        );
+    }
+
+    fn visit_equity(&self, e: &Equity) {
+        println!(
+            "  [trait] {} {} shares = {}",
+            e.ticker,
+            e.shares,
+            self.value(Instrument::Equity(e.clone()))
+        );
+    }
+
+    fn visit_warrant(&self, w: &Warrant) {
+        println!(
+            "  [trait] {} Warrant(K={:.2}) = {} diluted",
+            w.underlying,
+            w.strike,
+            self.value(Instrument::Warrant(w.clone()))
+        );
+    }
+}
+
+// ============================================================
+// Folding visitor: threads an accumulator through the portfolio
+// in one pass. The accumulator type is chosen by the visitor.
+// ============================================================
+
+pub trait FoldVisitor {
+    type Acc;
+    fn fold_bond(&self, acc: Self::Acc, b: &Bond) -> Self::Acc;
+    fn fold_swap(&self, acc: Self::Acc, s: &Swap) -> Self::Acc;
+    fn fold_fra(&self, acc: Self::Acc, r: &Fra) -> Self::Acc;
+    fn fold_option(&self, acc: Self::Acc, o: &Option) -> Self::Acc;
+    fn fold_future(&self, acc: Self::Acc, fut: &Future) -> Self::Acc;
+    fn fold_cds(&self, acc: Self::Acc, c: &Cds) -> Self::Acc;
+    fn fold_fx_forward(&self, acc: Self::Acc, fx: &FxForward) -> Self::Acc;
+    fn fold_cap_floor(&self, acc: Self::Acc, c: &CapFloor) -> Self::Acc;
+    fn fold_equity(&self, acc: Self::Acc, e: &Equity) -> Self::Acc;
+    fn fold_warrant(&self, acc: Self::Acc, w: &Warrant) -> Self::Acc;
+}
+
+pub fn fold<V: FoldVisitor>(portfolio: &[Instrument], init: V::Acc, v: &V) -> V::Acc {
+    portfolio.iter().fold(init, |acc, inst| match inst {
+        Instrument::Bond(b) => v.fold_bond(acc, b),
+        Instrument::Swap(s) => v.fold_swap(acc, s),
+        Instrument::Fra(r) => v.fold_fra(acc, r),
+        Instrument::Option(o) => v.fold_option(acc, o),
+        Instrument::Future(fut) => v.fold_future(acc, fut),
+        Instrument::Cds(c) => v.fold_cds(acc, c),
+        Instrument::FxForward(fx) => v.fold_fx_forward(acc, fx),
+        Instrument::CapFloor(c) => v.fold_cap_floor(acc, c),
+        Instrument::Equity(e) => v.fold_equity(acc, e),
+        Instrument::Warrant(w) => v.fold_warrant(acc, w),
+    })
+}
+
+// Face or notional amount in each instrument's own currency (no FX
+// conversion). Options count 100 shares of underlying per contract
+// and warrants one; futures and equities count their absolute
+// market value.
+pub struct NotionalTotal;
+
+impl FoldVisitor for NotionalTotal {
+    type Acc = f64;
+
+    fn fold_bond(&self, acc: f64, b: &Bond) -> f64 {
+        acc + b.face_value
+    }
+
+    fn fold_swap(&self, acc: f64, s: &Swap) -> f64 {
+        acc + s.notional
+    }
+
+    fn fold_fra(&self, acc: f64, r: &Fra) -> f64 {
+        acc + r.notional
+    }
+
+    fn fold_option(&self, acc: f64, o: &Option) -> f64 {
+        acc + o.spot * 100.0
+    }
+
+    fn fold_future(&self, acc: f64, fut: &Future) -> f64 {
+        acc + fut.notional().abs()
+    }
+
+    fn fold_cds(&self, acc: f64, c: &Cds) -> f64 {
+        acc + c.notional
+    }
+
+    fn fold_fx_forward(&self, acc: f64, fx: &FxForward) -> f64 {
+        acc + fx.notional
+    }
+
+    fn fold_cap_floor(&self, acc: f64, c: &CapFloor) -> f64 {
+        acc + c.notional
+    }
+
+    fn fold_equity(&self, acc: f64, e: &Equity) -> f64 {
+        acc + e.market_value().abs()
+    }
+
+    fn fold_warrant(&self, acc: f64, w: &Warrant) -> f64 {
+        acc + w.spot
+    }
+}
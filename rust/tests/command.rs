@@ -0,0 +1,1255 @@
+// Smoke test: the command module's public API is usable from
+// outside the crate.
+
+use design_patterns::command::*;
+
+#[test]
+fn enum_command_round_trips_through_history() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut history = TradeHistory::new();
+    let buy = TradeAction::Buy {
+        symbol: "AAPL".to_string(),
+        quantity: 100,
+        price: 150.0,
+        tag: None,
+    };
+    history.execute(buy, &mut portfolio).expect("enough cash");
+    assert_eq!(portfolio.position("AAPL"), 100);
+    assert!(history.undo(&mut portfolio).is_some());
+    assert_eq!(portfolio.position("AAPL"), 0);
+}
+
+#[test]
+fn trait_command_executes_through_history() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut history = CommandHistory::new();
+    let cmd = Box::new(MarketBuy {
+        symbol: "MSFT".to_string(),
+        quantity: 10,
+        price: 400.0,
+    });
+    history.execute(cmd, &mut portfolio).expect("enough cash");
+    assert_eq!(portfolio.position("MSFT"), 10);
+}
+
+#[test]
+fn equity_instruments_mark_to_market() {
+    use design_patterns::visitor::{Currency, Equity};
+    use std::collections::HashMap;
+
+    let mut portfolio = Portfolio::new(10_000.0);
+    let long = Equity {
+        ticker: "AAPL".to_string(),
+        currency: Currency::USD,
+        shares: 20,
+        price: 100.0,
+    };
+    TradeAction::from(&long)
+        .execute(&mut portfolio)
+        .expect("enough cash");
+    let marks = HashMap::from([("AAPL".to_string(), 110.0)]);
+    // 8_000 cash left + 20 shares at 110
+    assert_eq!(portfolio.mark_to_market(&marks), 10_200.0);
+    // Unmarked positions are carried at cost
+    assert_eq!(portfolio.mark_to_market(&HashMap::new()), 10_000.0);
+}
+
+#[test]
+fn buy_sell_undo_redo_cycle_restores_state() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut history = TradeHistory::new();
+    let buy = TradeAction::Buy {
+        symbol: "AAPL".to_string(),
+        quantity: 100,
+        price: 150.0,
+        tag: None,
+    };
+    let sell = TradeAction::Sell {
+        symbol: "AAPL".to_string(),
+        quantity: 40,
+        price: 160.0,
+        tag: None,
+    };
+    history.execute(buy, &mut portfolio).expect("enough cash");
+    history.execute(sell, &mut portfolio).expect("enough shares");
+    assert_eq!(portfolio.position("AAPL"), 60);
+    assert_eq!(portfolio.cash, 91_400.0);
+    assert_eq!(portfolio.realized_pnl, 400.0);
+
+    history.undo(&mut portfolio).expect("sell to undo");
+    assert_eq!(portfolio.position("AAPL"), 100);
+    assert_eq!(portfolio.cash, 85_000.0);
+    assert_eq!(portfolio.realized_pnl, 0.0);
+
+    history.redo(&mut portfolio).expect("sell to redo");
+    assert_eq!(portfolio.position("AAPL"), 60);
+    assert_eq!(portfolio.cash, 91_400.0);
+    assert_eq!(portfolio.realized_pnl, 400.0);
+
+    assert_eq!(history.undo_all(&mut portfolio), 2);
+    assert_eq!(portfolio.position("AAPL"), 0);
+    assert_eq!(portfolio.cash, 100_000.0);
+}
+
+#[test]
+fn portfolio_report_lists_positions_in_symbol_order() {
+    use design_patterns::report::Report;
+
+    let mut portfolio = Portfolio::new(50_000.0);
+    portfolio.buy("MSFT", 10, 400.0).expect("enough cash");
+    portfolio.buy("AAPL", 100, 150.0).expect("enough cash");
+    assert_eq!(
+        portfolio.report(),
+        "  Portfolio:\n    Cash: $31000.00\n    AAPL: 100 shares (avg $150.00)\n    \
+         MSFT: 10 shares (avg $400.00)\n    Realized P&L: $0.00"
+    );
+}
+
+#[test]
+fn diff_reports_position_and_cash_deltas() {
+    let mut before = Portfolio::new(100_000.0);
+    before.buy("AAPL", 100, 150.0).expect("enough cash");
+    before.buy("MSFT", 10, 400.0).expect("enough cash");
+    let mut after = before.clone();
+    assert!(before.diff(&after).is_empty());
+
+    after.sell("AAPL", 40, 160.0).expect("enough shares");
+    let diff = before.diff(&after);
+    assert!(!diff.is_empty());
+    assert_eq!(diff.positions, vec![("AAPL".to_string(), -40)]);
+    assert_eq!(diff.cash, 6_400.0);
+    assert_eq!(after.diff(&before).positions, vec![("AAPL".to_string(), 40)]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn journal_gets_one_json_line_per_executed_action() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let journal = Rc::new(RefCell::new(FileJournal::new(Vec::new())));
+    let mut history = TradeHistory::new().with_journal(journal.clone());
+    let mut portfolio = Portfolio::new(100_000.0);
+    let actions = [
+        TradeAction::Buy {
+            symbol: "AAPL".to_string(),
+            quantity: 100,
+            price: 150.0,
+            tag: None,
+        },
+        TradeAction::Sell {
+            symbol: "AAPL".to_string(),
+            quantity: 40,
+            price: 160.0,
+            tag: Some("trim".to_string()),
+        },
+    ];
+    for action in &actions {
+        history.execute(action.clone(), &mut portfolio).expect("valid trade");
+    }
+    history.undo(&mut portfolio).expect("sell to undo");
+
+    let journal = journal.borrow();
+    let written = String::from_utf8(journal.writer.clone()).expect("journal is UTF-8");
+    let expected: Vec<String> = actions
+        .iter()
+        .map(|a| serde_json::to_string(a).expect("serializable"))
+        .collect();
+    assert_eq!(written.lines().collect::<Vec<_>>(), expected);
+    assert!(journal.error.is_none());
+}
+
+#[test]
+fn rebalance_to_sixty_forty_sizes_trades_without_mutating() {
+    use std::collections::HashMap;
+
+    let mut portfolio = Portfolio::new(100_000.0);
+    portfolio.buy("AAPL", 500, 150.0).expect("enough cash");
+    let marks = HashMap::from([("AAPL".to_string(), 160.0), ("MSFT".to_string(), 400.0)]);
+    let targets = HashMap::from([("AAPL".to_string(), 0.6), ("MSFT".to_string(), 0.4)]);
+
+    // NAV 105,000: AAPL to 63,000 / 160 = 393 shares, MSFT to 42,000 / 400 = 105
+    let commands = portfolio.rebalance_to(&targets, &marks);
+    let trades: Vec<(Option<&str>, String)> = commands
+        .iter()
+        .map(|cmd| (cmd.symbol(), cmd.description()))
+        .collect();
+    assert_eq!(
+        trades,
+        vec![
+            (Some("AAPL"), "MARKET SELL 107 AAPL @ $160.00".to_string()),
+            (Some("MSFT"), "MARKET BUY 105 MSFT @ $400.00".to_string()),
+        ]
+    );
+    assert_eq!(portfolio.position("AAPL"), 500);
+    assert_eq!(portfolio.position("MSFT"), 0);
+
+    for cmd in &commands {
+        cmd.execute(&mut portfolio).expect("sells fund the buys");
+    }
+    assert_eq!(portfolio.position("AAPL"), 393);
+    assert_eq!(portfolio.position("MSFT"), 105);
+}
+
+#[test]
+fn snapshot_undo_rolls_back_exactly_across_a_split() {
+    let buy = || MarketBuy {
+        symbol: "KO".into(),
+        quantity: 100,
+        price: 60.00,
+    };
+
+    let mut naive_book = Portfolio::new(10_000.0);
+    let naive = buy();
+    naive.execute(&mut naive_book).expect("within cash");
+    naive_book.split("KO", 2.0).expect("positive ratio");
+    naive.undo(&mut naive_book);
+    assert_eq!(naive_book.position("KO"), 100);
+
+    let mut book = Portfolio::new(10_000.0);
+    let before = book.snapshot();
+    let captured = SnapshotUndo::new(Box::new(buy()));
+    captured.execute(&mut book).expect("within cash");
+    assert!(captured.captured.borrow().is_some());
+    book.split("KO", 2.0).expect("positive ratio");
+    captured.undo(&mut book);
+    assert_eq!(book.position("KO"), 0);
+    assert_eq!(book.snapshot(), before);
+
+    // Undo consumes the capture; a redo would take a fresh one
+    assert!(captured.captured.borrow().is_none());
+}
+
+#[test]
+fn price_feed_drains_into_marks_for_mark_to_market() {
+    use std::collections::HashMap;
+
+    let mut portfolio = Portfolio::new(10_000.0);
+    portfolio.buy("AAPL", 10, 100.0).expect("within cash");
+    portfolio.buy("MSFT", 5, 200.0).expect("within cash");
+    let mut feed = VecFeed::new(&[
+        ("AAPL", 105.0),
+        ("MSFT", 210.0),
+        ("AAPL", f64::NAN),
+        ("AAPL", 110.0),
+    ]);
+    let mut marks = HashMap::new();
+    portfolio.mark_from_feed(&mut feed, &mut marks);
+
+    assert_eq!(feed.next_mark(), None);
+    assert_eq!(marks.len(), 2);
+    assert_eq!(marks["AAPL"], 110.0);
+    // 8_000 cash + 10 AAPL at 110 + 5 MSFT at 210
+    assert_eq!(portfolio.mark_to_market(&marks), 10_150.0);
+}
+
+#[test]
+fn exposure_groups_market_value_by_sector() {
+    use std::collections::HashMap;
+
+    let mut portfolio = Portfolio::new(100_000.0);
+    portfolio.buy("AAPL", 100, 150.0).expect("within cash");
+    portfolio.buy("MSFT", 50, 400.0).expect("within cash");
+    portfolio.buy("XOM", 200, 100.0).expect("within cash");
+    portfolio.buy("ZZZ", 10, 5.0).expect("within cash");
+    let sectors = HashMap::from([
+        ("AAPL".to_string(), "Tech".to_string()),
+        ("MSFT".to_string(), "Tech".to_string()),
+    ]);
+    let marks = HashMap::from([
+        ("AAPL".to_string(), 160.0),
+        ("MSFT".to_string(), 420.0),
+        ("XOM".to_string(), 110.0),
+    ]);
+    let exposure = portfolio.exposure_by_sector(&sectors, &marks);
+    // ZZZ has no mark, so it is skipped rather than valued at zero
+    assert_eq!(exposure.len(), 2);
+    assert_eq!(exposure["Tech"], 100.0 * 160.0 + 50.0 * 420.0);
+    assert_eq!(exposure["Unknown"], 200.0 * 110.0);
+}
+
+#[test]
+fn guarded_command_rejects_over_the_cap_and_runs_compliant_orders() {
+    use std::rc::Rc;
+
+    let cap: Vec<Rc<dyn RiskCheck>> = vec![Rc::new(MaxNotional { limit: 100_000.0 })];
+    let guard = |quantity| GuardedCommand {
+        inner: Box::new(MarketBuy {
+            symbol: "TSLA".into(),
+            quantity,
+            price: 175.0,
+        }),
+        checks: cap.clone(),
+    };
+    let mut portfolio = Portfolio::new(500_000.0);
+    let mut history = CommandHistory::new();
+
+    assert_eq!(
+        history.execute(Box::new(guard(1_000)), &mut portfolio),
+        Err(TradeError::RiskRejected(RiskReject::NotionalExceeded {
+            notional: 175_000.0,
+            limit: 100_000.0,
+        }))
+    );
+    assert_eq!(portfolio.position("TSLA"), 0);
+    assert_eq!(portfolio.cash, 500_000.0);
+
+    // A boxed guard clones with its rule set and undoes like any command
+    let compliant: Box<dyn Command> = Box::new(guard(100));
+    history
+        .execute(compliant.clone(), &mut portfolio)
+        .expect("within the cap");
+    assert_eq!(portfolio.position("TSLA"), 100);
+    assert_eq!(compliant.description(), "MARKET BUY 100 TSLA @ $175.00");
+    assert!(history.undo(&mut portfolio));
+    assert_eq!(portfolio.position("TSLA"), 0);
+}
+
+#[test]
+fn effective_positions_tracks_concentration() {
+    use std::collections::HashMap;
+
+    let marks = HashMap::from([("AAPL".to_string(), 100.0), ("MSFT".to_string(), 100.0)]);
+    let mut even = Portfolio::new(100_000.0);
+    even.buy("AAPL", 100, 100.0).expect("within cash");
+    even.buy("MSFT", 100, 100.0).expect("within cash");
+    assert!((herfindahl_index(&even, &marks) - 0.5).abs() < 1e-12);
+    assert!((effective_positions(&even, &marks) - 2.0).abs() < 1e-12);
+
+    let mut concentrated = Portfolio::new(100_000.0);
+    concentrated.buy("AAPL", 100, 100.0).expect("within cash");
+    assert!((effective_positions(&concentrated, &marks) - 1.0).abs() < 1e-12);
+
+    assert_eq!(effective_positions(&Portfolio::new(100_000.0), &marks), 0.0);
+}
+
+#[test]
+fn buy_beyond_available_cash_is_rejected_untouched() {
+    let mut portfolio = Portfolio::new(10_000.0);
+    assert_eq!(
+        portfolio.buy("AAPL", 100, 150.0),
+        Err(TradeError::InsufficientFunds {
+            needed: 15_000.0,
+            available: 10_000.0,
+        })
+    );
+    assert_eq!(portfolio.cash, 10_000.0);
+    assert_eq!(portfolio.position("AAPL"), 0);
+    assert!(portfolio.positions.is_empty());
+
+    // Spending exactly the cash on hand is allowed
+    portfolio.buy("AAPL", 100, 100.0).expect("exactly affordable");
+    assert_eq!(portfolio.cash, 0.0);
+}
+
+fn buy(symbol: &str, quantity: i32, price: f64) -> TradeAction {
+    TradeAction::Buy {
+        symbol: symbol.to_string(),
+        quantity,
+        price,
+        tag: None,
+    }
+}
+
+fn sell(symbol: &str, quantity: i32, price: f64) -> TradeAction {
+    TradeAction::Sell {
+        symbol: symbol.to_string(),
+        quantity,
+        price,
+        tag: None,
+    }
+}
+
+#[test]
+fn realized_pnl_is_measured_against_average_cost() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut history = TradeHistory::new();
+    history.execute(buy("XYZ", 100, 10.0), &mut portfolio).expect("within cash");
+    history.execute(buy("XYZ", 100, 20.0), &mut portfolio).expect("within cash");
+    assert_eq!(portfolio.avg_price("XYZ"), Some(15.0));
+
+    history.execute(sell("XYZ", 100, 30.0), &mut portfolio).expect("shares held");
+    assert_eq!(portfolio.realized_pnl, 1_500.0);
+    assert_eq!(portfolio.avg_price("XYZ"), Some(15.0));
+
+    // Undo puts the basis back exactly
+    history.undo(&mut portfolio).expect("sell to undo");
+    assert_eq!(portfolio.realized_pnl, 0.0);
+    assert_eq!(portfolio.position("XYZ"), 200);
+    assert_eq!(portfolio.avg_price("XYZ"), Some(15.0));
+}
+
+#[test]
+fn command_history_undoes_and_redoes_mixed_commands() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut history = CommandHistory::new();
+    history
+        .execute(
+            Box::new(MarketBuy {
+                symbol: "MSFT".to_string(),
+                quantity: 100,
+                price: 400.0,
+            }),
+            &mut portfolio,
+        )
+        .expect("enough cash");
+    history
+        .execute(
+            Box::new(LimitSell {
+                symbol: "MSFT".to_string(),
+                quantity: 40,
+                limit_price: 410.0,
+            }),
+            &mut portfolio,
+        )
+        .expect("enough shares");
+    assert_eq!(portfolio.position("MSFT"), 60);
+
+    assert!(history.undo(&mut portfolio));
+    assert!(history.undo(&mut portfolio));
+    assert!(!history.undo(&mut portfolio));
+    assert_eq!(portfolio.position("MSFT"), 0);
+    assert_eq!(portfolio.cash, 100_000.0);
+
+    assert!(history.redo(&mut portfolio));
+    assert!(history.redo(&mut portfolio));
+    assert!(!history.redo(&mut portfolio));
+    assert_eq!(portfolio.position("MSFT"), 60);
+    assert_eq!(portfolio.cash, 100_000.0 - 40_000.0 + 16_400.0);
+    assert_eq!(history.executed.len(), 2);
+}
+
+#[test]
+fn executed_trades_are_stamped_in_chronological_order() {
+    use design_patterns::clock::FixedClock;
+    use std::rc::Rc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let clock = Rc::new(FixedClock::new(start).with_step(Duration::from_secs(5)));
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut history = TradeHistory::with_clock(clock);
+    history.execute(buy("AAPL", 10, 150.0), &mut portfolio).expect("within cash");
+    history.execute(buy("MSFT", 10, 400.0), &mut portfolio).expect("within cash");
+    history.execute(sell("AAPL", 5, 155.0), &mut portfolio).expect("shares held");
+
+    let stamps: Vec<_> = history.executed.iter().map(|t| t.executed_at).collect();
+    assert_eq!(
+        stamps,
+        vec![
+            start,
+            start + Duration::from_secs(5),
+            start + Duration::from_secs(10),
+        ]
+    );
+}
+
+#[test]
+fn stop_market_trades_only_once_triggered() {
+    let stop = |market_price| StopMarket {
+        symbol: "AAPL".to_string(),
+        quantity: 50,
+        trigger_price: 140.0,
+        side: Side::Sell,
+        market_price,
+    };
+    let mut portfolio = Portfolio::new(100_000.0);
+    portfolio.buy("AAPL", 100, 150.0).expect("within cash");
+
+    let untriggered = stop(145.0);
+    assert!(!untriggered.is_triggered());
+    untriggered.execute(&mut portfolio).expect("no-op");
+    untriggered.undo(&mut portfolio);
+    assert_eq!(portfolio.position("AAPL"), 100);
+
+    let triggered = stop(139.5);
+    assert!(triggered.is_triggered());
+    triggered.execute(&mut portfolio).expect("shares held");
+    assert_eq!(portfolio.position("AAPL"), 50);
+    assert_eq!(portfolio.cash, 85_000.0 + 50.0 * 139.5);
+    triggered.undo(&mut portfolio);
+    assert_eq!(portfolio.position("AAPL"), 100);
+    assert_eq!(portfolio.cash, 85_000.0);
+}
+
+#[test]
+fn failed_execute_leaves_portfolio_and_history_untouched() {
+    let mut portfolio = Portfolio::new(100_000.0).with_short_selling(false);
+    let mut history = TradeHistory::new();
+    history.execute(buy("AAPL", 100, 150.0), &mut portfolio).expect("within cash");
+    history.undo(&mut portfolio).expect("buy to undo");
+    let before = portfolio.snapshot();
+
+    assert_eq!(
+        history.execute(sell("AAPL", 10, 150.0), &mut portfolio),
+        Err(TradeError::InsufficientShares {
+            held: 0,
+            requested: 10,
+        })
+    );
+    assert!(history
+        .execute(buy("AAPL", 1_000, 150.0), &mut portfolio)
+        .is_err());
+    assert_eq!(portfolio.snapshot(), before);
+    assert!(history.executed.is_empty());
+    // The rejected trades didn't clear the redo stack either
+    assert_eq!(history.undone.len(), 1);
+}
+
+fn market_buy(symbol: &str, quantity: i32, price: f64) -> Box<dyn Command> {
+    Box::new(MarketBuy {
+        symbol: symbol.to_string(),
+        quantity,
+        price,
+    })
+}
+
+#[test]
+fn macro_command_rolls_back_when_a_leg_fails() {
+    let mut portfolio = Portfolio::new(10_000.0);
+    let before = portfolio.clone();
+    let pairs_trade = MacroCommand {
+        name: "PAIRS".to_string(),
+        legs: vec![
+            market_buy("KO", 50, 60.0),
+            market_buy("PEP", 100, 170.0),
+            market_buy("MNST", 10, 55.0),
+        ],
+    };
+    assert_eq!(
+        pairs_trade.execute(&mut portfolio),
+        Err(TradeError::InsufficientFunds {
+            needed: 17_000.0,
+            available: 7_000.0,
+        })
+    );
+    assert!(portfolio.diff(&before).is_empty());
+
+    let affordable = MacroCommand {
+        name: "PAIRS".to_string(),
+        legs: vec![market_buy("KO", 50, 60.0), market_buy("PEP", 10, 170.0)],
+    };
+    affordable.execute(&mut portfolio).expect("within cash");
+    assert_eq!(portfolio.position("KO"), 50);
+    assert_eq!(portfolio.position("PEP"), 10);
+    affordable.undo(&mut portfolio);
+    assert_eq!(portfolio.realized_pnl, 0.0);
+    assert!(portfolio.diff(&before).is_empty());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn history_round_trips_through_json_and_replays() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut history = TradeHistory::new();
+    history.execute(buy("AAPL", 100, 150.0), &mut portfolio).expect("within cash");
+    history
+        .execute(sell("AAPL", 40, 160.0).tagged("trim"), &mut portfolio)
+        .expect("shares held");
+
+    let json = history.to_json();
+    let restored = TradeHistory::from_json(&json).expect("valid JSON");
+    assert_eq!(restored.to_json(), json);
+    assert_eq!(restored.executed.len(), 2);
+    assert_eq!(restored.executed[1].action.tag(), Some("trim"));
+
+    let mut replayed = Portfolio::new(100_000.0);
+    restored.replay(&mut replayed).expect("log replays cleanly");
+    assert!(replayed.diff(&portfolio).is_empty());
+    assert_eq!(replayed.realized_pnl, portfolio.realized_pnl);
+    assert!(TradeHistory::from_json("not json").is_err());
+}
+
+#[test]
+fn max_depth_evicts_old_actions_and_their_basis_entries() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut history = TradeHistory::with_max_depth(2);
+    for price in [10.0, 20.0, 30.0, 40.0] {
+        history.execute(buy("XYZ", 100, price), &mut portfolio).expect("within cash");
+    }
+    assert_eq!(history.executed.len(), 2);
+    assert!(history.executed.iter().all(|timed| timed.basis.len() == 1));
+    assert!(portfolio.basis_log.is_empty());
+    assert_eq!(portfolio.avg_price("XYZ"), Some(25.0));
+
+    history.undo(&mut portfolio).expect("newest buy");
+    history.undo(&mut portfolio).expect("second newest buy");
+    assert!(history.undo(&mut portfolio).is_none());
+    assert_eq!(portfolio.position("XYZ"), 200);
+    assert_eq!(portfolio.avg_price("XYZ"), Some(15.0));
+    assert!(portfolio.basis_log.is_empty());
+}
+
+#[test]
+fn reversing_a_fill_out_of_order_restores_its_own_symbol() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    portfolio.buy("AAPL", 10, 150.0).expect("within cash");
+    portfolio.buy("AAPL", 10, 170.0).expect("within cash");
+    portfolio.buy("MSFT", 10, 400.0).expect("within cash");
+    portfolio.reverse_buy("AAPL", 10, 170.0);
+    assert_eq!(portfolio.position("AAPL"), 10);
+    assert_eq!(portfolio.avg_price("AAPL"), Some(150.0));
+    assert_eq!(portfolio.avg_price("MSFT"), Some(400.0));
+}
+
+#[test]
+fn each_history_undoes_its_own_fills_on_a_shared_portfolio() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut commands = CommandHistory::new();
+    let mut trades = TradeHistory::with_max_depth(1);
+    commands.execute(market_buy("MSFT", 10, 400.0), &mut portfolio).expect("within cash");
+    trades.execute(buy("AAPL", 10, 150.0), &mut portfolio).expect("within cash");
+    portfolio.sell("AAPL", 5, 160.0).expect("shares held");
+    let realized = portfolio.realized_pnl;
+
+    assert!(commands.undo(&mut portfolio));
+    assert_eq!(portfolio.position("MSFT"), 0);
+    assert_eq!(portfolio.avg_price("MSFT"), None);
+    assert_eq!(portfolio.avg_price("AAPL"), Some(150.0));
+    assert_eq!(portfolio.realized_pnl, realized);
+
+    assert!(commands.redo(&mut portfolio));
+    assert_eq!(portfolio.avg_price("MSFT"), Some(400.0));
+    assert!(commands.undo(&mut portfolio));
+    assert_eq!(portfolio.avg_price("MSFT"), None);
+}
+
+#[test]
+fn restore_returns_the_portfolio_to_its_snapshot() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    portfolio.buy("AAPL", 100, 150.0).expect("within cash");
+    let checkpoint = portfolio.snapshot();
+
+    portfolio.sell("AAPL", 60, 170.0).expect("shares held");
+    portfolio.buy("MSFT", 20, 400.0).expect("within cash");
+    assert_ne!(portfolio.snapshot(), checkpoint);
+
+    portfolio.restore(checkpoint.clone());
+    assert_eq!(portfolio.snapshot(), checkpoint);
+    assert_eq!(portfolio.position("AAPL"), 100);
+    assert_eq!(portfolio.realized_pnl, 0.0);
+    assert_eq!(portfolio.cash, 85_000.0);
+}
+
+#[test]
+fn short_selling_guard_is_opt_in() {
+    let mut margin = Portfolio::new(100_000.0);
+    margin.sell("AAPL", 50, 150.0).expect("shorting allowed by default");
+    assert_eq!(margin.position("AAPL"), -50);
+
+    let mut cash_account = Portfolio::new(100_000.0).with_short_selling(false);
+    cash_account.buy("AAPL", 30, 150.0).expect("within cash");
+    assert_eq!(
+        cash_account.sell("AAPL", 50, 150.0),
+        Err(TradeError::InsufficientShares {
+            held: 30,
+            requested: 50,
+        })
+    );
+    assert_eq!(cash_account.position("AAPL"), 30);
+    cash_account.sell("AAPL", 30, 150.0).expect("closing is fine");
+    assert_eq!(cash_account.position("AAPL"), 0);
+}
+
+#[test]
+fn position_and_gross_limits_reject_before_mutating() {
+    let mut capped = Portfolio::new(1_000_000.0).with_position_limit("AAPL", 500);
+    capped.buy("AAPL", 400, 150.0).expect("under the cap");
+    assert_eq!(
+        capped.buy("AAPL", 200, 150.0),
+        Err(TradeError::LimitBreached(LimitBreach::Position {
+            symbol: "AAPL".to_string(),
+            limit: 500,
+            resulting: 600,
+        }))
+    );
+    assert_eq!(capped.position("AAPL"), 400);
+    assert_eq!(capped.cash, 940_000.0);
+
+    let mut gross = Portfolio::new(1_000_000.0).with_max_gross_exposure(100_000.0);
+    gross.buy("AAPL", 400, 150.0).expect("60k gross");
+    assert_eq!(
+        gross.buy("MSFT", 100, 410.0),
+        Err(TradeError::LimitBreached(LimitBreach::GrossExposure {
+            limit: 100_000.0,
+            resulting: 101_000.0,
+        }))
+    );
+    assert_eq!(gross.position("MSFT"), 0);
+    gross.buy("MSFT", 100, 400.0).expect("exactly at the cap");
+}
+
+#[test]
+fn commission_is_charged_on_fills_and_refunded_on_undo() {
+    let mut portfolio = Portfolio::new(100_000.0).with_commission(0.01);
+    let mut history = TradeHistory::new();
+    history.execute(buy("AAPL", 1_000, 50.0), &mut portfolio).expect("within cash");
+    assert!((portfolio.cash - (100_000.0 - 50_000.0 - 10.0)).abs() < 1e-9);
+    history.execute(sell("AAPL", 1_000, 50.0), &mut portfolio).expect("shares held");
+    // A flat round trip costs both commissions
+    assert!((portfolio.cash - (100_000.0 - 20.0)).abs() < 1e-9);
+
+    assert_eq!(history.undo_all(&mut portfolio), 2);
+    assert!((portfolio.cash - 100_000.0).abs() < 1e-9);
+
+    // The fee counts toward the cash check
+    let mut tight = Portfolio::new(50_000.0).with_commission(0.01);
+    assert!(matches!(
+        tight.buy("AAPL", 1_000, 50.0),
+        Err(TradeError::InsufficientFunds { .. })
+    ));
+}
+
+#[test]
+fn undo_all_and_redo_all_walk_the_whole_stack() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut history = TradeHistory::new();
+    let trades = [
+        buy("AAPL", 100, 150.0),
+        buy("MSFT", 50, 400.0),
+        sell("AAPL", 30, 155.0),
+        buy("NVDA", 10, 900.0),
+        sell("MSFT", 50, 410.0),
+    ];
+    for trade in trades {
+        history.execute(trade, &mut portfolio).expect("valid trade");
+    }
+    let after = portfolio.clone();
+
+    assert_eq!(history.undo_all(&mut portfolio), 5);
+    assert_eq!(portfolio.cash, 100_000.0);
+    assert!(portfolio.symbols().is_empty());
+    assert_eq!(history.undo_all(&mut portfolio), 0);
+
+    assert_eq!(history.redo_all(&mut portfolio), 5);
+    assert!(portfolio.diff(&after).is_empty());
+    assert_eq!(portfolio.realized_pnl, after.realized_pnl);
+}
+
+#[test]
+fn undo_hands_back_the_reversed_action() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut history = TradeHistory::new();
+    assert!(history.undo(&mut portfolio).is_none());
+
+    history.execute(buy("AAPL", 100, 150.0), &mut portfolio).expect("within cash");
+    history.execute(sell("AAPL", 40, 160.0), &mut portfolio).expect("shares held");
+    let undone = history.undo(&mut portfolio).expect("sell to undo");
+    assert_eq!(undone.to_string(), sell("AAPL", 40, 160.0).to_string());
+    assert!(matches!(undone, TradeAction::Sell { quantity: 40, .. }));
+    let redone = history.redo(&mut portfolio).expect("sell to redo");
+    assert_eq!(redone.to_string(), undone.to_string());
+}
+
+// Counts how each entry point is reached, to prove redo is not
+// routed back through execute
+#[derive(Debug, Clone, Default)]
+struct CountingCommand {
+    executes: std::rc::Rc<std::cell::Cell<u32>>,
+    redos: std::rc::Rc<std::cell::Cell<u32>>,
+}
+
+impl Command for CountingCommand {
+    fn execute(&self, _portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        self.executes.set(self.executes.get() + 1);
+        Ok(())
+    }
+
+    fn undo(&self, _portfolio: &mut Portfolio) {}
+
+    fn description(&self) -> String {
+        "COUNT".to_string()
+    }
+
+    fn clone_box(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn redo(&self, _portfolio: &mut Portfolio) -> Result<(), TradeError> {
+        self.redos.set(self.redos.get() + 1);
+        Ok(())
+    }
+}
+
+#[test]
+fn command_history_redo_uses_the_overridden_redo() {
+    let counting = CountingCommand::default();
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut history = CommandHistory::new();
+    history
+        .execute(Box::new(counting.clone()), &mut portfolio)
+        .expect("no-op");
+    assert!(history.undo(&mut portfolio));
+    assert!(history.redo(&mut portfolio));
+    assert_eq!((counting.executes.get(), counting.redos.get()), (1, 1));
+
+    // MacroCommand forwards redo to each leg's own redo
+    let wrapped = MacroCommand {
+        name: "WRAP".to_string(),
+        legs: vec![Box::new(counting.clone())],
+    };
+    history.execute(Box::new(wrapped), &mut portfolio).expect("no-op");
+    assert!(history.undo(&mut portfolio));
+    assert!(history.redo(&mut portfolio));
+    assert_eq!((counting.executes.get(), counting.redos.get()), (2, 2));
+}
+
+#[test]
+fn coalesced_merges_adjacent_same_side_fills_only() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut history = TradeHistory::new();
+    let trades = [
+        buy("AAPL", 100, 150.0),
+        buy("AAPL", 300, 154.0),
+        buy("MSFT", 10, 400.0),
+        buy("AAPL", 50, 152.0),
+        sell("AAPL", 20, 155.0),
+        sell("AAPL", 30, 156.0).tagged("trim"),
+    ];
+    for trade in trades {
+        history.execute(trade, &mut portfolio).expect("valid trade");
+    }
+    let rendered: Vec<String> = history.coalesced().iter().map(ToString::to_string).collect();
+    assert_eq!(
+        rendered,
+        vec![
+            buy("AAPL", 400, 153.0).to_string(),
+            buy("MSFT", 10, 400.0).to_string(),
+            buy("AAPL", 50, 152.0).to_string(),
+            sell("AAPL", 20, 155.0).to_string(),
+            sell("AAPL", 30, 156.0).tagged("trim").to_string(),
+        ]
+    );
+    assert_eq!(history.executed.len(), 6);
+}
+
+fn bracket(market_price: f64) -> Oco {
+    Oco {
+        take_profit: Box::new(LimitSell {
+            symbol: "AAPL".to_string(),
+            quantity: 100,
+            limit_price: 170.0,
+        }),
+        stop_loss: Box::new(LimitSell {
+            symbol: "AAPL".to_string(),
+            quantity: 100,
+            limit_price: 140.0,
+        }),
+        take_profit_price: 170.0,
+        stop_price: 140.0,
+        market_price,
+    }
+}
+
+#[test]
+fn oco_fires_one_leg_or_neither() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    portfolio.buy("AAPL", 100, 150.0).expect("within cash");
+
+    let working = bracket(155.0);
+    assert_eq!(working.fired(), None);
+    working.execute(&mut portfolio).expect("nothing fires");
+    assert_eq!(portfolio.position("AAPL"), 100);
+
+    let profit = bracket(171.0);
+    assert_eq!(profit.fired(), Some(OcoLeg::TakeProfit));
+    profit.execute(&mut portfolio).expect("shares held");
+    assert_eq!(portfolio.position("AAPL"), 0);
+    assert_eq!(portfolio.realized_pnl, 2_000.0);
+    profit.undo(&mut portfolio);
+    assert_eq!(portfolio.position("AAPL"), 100);
+
+    let stopped = bracket(139.0);
+    assert_eq!(stopped.fired(), Some(OcoLeg::StopLoss));
+    stopped.execute(&mut portfolio).expect("shares held");
+    assert_eq!(portfolio.realized_pnl, -1_000.0);
+    assert!(stopped.description().ends_with("stop filled, take-profit cancelled"));
+}
+
+#[test]
+fn tags_show_up_in_the_audit_trail() {
+    let tagged = buy("AAPL", 100, 150.0).tagged("rebalance");
+    assert_eq!(tagged.tag(), Some("rebalance"));
+    assert_eq!(tagged.to_string(), "BUY 100 AAPL @ $150.00 (tag: rebalance)");
+
+    let plain = buy("AAPL", 100, 150.0);
+    assert_eq!(plain.tag(), None);
+    assert_eq!(plain.to_string(), "BUY 100 AAPL @ $150.00");
+
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut history = TradeHistory::new();
+    history.execute(tagged, &mut portfolio).expect("within cash");
+    assert_eq!(history.executed[0].action.tag(), Some("rebalance"));
+}
+
+#[test]
+fn apply_is_all_or_nothing() {
+    let mut portfolio = Portfolio::new(50_000.0);
+    let before = portfolio.snapshot();
+    let batch = [
+        market_buy("AAPL", 100, 150.0),
+        market_buy("MSFT", 50, 400.0),
+        market_buy("NVDA", 100, 900.0),
+        market_buy("KO", 10, 60.0),
+    ];
+    assert!(matches!(
+        portfolio.apply(&batch),
+        Err(TradeError::InsufficientFunds { .. })
+    ));
+    assert_eq!(portfolio.snapshot(), before);
+
+    portfolio.apply(&batch[..2]).expect("within cash");
+    assert_eq!(portfolio.position("AAPL"), 100);
+    assert_eq!(portfolio.position("MSFT"), 50);
+    assert_eq!(portfolio.cash, 15_000.0);
+}
+
+#[test]
+fn corporate_actions_apply_and_undo() {
+    let mut portfolio = Portfolio::new(10_000.0);
+    portfolio.buy("KO", 100, 60.0).expect("within cash");
+    let mut history = CommandHistory::new();
+
+    let dividend = Dividend {
+        symbol: "KO".to_string(),
+        per_share: 0.50,
+    };
+    history.execute(Box::new(dividend), &mut portfolio).expect("held");
+    assert_eq!(portfolio.cash, 4_050.0);
+
+    let split = Split {
+        symbol: "KO".to_string(),
+        ratio: 2.0,
+    };
+    assert_eq!(split.description(), "SPLIT KO 2:1");
+    history.execute(Box::new(split), &mut portfolio).expect("valid ratio");
+    assert_eq!(portfolio.position("KO"), 200);
+    assert_eq!(portfolio.avg_price("KO"), Some(30.0));
+
+    assert!(history.undo(&mut portfolio));
+    assert_eq!(portfolio.position("KO"), 100);
+    assert_eq!(portfolio.avg_price("KO"), Some(60.0));
+    assert!(history.undo(&mut portfolio));
+    assert_eq!(portfolio.cash, 4_000.0);
+}
+
+#[test]
+fn split_rejects_non_positive_and_non_finite_ratios() {
+    let mut portfolio = Portfolio::new(10_000.0);
+    portfolio.buy("KO", 100, 60.0).expect("within cash");
+    for ratio in [0.0, -2.0, f64::INFINITY] {
+        assert_eq!(
+            portfolio.split("KO", ratio),
+            Err(TradeError::InvalidRatio(ratio))
+        );
+    }
+    assert!(matches!(
+        portfolio.split("KO", f64::NAN),
+        Err(TradeError::InvalidRatio(r)) if r.is_nan()
+    ));
+    let zero = Split {
+        symbol: "KO".to_string(),
+        ratio: 0.0,
+    };
+    assert_eq!(zero.execute(&mut portfolio), Err(TradeError::InvalidRatio(0.0)));
+    assert_eq!(portfolio.position("KO"), 100);
+    assert_eq!(portfolio.avg_price("KO"), Some(60.0));
+}
+
+#[test]
+fn limit_buy_mirrors_limit_sell() {
+    let buy = LimitBuy {
+        symbol: "AAPL".to_string(),
+        quantity: 10,
+        limit_price: 150.0,
+    };
+    assert_eq!(buy.description(), "LIMIT BUY 10 AAPL @ $150.00");
+    assert!(buy.is_marketable(149.5));
+    assert!(!buy.is_marketable(150.5));
+    assert_eq!(buy.notional(), 1_500.0);
+
+    let mut portfolio = Portfolio::new(10_000.0);
+    buy.execute(&mut portfolio).expect("within cash");
+    assert_eq!(portfolio.position("AAPL"), 10);
+    assert_eq!(portfolio.cash, 8_500.0);
+    buy.undo(&mut portfolio);
+    assert_eq!(portfolio.position("AAPL"), 0);
+    assert_eq!(portfolio.cash, 10_000.0);
+}
+
+#[test]
+fn position_queries_skip_flat_and_unknown_symbols() {
+    use std::collections::HashMap;
+
+    let mut portfolio = Portfolio::new(100_000.0);
+    portfolio.buy("MSFT", 10, 400.0).expect("within cash");
+    portfolio.buy("AAPL", 100, 150.0).expect("within cash");
+    portfolio.sell("NVDA", 5, 900.0).expect("short allowed");
+    portfolio.buy("KO", 10, 60.0).expect("within cash");
+    portfolio.sell("KO", 10, 60.0).expect("shares held");
+
+    assert_eq!(portfolio.position("TSLA"), 0);
+    assert_eq!(portfolio.avg_price("TSLA"), None);
+    assert_eq!(portfolio.position("KO"), 0);
+    assert_eq!(portfolio.symbols(), vec!["AAPL", "MSFT", "NVDA"]);
+
+    // Shorts count at their absolute value; unmarked symbols are skipped
+    let marks = HashMap::from([("AAPL".to_string(), 160.0), ("NVDA".to_string(), 1_000.0)]);
+    assert_eq!(portfolio.gross_exposure(&marks), 16_000.0 + 5_000.0);
+}
+
+#[test]
+fn macro_generated_commands_match_hand_written_ones() {
+    let generated: Vec<Box<dyn Command>> = vec![
+        market_buy("AAPL", 100, 150.0),
+        Box::new(MarketSell {
+            symbol: "AAPL".to_string(),
+            quantity: 40,
+            price: 160.0,
+        }),
+        Box::new(LimitSell {
+            symbol: "AAPL".to_string(),
+            quantity: 10,
+            limit_price: 170.0,
+        }),
+        Box::new(Cancel {
+            order_ref: "LIMIT SELL 10 AAPL @ $170.00".to_string(),
+        }),
+    ];
+    let descriptions: Vec<String> = generated.iter().map(|c| c.description()).collect();
+    assert_eq!(
+        descriptions,
+        vec![
+            "MARKET BUY 100 AAPL @ $150.00",
+            "MARKET SELL 40 AAPL @ $160.00",
+            "LIMIT SELL 10 AAPL @ $170.00",
+            "CANCEL LIMIT SELL 10 AAPL @ $170.00",
+        ]
+    );
+    // clone_box produces an equivalent, independently usable command
+    let mut portfolio = Portfolio::new(100_000.0);
+    for cmd in &generated {
+        let copy = cmd.clone_box();
+        assert_eq!(copy.description(), cmd.description());
+        copy.execute(&mut portfolio).expect("valid command");
+    }
+    assert_eq!(portfolio.position("AAPL"), 50);
+}
+
+#[test]
+fn rebuild_from_the_log_matches_the_live_book() {
+    let opening = Portfolio::new(100_000.0).with_commission(1.0);
+    let mut live = opening.clone();
+    let mut history = TradeHistory::new();
+    for trade in [
+        buy("AAPL", 100, 150.0),
+        buy("MSFT", 20, 400.0),
+        sell("AAPL", 30, 165.0),
+    ] {
+        history.execute(trade, &mut live).expect("valid trade");
+    }
+    let rebuilt = history.rebuild(&opening);
+    assert!(rebuilt.diff(&live).is_empty());
+    assert_eq!(rebuilt.realized_pnl, live.realized_pnl);
+    // Replaying without the account's commission misreports cash
+    let uncharged = history.rebuild(&Portfolio::new(100_000.0)).diff(&live);
+    assert!(uncharged.positions.is_empty());
+    assert_eq!(uncharged.cash, -150.0);
+
+    // A trade booked outside the log shows up as a break
+    live.buy("NVDA", 5, 900.0).expect("within cash");
+    let breaks = history.rebuild(&opening).diff(&live);
+    assert_eq!(breaks.positions, vec![("NVDA".to_string(), 5)]);
+}
+
+#[test]
+fn average_fill_price_blends_and_clears_on_close() {
+    let mut portfolio = Portfolio::new(10_000.0);
+    portfolio.buy("XYZ", 100, 10.0).expect("within cash");
+    portfolio.buy("XYZ", 300, 14.0).expect("within cash");
+    assert_eq!(portfolio.avg_price("XYZ"), Some(13.0));
+
+    // Partial sells realize P&L but leave the average alone
+    portfolio.sell("XYZ", 100, 15.0).expect("shares held");
+    assert_eq!(portfolio.avg_price("XYZ"), Some(13.0));
+    portfolio.sell("XYZ", 300, 15.0).expect("shares held");
+    assert_eq!(portfolio.avg_price("XYZ"), None);
+    assert_eq!(portfolio.realized_pnl, 800.0);
+
+    // Flipping through flat starts a fresh average at the fill price
+    portfolio.sell("XYZ", 50, 16.0).expect("short allowed");
+    assert_eq!(portfolio.avg_price("XYZ"), Some(16.0));
+}
+
+#[test]
+fn working_orders_rest_until_the_market_reaches_them() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    portfolio.buy("AAPL", 100, 150.0).expect("within cash");
+    let mut manager = OrderManager::new();
+    manager.submit(Box::new(LimitSell {
+        symbol: "AAPL".to_string(),
+        quantity: 100,
+        limit_price: 170.0,
+    }));
+
+    assert_eq!(manager.tick(165.0, &mut portfolio), 0);
+    assert_eq!(manager.working.len(), 1);
+    assert_eq!(portfolio.position("AAPL"), 100);
+
+    assert_eq!(manager.tick(171.0, &mut portfolio), 1);
+    assert!(manager.working.is_empty());
+    assert_eq!(portfolio.position("AAPL"), 0);
+    assert_eq!(manager.history.executed.len(), 1);
+    assert!(manager.history.undo(&mut portfolio));
+    assert_eq!(portfolio.position("AAPL"), 100);
+}
+
+#[test]
+fn net_cash_flow_sums_executed_trades_only() {
+    let mut portfolio = Portfolio::new(100_000.0).with_commission(0.01);
+    let mut history = TradeHistory::new();
+    history.execute(buy("AAPL", 100, 150.0), &mut portfolio).expect("within cash");
+    history.execute(buy("MSFT", 10, 400.0), &mut portfolio).expect("within cash");
+    history.execute(sell("AAPL", 50, 160.0), &mut portfolio).expect("shares held");
+    // Commission is excluded from the flow
+    assert_eq!(history.net_cash_flow(), -15_000.0 - 4_000.0 + 8_000.0);
+
+    history.undo(&mut portfolio).expect("sell to undo");
+    assert_eq!(history.net_cash_flow(), -19_000.0);
+    assert_eq!(TradeHistory::new().net_cash_flow(), 0.0);
+}
+
+#[test]
+fn undo_partial_trims_or_fully_reverses_the_last_trade() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut history = TradeHistory::new();
+    history.execute(buy("AAPL", 100, 150.0), &mut portfolio).expect("within cash");
+
+    history.undo_partial(30, &mut portfolio).expect("part of the trade");
+    assert_eq!(portfolio.position("AAPL"), 70);
+    assert_eq!(portfolio.cash, 89_500.0);
+    assert_eq!(history.executed[0].action.quantity(), 70);
+    assert_eq!(history.undone[0].quantity(), 30);
+
+    history.undo_partial(70, &mut portfolio).expect("the rest of it");
+    assert_eq!(portfolio.position("AAPL"), 0);
+    assert_eq!(portfolio.cash, 100_000.0);
+    assert!(history.executed.is_empty());
+    assert_eq!(
+        history.undo_partial(10, &mut portfolio),
+        Err(TradeError::NothingToUndo)
+    );
+}
+
+#[test]
+fn undo_partial_rejects_out_of_range_quantities() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    let mut history = TradeHistory::new();
+    history.execute(buy("AAPL", 100, 150.0), &mut portfolio).expect("within cash");
+    let before = portfolio.snapshot();
+
+    for qty in [0, -5] {
+        assert_eq!(
+            history.undo_partial(qty, &mut portfolio),
+            Err(TradeError::InvalidQuantity(qty))
+        );
+    }
+    assert_eq!(
+        history.undo_partial(101, &mut portfolio),
+        Err(TradeError::ExceedsTrade {
+            requested: 101,
+            executed: 100,
+        })
+    );
+    assert_eq!(portfolio.snapshot(), before);
+    assert_eq!(history.executed[0].action.quantity(), 100);
+    assert!(history.undone.is_empty());
+}
+
+#[test]
+fn cancel_records_intent_without_touching_the_portfolio() {
+    let mut portfolio = Portfolio::new(100_000.0);
+    portfolio.buy("AAPL", 100, 150.0).expect("within cash");
+    let before = portfolio.snapshot();
+    let mut history = CommandHistory::new();
+    let cancel = Cancel {
+        order_ref: "LIMIT SELL 100 AAPL @ $170.00".to_string(),
+    };
+    history.execute(Box::new(cancel), &mut portfolio).expect("always succeeds");
+    assert_eq!(portfolio.snapshot(), before);
+    assert_eq!(history.executed.len(), 1);
+    assert_eq!(
+        history.executed[0].command.description(),
+        "CANCEL LIMIT SELL 100 AAPL @ $170.00"
+    );
+    assert!(history.undo(&mut portfolio));
+    assert_eq!(portfolio.snapshot(), before);
+}
+
+#[test]
+fn zero_and_negative_fills_are_rejected_before_touching_the_book() {
+    let mut portfolio = Portfolio::new(10_000.0).with_short_selling(false);
+    let before = portfolio.snapshot();
+    assert_eq!(portfolio.buy("AAPL", 0, 150.0), Err(TradeError::InvalidQuantity(0)));
+    // A negative buy would otherwise be an unchecked short sale
+    assert_eq!(portfolio.buy("AAPL", -10, 150.0), Err(TradeError::InvalidQuantity(-10)));
+    assert_eq!(portfolio.sell("AAPL", -10, 150.0), Err(TradeError::InvalidQuantity(-10)));
+    assert_eq!(portfolio.snapshot(), before);
+    assert_eq!(portfolio.avg_price("AAPL"), None);
+
+    portfolio.buy("AAPL", 10, 150.0).expect("within cash");
+    assert_eq!(portfolio.avg_price("AAPL"), Some(150.0));
+}
+
+#[test]
+fn undo_refunds_the_commission_actually_charged() {
+    let mut portfolio = Portfolio::new(100_000.0).with_commission(1.0);
+    let mut history = TradeHistory::new();
+    history.execute(buy("AAPL", 100, 150.0), &mut portfolio).expect("within cash");
+    history.execute(sell("AAPL", 40, 160.0), &mut portfolio).expect("shares held");
+    assert_eq!(portfolio.cash, 100_000.0 - 15_100.0 + 6_360.0);
+
+    // The rate changes after the fills; undo must not create or destroy cash
+    portfolio.commission_per_share = 5.0;
+    history.undo_all(&mut portfolio);
+    assert_eq!(portfolio.cash, 100_000.0);
+    assert_eq!(portfolio.position("AAPL"), 0);
+
+    let mut commands = CommandHistory::new();
+    commands.execute(market_buy("MSFT", 10, 400.0), &mut portfolio).expect("within cash");
+    assert_eq!(portfolio.cash, 100_000.0 - 4_050.0);
+    portfolio.commission_per_share = 0.0;
+    assert!(commands.undo(&mut portfolio));
+    assert_eq!(portfolio.cash, 100_000.0);
+}
+
+#[test]
+fn rebuild_counts_trades_lost_to_max_depth() {
+    let opening = Portfolio::new(100_000.0);
+    let mut live = opening.clone();
+    let mut history = TradeHistory::with_max_depth(2);
+    for price in [10.0, 20.0, 30.0] {
+        history.execute(buy("XYZ", 100, price), &mut live).expect("within cash");
+    }
+    assert_eq!(history.evicted, 1);
+    let breaks = history.rebuild(&opening).diff(&live);
+    assert_eq!(breaks.positions, vec![("XYZ".to_string(), 100)]);
+}
+
+#[test]
+fn rebalance_to_leaves_room_for_commission_on_a_full_nav_target() {
+    use std::collections::HashMap;
+
+    let mut portfolio = Portfolio::new(10_000.0).with_commission(1.0);
+    let marks = HashMap::from([("AAPL".to_string(), 100.0)]);
+    let targets = HashMap::from([("AAPL".to_string(), 1.0)]);
+
+    // 10,000 / (100 + 1) = 99 shares, not the 100 a commission-blind sizing asks for
+    let commands = portfolio.rebalance_to(&targets, &marks);
+    assert_eq!(commands.len(), 1);
+    assert_eq!(commands[0].description(), "MARKET BUY 99 AAPL @ $100.00");
+
+    commands[0].execute(&mut portfolio).expect("commission fits in the budget");
+    assert_eq!(portfolio.position("AAPL"), 99);
+    assert!((portfolio.cash - 1.0).abs() < 1e-9);
+}
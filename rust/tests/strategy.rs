@@ -0,0 +1,656 @@
+// Smoke test: the strategy module's public API is usable from
+// outside the crate.
+
+use design_patterns::strategy::*;
+
+#[test]
+fn enum_strategy_previews_an_order() {
+    let order = Order::new("AAPL", 1_000, 185.50, ExecutionStrategy::Twap { slices: 4 });
+    let plan = order.preview().expect("valid TWAP");
+    let total: u32 = plan.children.iter().map(|c| c.quantity).sum();
+    assert_eq!(plan.children.len(), 4);
+    assert_eq!(total, 1_000);
+}
+
+#[test]
+fn closure_strategy_is_callable() {
+    let twap = twap_closure(5);
+    assert_eq!(twap("MSFT", 500, 410.0).len(), 5);
+}
+
+#[test]
+fn twap_order_slices_evenly_with_residue_last() {
+    let order = Order::new("AAPL", 1_003, 185.50, ExecutionStrategy::Twap { slices: 4 });
+    let plan = order.preview().expect("valid TWAP");
+    let quantities: Vec<u32> = plan.children.iter().map(|c| c.quantity).collect();
+    let offsets: Vec<f64> = plan.children.iter().map(|c| c.time_offset).collect();
+    assert_eq!(quantities, vec![250, 250, 250, 253]);
+    assert_eq!(offsets, vec![0.0, 0.25, 0.5, 0.75]);
+    assert!(plan.children.iter().all(|c| c.price == 185.50));
+}
+
+#[test]
+fn twap_with_zero_slices_is_rejected() {
+    let order = Order::new("AAPL", 1_000, 185.50, ExecutionStrategy::Twap { slices: 0 });
+    assert_eq!(order.preview(), Err(StrategyError::InvalidSlices));
+}
+
+#[test]
+fn builder_sets_every_field() {
+    let order = Order::builder()
+        .symbol("AAPL")
+        .quantity(10_000)
+        .price(185.50)
+        .strategy(ExecutionStrategy::Vwap {
+            participation_rate: 0.1,
+        })
+        .commission(0.005)
+        .build()
+        .expect("all required fields set");
+    assert_eq!(order.symbol, "AAPL");
+    assert_eq!(order.quantity, 10_000);
+    assert_eq!(order.price, 185.50);
+    assert_eq!(order.commission(), 50.0);
+}
+
+#[test]
+fn builder_reports_missing_fields_and_zero_quantity() {
+    let missing = Order::builder().symbol("AAPL").price(185.50).build();
+    assert_eq!(missing.unwrap_err(), BuildError::MissingField("quantity"));
+
+    let zero = Order::builder()
+        .symbol("AAPL")
+        .quantity(0)
+        .price(185.50)
+        .strategy(ExecutionStrategy::Twap { slices: 4 })
+        .build();
+    assert_eq!(zero.unwrap_err(), BuildError::ZeroQuantity);
+}
+
+#[test]
+fn order_report() {
+    use design_patterns::report::Report;
+
+    let order = Order::new("AAPL", 10_000, 185.50, ExecutionStrategy::Twap { slices: 5 });
+    assert_eq!(
+        order.report(),
+        "Order: AAPL 10000 shares @ $185.50 using TWAP(5 slices)"
+    );
+}
+
+#[test]
+fn cancel_moves_a_live_order_to_cancelled_but_not_a_filled_one() {
+    let mut order = Order::new("MSFT", 1_000, 415.00, ExecutionStrategy::Twap { slices: 4 });
+    assert_eq!(order.cancel(), Ok(()));
+    assert_eq!(order.status, OrderStatus::Cancelled);
+    assert_eq!(order.cancel(), Err(CancelError::AlreadyCancelled));
+
+    let mut filled = Order::new("MSFT", 1_000, 415.00, ExecutionStrategy::Twap { slices: 4 });
+    filled.apply_fill(1_000);
+    assert_eq!(filled.cancel(), Err(CancelError::AlreadyFilled));
+    assert_eq!(filled.status, OrderStatus::Filled);
+}
+
+#[test]
+fn send_timed_reports_the_clock_delta() {
+    use design_patterns::clock::FixedClock;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let clock = FixedClock::new(UNIX_EPOCH).with_step(Duration::from_millis(40));
+    let order = Order::new("AAPL", 1_000, 185.50, ExecutionStrategy::Twap { slices: 4 });
+    let (plan, elapsed) = order.send_timed(&clock).expect("valid TWAP");
+    assert_eq!(plan.children.len(), 4);
+    assert_eq!(elapsed, Duration::from_millis(40));
+}
+
+#[test]
+fn sniper_fires_one_full_clip_at_or_under_its_cap() {
+    let sniper = ExecutionStrategy::Sniper {
+        min_size: 500,
+        max_price: 186.00,
+    };
+    let fired = sniper.execute("AAPL", 1_000, 185.50).expect("sniper never errors");
+    assert_eq!(
+        fired,
+        vec![ChildOrder {
+            quantity: 1_000,
+            price: 185.50,
+            time_offset: 0.0,
+        }]
+    );
+    assert_eq!(sniper.name(), "Sniper");
+
+    let trait_form = SniperStrategy {
+        min_size: 500,
+        max_price: 186.00,
+    };
+    assert_eq!(trait_form.execute("AAPL", 1_000, 185.50), Ok(fired));
+}
+
+#[test]
+fn sniper_waits_above_its_cap() {
+    let sniper = ExecutionStrategy::Sniper {
+        min_size: 500,
+        max_price: 186.00,
+    };
+    assert_eq!(sniper.execute("AAPL", 1_000, 186.25), Ok(Vec::new()));
+    assert_eq!(sniper.estimated_cost(1_000, 186.25), f64::INFINITY);
+
+    let trait_form = SniperStrategy {
+        min_size: 500,
+        max_price: 186.00,
+    };
+    assert_eq!(trait_form.execute("AAPL", 1_000, 186.25), Ok(Vec::new()));
+}
+
+#[test]
+fn half_fill_reports_the_unfilled_remainder() {
+    let twap = ExecutionStrategy::Twap { slices: 4 };
+    let report = twap.execute_with_fill("AAPL", 1_000, 185.50, 0.5).expect("valid TWAP");
+    let quantities: Vec<u32> = report.filled.iter().map(|c| c.quantity).collect();
+    assert_eq!(quantities, vec![125, 125, 125, 125]);
+    assert_eq!(report.unfilled, 500);
+
+    let over = twap.execute_with_fill("AAPL", 1_000, 185.50, 1.5).expect("valid TWAP");
+    assert_eq!(over.unfilled, 0);
+}
+
+#[test]
+fn every_enum_variant_converts_to_its_trait_form() {
+    let variants = [
+        ExecutionStrategy::Twap { slices: 4 },
+        ExecutionStrategy::Vwap {
+            participation_rate: 0.2,
+        },
+        ExecutionStrategy::Iceberg { visible_qty: 300 },
+        ExecutionStrategy::StealthTwap {
+            slices: 5,
+            size_jitter: 0.3,
+            time_jitter: 0.5,
+            seed: 42,
+        },
+        ExecutionStrategy::ImplementationShortfall { urgency: 0.8 },
+        ExecutionStrategy::Peg {
+            reference: PegReference::Mid,
+            offset_bps: 5.0,
+        },
+        ExecutionStrategy::DarkPool { min_fill: 300 },
+        ExecutionStrategy::Sniper {
+            min_size: 100,
+            max_price: 190.0,
+        },
+    ];
+    for strategy in variants {
+        let expected = strategy.child_orders(1_000, 185.50).expect("valid strategy");
+        let name = strategy.name();
+        let boxed: Box<dyn ExecutionStrategyTrait> = strategy.into();
+        assert_eq!(boxed.name(), name);
+        assert_eq!(boxed.execute("AAPL", 1_000, 185.50), Ok(expected), "{}", name);
+    }
+}
+
+#[test]
+fn passive_strategies_fill_at_mid_and_aggressive_ones_cross() {
+    let quoted = |strategy| Order::new("AAPL", 1_000, 0.0, strategy).with_quote(185.40, 185.60);
+    let iceberg = quoted(ExecutionStrategy::Iceberg { visible_qty: 200 });
+    let sniper = quoted(ExecutionStrategy::Sniper {
+        min_size: 100,
+        max_price: 186.0,
+    });
+
+    let passive = iceberg.send().expect("valid iceberg").execution_price;
+    let aggressive = sniper.send().expect("valid sniper").execution_price;
+    assert!((iceberg.price - 185.50).abs() < 1e-9);
+    assert!((passive - 185.50).abs() < 1e-9);
+    assert_eq!(aggressive, 185.60);
+    assert!(passive - iceberg.bid < aggressive - iceberg.bid);
+}
+
+#[test]
+fn zero_slice_twap_is_rejected_in_every_form() {
+    assert_eq!(
+        TwapStrategy { slices: 0 }.execute("AAPL", 1_000, 185.50),
+        Err(StrategyError::InvalidSlices)
+    );
+    assert!(twap_closure(0)("AAPL", 1_000, 185.50).is_empty());
+}
+
+#[test]
+fn valid_twap_agrees_across_enum_trait_and_closure() {
+    let from_enum = ExecutionStrategy::Twap { slices: 3 }
+        .child_orders(900, 50.0)
+        .expect("valid TWAP");
+    let from_trait = TwapStrategy { slices: 3 }
+        .execute("AAPL", 900, 50.0)
+        .expect("valid TWAP");
+    let from_closure = twap_closure(3)("AAPL", 900, 50.0);
+    assert_eq!(from_enum.len(), 3);
+    assert!(from_enum.iter().all(|c| c.quantity == 300));
+    assert_eq!(from_enum, from_trait);
+    assert_eq!(from_enum, from_closure);
+}
+
+fn stealth(seed: u64) -> ExecutionStrategy {
+    ExecutionStrategy::StealthTwap {
+        slices: 6,
+        size_jitter: 0.4,
+        time_jitter: 0.8,
+        seed,
+    }
+}
+
+#[test]
+fn stealth_schedule_is_reproducible_from_its_seed() {
+    let first = stealth(42).child_orders(10_000, 75.0).expect("valid stealth");
+    let again = stealth(42).child_orders(10_000, 75.0).expect("valid stealth");
+    let other = stealth(7).child_orders(10_000, 75.0).expect("valid stealth");
+    assert_eq!(first, again);
+    assert_ne!(first, other);
+}
+
+#[test]
+fn stealth_quantities_sum_and_times_stay_ordered() {
+    let children = stealth(42).child_orders(10_001, 75.0).expect("valid stealth");
+    assert_eq!(children.len(), 6);
+    assert_eq!(children.iter().map(|c| c.quantity).sum::<u32>(), 10_001);
+    // Sizes actually vary, and each release stays inside the window in order
+    assert!(children.iter().any(|c| c.quantity != children[0].quantity));
+    assert!(children.windows(2).all(|w| w[0].time_offset < w[1].time_offset));
+    assert!(children.iter().all(|c| (0.0..1.0).contains(&c.time_offset)));
+}
+
+#[test]
+fn shortfall_front_loads_at_high_urgency() {
+    let is = ExecutionStrategy::ImplementationShortfall { urgency: 0.8 };
+    let children = is.child_orders(10_000, 42.0).expect("valid IS");
+    assert_eq!(children.len(), IS_SLICES as usize);
+    assert_eq!(children.iter().map(|c| c.quantity).sum::<u32>(), 10_000);
+    assert!(children.windows(2).all(|w| w[0].quantity > w[1].quantity));
+
+    let trait_form = ImplementationShortfallStrategy { urgency: 0.8 }
+        .execute("XOM", 10_000, 42.0)
+        .expect("valid IS");
+    assert_eq!(children, trait_form);
+}
+
+#[test]
+fn shortfall_at_zero_urgency_is_flat() {
+    let is = ExecutionStrategy::ImplementationShortfall { urgency: 0.0 };
+    let children = is.child_orders(1_000, 42.0).expect("valid IS");
+    assert!(children.iter().all(|c| c.quantity == 200));
+}
+
+#[test]
+fn estimated_cost_prices_each_strategy_before_sending() {
+    let twap = ExecutionStrategy::Twap { slices: 5 };
+    let vwap = ExecutionStrategy::Vwap {
+        participation_rate: 0.1,
+    };
+    let iceberg = ExecutionStrategy::Iceberg { visible_qty: 1_000 };
+    assert_eq!(twap.estimated_cost(10_000, 100.0), 25.0);
+    assert_eq!(vwap.estimated_cost(10_000, 100.0), 250.0);
+    assert_eq!(iceberg.estimated_cost(10_000, 100.0), 50.0);
+    assert_eq!(TwapStrategy { slices: 5 }.estimated_cost(10_000, 100.0), 25.0);
+    // Invalid parameters never look cheap
+    let bad = ExecutionStrategy::Twap { slices: 0 };
+    assert_eq!(bad.estimated_cost(10_000, 100.0), f64::INFINITY);
+
+    let order = Order::new("AAPL", 10_000, 100.0, vwap.clone());
+    let candidates = [vwap, iceberg, bad, twap.clone()];
+    assert_eq!(order.cheapest_of(&candidates), Some(&twap));
+    assert_eq!(order.cheapest_of(&[]), None);
+}
+
+#[test]
+fn strategy_fn_clones_share_one_closure_across_threads() {
+    let twap = twap_closure(4);
+    let copy = twap.clone();
+    let remote = std::thread::spawn(move || copy("AAPL", 1_000, 185.50))
+        .join()
+        .expect("thread runs");
+    assert_eq!(remote, twap("AAPL", 1_000, 185.50));
+    assert_eq!(remote.len(), 4);
+}
+
+#[test]
+fn fills_walk_an_order_from_new_to_filled() {
+    let mut order = Order::new("AAPL", 1_000, 185.50, ExecutionStrategy::Twap { slices: 4 });
+    assert_eq!(order.status, OrderStatus::New);
+    order.apply_fill(400);
+    assert_eq!(order.status, OrderStatus::PartiallyFilled { filled: 400 });
+    assert!(order.send().is_ok());
+    // Overfills clamp at the order quantity
+    order.apply_fill(700);
+    assert_eq!(order.status, OrderStatus::Filled);
+    order.apply_fill(1);
+    assert_eq!(order.status, OrderStatus::Filled);
+    assert_eq!(
+        order.send(),
+        Err(StrategyError::OrderNotLive(OrderStatus::Filled))
+    );
+
+    let mut cancelled = Order::new("AAPL", 1_000, 185.50, ExecutionStrategy::Twap { slices: 4 });
+    cancelled.status = OrderStatus::Cancelled;
+    cancelled.apply_fill(100);
+    assert_eq!(cancelled.status, OrderStatus::Cancelled);
+    assert_eq!(
+        cancelled.send(),
+        Err(StrategyError::OrderNotLive(OrderStatus::Cancelled))
+    );
+}
+
+fn one_of_each_strategy() -> Vec<ExecutionStrategy> {
+    vec![
+        ExecutionStrategy::Twap { slices: 5 },
+        ExecutionStrategy::Vwap {
+            participation_rate: 0.15,
+        },
+        ExecutionStrategy::Iceberg { visible_qty: 500 },
+        ExecutionStrategy::StealthTwap {
+            slices: 6,
+            size_jitter: 0.3,
+            time_jitter: 0.5,
+            seed: 42,
+        },
+        ExecutionStrategy::ImplementationShortfall { urgency: 0.8 },
+        ExecutionStrategy::Peg {
+            reference: PegReference::Mid,
+            offset_bps: 5.0,
+        },
+        ExecutionStrategy::DarkPool { min_fill: 1_000 },
+        ExecutionStrategy::Sniper {
+            min_size: 2_000,
+            max_price: 150.25,
+        },
+    ]
+}
+
+#[test]
+fn command_strings_parse_and_round_trip() {
+    assert_eq!(
+        " twap : 5 ".parse::<ExecutionStrategy>(),
+        Ok(ExecutionStrategy::Twap { slices: 5 })
+    );
+    assert_eq!(
+        "vwap:0.2".parse::<ExecutionStrategy>(),
+        Ok(ExecutionStrategy::Vwap {
+            participation_rate: 0.2
+        })
+    );
+    for strategy in one_of_each_strategy() {
+        let text = strategy.to_string();
+        assert_eq!(text.parse(), Ok(strategy));
+        assert_eq!(text.parse::<ExecutionStrategy>().unwrap().to_string(), text);
+    }
+    assert_eq!(
+        ExecutionStrategy::Iceberg { visible_qty: 500 }.to_string(),
+        "ICEBERG:500"
+    );
+}
+
+#[test]
+fn malformed_command_strings_are_rejected() {
+    assert_eq!(
+        "TWAP".parse::<ExecutionStrategy>(),
+        Err(ParseStrategyError::MissingParameter("TWAP".to_string()))
+    );
+    assert_eq!(
+        "POV:0.1".parse::<ExecutionStrategy>(),
+        Err(ParseStrategyError::UnknownStrategy("POV".to_string()))
+    );
+    assert_eq!(
+        "TWAP:five".parse::<ExecutionStrategy>(),
+        Err(ParseStrategyError::InvalidParameter {
+            strategy: "TWAP",
+            value: "five".to_string(),
+        })
+    );
+    assert_eq!(
+        "PEG:MID".parse::<ExecutionStrategy>(),
+        Err(ParseStrategyError::InvalidParameter {
+            strategy: "PEG",
+            value: "MID".to_string(),
+        })
+    );
+}
+
+#[test]
+fn every_strategy_describes_its_parameters() {
+    let rendered: Vec<String> = one_of_each_strategy()
+        .iter()
+        .map(ExecutionStrategy::description)
+        .collect();
+    assert_eq!(
+        rendered,
+        vec![
+            "TWAP(5 slices)",
+            "VWAP(15% participation)",
+            "Iceberg(500 visible)",
+            "Stealth(6 slices, 30% size / 50% time jitter, seed 42)",
+            "IS(80% urgency)",
+            "Peg(MID +5.0bps)",
+            "Dark(1000 min fill)",
+            "Sniper(2000+ shares at <= $150.25)",
+        ]
+    );
+}
+
+#[test]
+fn composite_splits_the_parent_across_stages_in_order() {
+    let composite = CompositeStrategy {
+        stages: vec![
+            Box::new(TwapStrategy { slices: 4 }),
+            Box::new(VwapStrategy {
+                participation_rate: 0.25,
+            }),
+        ],
+        split: 0.6,
+    };
+    assert_eq!(composite.allocations(1_000), vec![600, 400]);
+    let children = composite.execute("AAPL", 1_000, 185.50).expect("valid stages");
+    assert_eq!(children.iter().map(|c| c.quantity).sum::<u32>(), 1_000);
+    assert!(children.windows(2).all(|w| w[0].time_offset <= w[1].time_offset));
+    // The TWAP stage fills the first half of the window
+    assert!(children[..4].iter().all(|c| c.quantity == 150 && c.time_offset < 0.5));
+
+    let mut copy = composite.clone();
+    copy.split = 0.5;
+    copy.stages.pop();
+    assert_eq!(composite.stages.len(), 2);
+    assert_eq!(composite.allocations(1_000), vec![600, 400]);
+}
+
+#[test]
+fn registry_creates_registered_strategies_by_name() {
+    let mut registry = StrategyRegistry::with_defaults();
+    assert_eq!(registry.create("TWAP").expect("default").name(), "TWAP");
+    assert!(registry.create("Sniper").is_none());
+
+    registry.register("Sniper", || {
+        Box::new(SniperStrategy {
+            min_size: 1_000,
+            max_price: 150.0,
+        })
+    });
+    let sniper = registry.create("Sniper").expect("just registered");
+    assert_eq!(sniper.name(), "Sniper");
+    assert_eq!(sniper.execute("AAPL", 5_000, 149.0).expect("fires").len(), 1);
+
+    // Re-registering replaces the previous factory
+    registry.register("TWAP", || Box::new(TwapStrategy { slices: 2 }));
+    let twap = registry.create("TWAP").expect("replaced");
+    assert_eq!(twap.execute("AAPL", 1_000, 150.0).expect("valid").len(), 2);
+}
+
+#[test]
+fn commission_is_per_share_and_folds_into_all_in_cost() {
+    let order = Order::new(
+        "AAPL",
+        10_000,
+        50.0,
+        ExecutionStrategy::Iceberg { visible_qty: 1_000 },
+    )
+    .with_commission(0.005);
+    assert!((order.commission() - 50.0).abs() < 1e-9);
+    assert!((order.all_in_cost() - 500_050.0).abs() < 1e-6);
+
+    let free = Order::new("AAPL", 10_000, 50.0, ExecutionStrategy::Twap { slices: 4 });
+    assert_eq!(free.commission(), 0.0);
+    assert_eq!(free.all_in_cost(), 500_000.0);
+}
+
+// The paused clock auto-advances through each sleep, so the elapsed
+// time is exactly the pacing between slices
+#[cfg(feature = "async")]
+#[tokio::test(start_paused = true)]
+async fn async_twap_paces_its_slices_over_time() {
+    use design_patterns::strategy::async_strategies::{AsyncExecutionStrategy, AsyncTwapStrategy};
+    use std::time::Duration;
+
+    let twap = AsyncTwapStrategy {
+        slices: 4,
+        interval: Duration::from_millis(250),
+    };
+    let start = tokio::time::Instant::now();
+    assert_eq!(twap.execute("AAPL", 1_003, 185.50).await, Ok(4));
+    assert_eq!(start.elapsed(), Duration::from_millis(750));
+
+    let zero = AsyncTwapStrategy {
+        slices: 0,
+        interval: Duration::from_millis(250),
+    };
+    assert_eq!(zero.execute("AAPL", 1_003, 185.50).await, Err(StrategyError::InvalidSlices));
+}
+
+#[test]
+fn peg_quotes_its_offset_from_the_reference() {
+    let peg = ExecutionStrategy::Peg {
+        reference: PegReference::Mid,
+        offset_bps: 5.0,
+    };
+    let children = peg.child_orders(1_000, 100.0).expect("valid peg");
+    assert_eq!(children.len(), 1);
+    assert_eq!(children[0].quantity, 1_000);
+    assert!((children[0].price - 100.05).abs() < 1e-9);
+
+    // Bid sits QUOTED_HALF_SPREAD_BPS under mid before the offset
+    let bid = PegStrategy {
+        reference: PegReference::Bid,
+        offset_bps: -2.0,
+    };
+    let child = &bid.execute("AAPL", 1_000, 100.0).expect("valid peg")[0];
+    assert!((child.price - 99.99 * 0.9998).abs() < 1e-9);
+    assert_eq!(" ask ".parse::<PegReference>(), Ok(PegReference::Ask));
+}
+
+#[test]
+fn closure_strategies_return_their_child_orders() {
+    let twap = twap_closure(6)("AAPL", 6_005, 185.50);
+    let quantities: Vec<u32> = twap.iter().map(|c| c.quantity).collect();
+    assert_eq!(quantities, vec![1_000, 1_000, 1_000, 1_000, 1_000, 1_005]);
+
+    let vwap = vwap_closure(0.25)("MSFT", 1_000, 415.0);
+    assert_eq!(vwap.len(), 4);
+    assert!(vwap.iter().all(|c| c.quantity == 250 && c.price == 415.0));
+}
+
+#[test]
+fn dark_pool_routes_whole_blocks_and_holds_back_the_rest() {
+    let dark = ExecutionStrategy::DarkPool { min_fill: 500 };
+    let exact = dark.child_orders(2_000, 50.0).expect("valid dark");
+    assert_eq!(exact.len(), 4);
+    assert!(exact.iter().all(|c| c.quantity == 500));
+
+    let leftover = dark.child_orders(2_300, 50.0).expect("valid dark");
+    assert_eq!(leftover.iter().map(|c| c.quantity).sum::<u32>(), 2_000);
+    assert!(leftover.iter().all(|c| c.quantity >= 500));
+    assert_eq!(dark_routable(2_300, 500), 2_000);
+    assert!(dark.child_orders(300, 50.0).expect("valid dark").is_empty());
+}
+
+#[test]
+fn preview_matches_send_without_changing_the_order() {
+    let order = Order::new("AAPL", 1_000, 185.50, ExecutionStrategy::Twap { slices: 4 })
+        .with_quote(185.45, 185.55)
+        .with_commission(0.01);
+    let plan = order.preview().expect("valid TWAP");
+    assert_eq!(order.status, OrderStatus::New);
+    assert_eq!(order.preview(), Ok(plan.clone()));
+    assert_eq!(plan.strategy, "TWAP");
+    assert_eq!(plan.execution_price, 185.55);
+    assert!(plan.children.iter().all(|child| child.price == 185.55));
+    assert_eq!(order.send(), Ok(plan));
+}
+
+#[test]
+fn vwap_participation_must_be_in_zero_to_one() {
+    for rate in [-0.1, 0.0, 1.5] {
+        let vwap = ExecutionStrategy::Vwap {
+            participation_rate: rate,
+        };
+        assert_eq!(
+            vwap.child_orders(1_000, 50.0),
+            Err(StrategyError::InvalidParticipation(rate))
+        );
+        assert_eq!(
+            VwapStrategy {
+                participation_rate: rate
+            }
+            .execute("AAPL", 1_000, 50.0),
+            Err(StrategyError::InvalidParticipation(rate))
+        );
+    }
+    let half = ExecutionStrategy::Vwap {
+        participation_rate: 0.5,
+    };
+    assert_eq!(half.child_orders(1_000, 50.0).expect("valid VWAP").len(), 2);
+    assert!(VwapStrategy {
+        participation_rate: 0.5
+    }
+    .execute("AAPL", 1_000, 50.0)
+    .is_ok());
+}
+
+// The dispatch benchmark assumes the three forms do the same work
+#[test]
+fn enum_trait_object_and_closure_dispatch_agree() {
+    let book = [
+        (ExecutionStrategy::Twap { slices: 8 }, twap_closure(8)),
+        (
+            ExecutionStrategy::Vwap {
+                participation_rate: 0.2,
+            },
+            vwap_closure(0.2),
+        ),
+    ];
+    for (i, (strategy, closure)) in book.iter().enumerate() {
+        let quantity = 1_000 + 137 * i as u32;
+        let boxed: Box<dyn ExecutionStrategyTrait> = strategy.clone().into();
+        let from_enum = strategy.child_orders(quantity, 99.0).expect("valid");
+        assert_eq!(boxed.execute("AAPL", quantity, 99.0), Ok(from_enum.clone()));
+        assert_eq!(closure("AAPL", quantity, 99.0), from_enum);
+        assert_eq!(
+            boxed.estimated_cost(quantity, 99.0),
+            strategy.estimated_cost(quantity, 99.0)
+        );
+    }
+}
+
+#[test]
+fn sequenced_closures_cover_the_parent_quantity() {
+    let chained = sequence(twap_closure(4), vwap_closure(0.2), 0.5);
+    let children = chained("AAPL", 2_001, 185.50);
+    assert_eq!(children.iter().map(|c| c.quantity).sum::<u32>(), 2_001);
+    // Four TWAP slices in the first half, then the VWAP chunks
+    assert!(children[..4].iter().all(|c| c.time_offset < 0.5));
+    assert!(children[4..].iter().all(|c| c.time_offset >= 0.5));
+    assert!(children.windows(2).all(|w| w[0].time_offset <= w[1].time_offset));
+}
+
+#[test]
+fn oversized_fill_report_clamps_instead_of_overflowing() {
+    let mut order = Order::new("AAPL", 1_000, 185.50, ExecutionStrategy::Twap { slices: 4 });
+    order.apply_fill(400);
+    order.apply_fill(u32::MAX);
+    assert_eq!(order.status, OrderStatus::Filled);
+}
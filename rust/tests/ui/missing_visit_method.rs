@@ -0,0 +1,19 @@
+// A visitor that forgets one instrument must not compile.
+
+use design_patterns::visitor::*;
+
+struct Incomplete;
+
+impl InstrumentVisitor for Incomplete {
+    fn visit_bond(&self, _: &Bond) {}
+    fn visit_swap(&self, _: &Swap) {}
+    fn visit_fra(&self, _: &Fra) {}
+    fn visit_option(&self, _: &Option) {}
+    fn visit_future(&self, _: &Future) {}
+    fn visit_cds(&self, _: &Cds) {}
+    fn visit_fx_forward(&self, _: &FxForward) {}
+    fn visit_cap_floor(&self, _: &CapFloor) {}
+    fn visit_equity(&self, _: &Equity) {}
+}
+
+fn main() {}
@@ -0,0 +1,924 @@
+// Smoke test: the visitor module's public API is usable from
+// outside the crate.
+
+use design_patterns::visitor::*;
+
+#[test]
+fn bond_prices_through_the_enum() {
+    let bond = Instrument::Bond(Bond {
+        issuer: "US-TREASURY".to_string(),
+        currency: Currency::USD,
+        face_value: 1_000.0,
+        coupon_rate: 0.05,
+        maturity_years: 5,
+        callable: None,
+    });
+    // Coupon equal to the discount rate prices at par
+    let px = price(&bond, &MarketData::demo()).expect("valid bond");
+    assert!((px - 1_000.0).abs() < 1e-6);
+    assert_eq!(bond.name(), "Bond");
+}
+
+#[test]
+fn matured_bond_is_rejected() {
+    let bond = Instrument::Bond(Bond {
+        issuer: "MATURED-CO".to_string(),
+        currency: Currency::USD,
+        face_value: 1_000.0,
+        coupon_rate: 0.05,
+        maturity_years: 0,
+        callable: None,
+    });
+    assert_eq!(price_default(&bond), Err(PricingError::ZeroMaturity));
+}
+
+#[test]
+fn mixed_portfolio_prices_to_known_values() {
+    let portfolio = vec![
+        // Coupon at the discount rate: par
+        Instrument::Bond(Bond {
+            issuer: "US-TREASURY".to_string(),
+            currency: Currency::USD,
+            face_value: 1_000_000.0,
+            coupon_rate: 0.05,
+            maturity_years: 5,
+            callable: None,
+        }),
+        // 50bp over the 4% par rate for 2 years on 1mm
+        Instrument::Swap(Swap {
+            currency: Currency::USD,
+            notional: 1_000_000.0,
+            fixed_rate: 0.045,
+            tenor_years: 2,
+        }),
+        Instrument::Future(Future {
+            underlying: "ES".to_string(),
+            currency: Currency::USD,
+            contract_price: 4_500.0,
+            contract_size: 50.0,
+            num_contracts: 2,
+        }),
+        Instrument::Equity(Equity {
+            ticker: "ACME".to_string(),
+            currency: Currency::USD,
+            shares: 100,
+            price: 50.0,
+        }),
+    ];
+    let prices: Vec<f64> = portfolio
+        .iter()
+        .map(|inst| price(inst, &MarketData::demo()).expect("valid instrument"))
+        .collect();
+    let expected = [1_000_000.0, 10_000.0, 450_000.0, 5_000.0];
+    for (px, want) in prices.iter().zip(expected) {
+        assert!((px - want).abs() < 1e-6, "priced {} expected {}", px, want);
+    }
+    let total = portfolio_value(&portfolio, &MarketData::demo()).expect("all instruments price");
+    assert!((total - 1_465_000.0).abs() < 1e-6);
+
+    // Par at 4% on the bond, 1mm * -0.5% * 2 on the swap, ES marked at 4,600
+    let market = MarketData {
+        risk_free: 0.04,
+        swap_rate: 0.05,
+        ..MarketData::demo()
+    }
+    .with_spot("ES", 4_600.0);
+    let bond_at_four = 1_000_000.0 * (0.05 / 0.04 * (1.0 - 1.04f64.powi(-5)) + 1.04f64.powi(-5));
+    let total = portfolio_value(&portfolio, &market).expect("all instruments price");
+    let expected = bond_at_four - 10_000.0 + 460_000.0 + 5_000.0;
+    assert!((total - expected).abs() < 1e-6, "priced {} expected {}", total, expected);
+}
+
+#[test]
+fn instrument_report() {
+    use design_patterns::report::Report;
+
+    let equity = Instrument::Equity(Equity {
+        ticker: "ACME".to_string(),
+        currency: Currency::USD,
+        shares: 100,
+        price: 50.0,
+    });
+    assert_eq!(equity.report(), "ACME Equity(100 @ 50.00 USD) = $5000.00");
+}
+
+#[test]
+fn dedup_keeps_one_of_identical_bonds() {
+    let bond = Instrument::Bond(Bond {
+        issuer: "US-TREASURY".to_string(),
+        currency: Currency::USD,
+        face_value: 1_000.0,
+        coupon_rate: 0.05,
+        maturity_years: 5,
+        callable: None,
+    });
+    let swap = Instrument::Swap(Swap {
+        currency: Currency::USD,
+        notional: 1_000_000.0,
+        fixed_rate: 0.04,
+        tenor_years: 5,
+    });
+    let mut portfolio = vec![bond.clone(), swap.clone(), bond.clone()];
+    dedup(&mut portfolio);
+    assert_eq!(portfolio, vec![bond, swap]);
+}
+
+#[test]
+fn pricers_at_different_rates_disagree() {
+    let bond = Instrument::Bond(Bond {
+        issuer: "US-TREASURY".to_string(),
+        currency: Currency::USD,
+        face_value: 1_000.0,
+        coupon_rate: 0.05,
+        maturity_years: 5,
+        callable: None,
+    });
+    let pricers: Vec<Box<dyn Pricer>> = vec![
+        Box::new(DiscountCurvePricer { rate: 0.03 }),
+        Box::new(DiscountCurvePricer { rate: 0.07 }),
+    ];
+    let prices: Vec<f64> = pricers
+        .iter()
+        .map(|p| p.price(&bond).expect("valid bond"))
+        .collect();
+    // Premium below par yield, discount above it
+    assert!(prices[0] > 1_000.0);
+    assert!(prices[1] < 1_000.0);
+}
+
+#[test]
+fn swap_npv_flips_sign_as_the_float_rate_crosses_the_fixed_rate() {
+    let swap = Instrument::Swap(Swap {
+        currency: Currency::USD,
+        notional: 1_000_000.0,
+        fixed_rate: 0.045,
+        tenor_years: 5,
+    });
+    let npv = |swap_rate| {
+        let market = MarketData {
+            swap_rate,
+            ..MarketData::demo()
+        };
+        price(&swap, &market).expect("valid swap")
+    };
+    assert!(npv(0.04) > 0.0);
+    assert_eq!(npv(0.045), 0.0);
+    assert!(npv(0.05) < 0.0);
+    assert_eq!(price_default(&swap), Ok(npv(PAR_RATE)));
+}
+
+#[test]
+fn dilution_prices_a_warrant_below_the_equivalent_option() {
+    let warrant = Warrant {
+        underlying: "ACME".to_string(),
+        currency: Currency::USD,
+        strike: 55.0,
+        spot: 50.0,
+        vol: 0.30,
+        time_to_expiry: 3.0,
+        shares_outstanding: 8_000_000,
+        warrants_issued: 2_000_000,
+    };
+    let option = price_default(&Instrument::Option(warrant.as_option())).expect("valid option");
+    let diluted = price_default(&Instrument::Warrant(warrant.clone())).expect("valid warrant");
+    assert_eq!(warrant.dilution_factor(), 0.8);
+    assert!(diluted < option);
+    assert!((diluted - 0.8 * option).abs() < 1e-9);
+}
+
+#[test]
+fn validate_portfolio_reports_every_bad_instrument() {
+    let good = Instrument::Swap(Swap {
+        currency: Currency::USD,
+        notional: 1_000_000.0,
+        fixed_rate: 0.04,
+        tenor_years: 5,
+    });
+    let matured = Instrument::Bond(Bond {
+        issuer: "MATURED-CO".to_string(),
+        currency: Currency::USD,
+        face_value: 1_000.0,
+        coupon_rate: 0.05,
+        maturity_years: 0,
+        callable: None,
+    });
+    let negative = Instrument::Swap(Swap {
+        currency: Currency::USD,
+        notional: -1_000_000.0,
+        fixed_rate: 0.04,
+        tenor_years: 5,
+    });
+    assert_eq!(validate_portfolio(std::slice::from_ref(&good)), Ok(()));
+    assert_eq!(
+        validate_portfolio(&[matured, good, negative]),
+        Err(vec![
+            ValidationError {
+                index: 0,
+                error: PricingError::ZeroMaturity,
+            },
+            ValidationError {
+                index: 2,
+                error: PricingError::NegativeNotional(-1_000_000.0),
+            },
+        ])
+    );
+}
+
+fn seven_year_bond(callable: std::option::Option<CallSchedule>) -> Bond {
+    Bond {
+        issuer: "ACME-CORP".to_string(),
+        currency: Currency::USD,
+        face_value: 1_000_000.0,
+        coupon_rate: 0.06,
+        maturity_years: 7,
+        callable,
+    }
+}
+
+fn upward_curve() -> DiscountCurve {
+    DiscountCurve {
+        zero_rates: vec![0.040, 0.042, 0.044, 0.045, 0.046],
+    }
+}
+
+#[test]
+fn oas_of_a_straight_bond_is_its_z_spread() {
+    let bond = seven_year_bond(None);
+    let curve = upward_curve();
+    let z = z_spread(&bond, 1_010_000.0, &curve).expect("price within range");
+    let oas = option_adjusted_spread(&bond, 1_010_000.0, &curve).expect("price within range");
+    assert!((oas - z).abs() < 1e-12);
+}
+
+#[test]
+fn call_option_pulls_oas_below_z_spread() {
+    let callable = seven_year_bond(Some(CallSchedule {
+        first_call_year: 3,
+        call_price: 1_000_000.0,
+    }));
+    let curve = upward_curve();
+    let z = z_spread(&callable, 1_010_000.0, &curve).expect("price within range");
+    let oas = option_adjusted_spread(&callable, 1_010_000.0, &curve).expect("price within range");
+    // The investor is short the call, so less spread is left over
+    assert!(oas < z - 1e-4, "oas {oas} vs z {z}");
+}
+
+#[test]
+fn calibrated_lattice_reprices_the_curve() {
+    // A call that can never be exercised leaves a straight bond
+    let never_called = seven_year_bond(Some(CallSchedule {
+        first_call_year: 99,
+        call_price: 1_000_000.0,
+    }));
+    let curve = upward_curve();
+    let on_lattice = callable_pv_on_lattice(
+        &never_called,
+        never_called.callable.as_ref().unwrap(),
+        &curve,
+        0.0,
+    )
+    .expect("non-empty curve");
+    let on_curve = bond_pv_over_curve(&never_called, &curve, 0.0).expect("non-empty curve");
+    assert!((on_lattice - on_curve).abs() < 1e-6);
+}
+
+#[test]
+fn empty_curve_yields_none_instead_of_panicking() {
+    let curve = DiscountCurve { zero_rates: vec![] };
+    assert_eq!(curve.zero_rate(3), None);
+    assert_eq!(curve.discount_factor(3, 0.0), None);
+    assert_eq!(z_spread(&seven_year_bond(None), 1_000_000.0, &curve), None);
+    assert_eq!(
+        option_adjusted_spread(&seven_year_bond(None), 1_000_000.0, &curve),
+        None
+    );
+}
+
+fn fra(start_months: u32, end_months: u32) -> Instrument {
+    Instrument::Fra(Fra {
+        currency: Currency::USD,
+        notional: 1_000_000.0,
+        fixed_rate: 0.045,
+        start_months,
+        end_months,
+    })
+}
+
+#[test]
+fn fra_prices_its_accrual_period() {
+    let market = MarketData::demo();
+    let three_by_six = fra(3, 6);
+    let Instrument::Fra(r) = &three_by_six else {
+        unreachable!()
+    };
+    assert_eq!(r.accrual(), 0.25);
+    let expected = 1_000_000.0 * (0.045 - 0.04) * 0.25 / 1.04f64.powf(0.5);
+    let px = price(&three_by_six, &market).expect("valid FRA");
+    assert!((px - expected).abs() < 1e-6);
+    assert_eq!(dv01(&three_by_six, &market), 25.0);
+    assert_eq!(capital_charge(&three_by_six, &market), 12_500.0);
+    let flows = cashflows(&three_by_six, &market);
+    assert_eq!(flows.len(), 1);
+    assert_eq!(flows[0].period, 1);
+}
+
+#[test]
+fn inverted_fra_is_rejected_and_accrues_nothing() {
+    let market = MarketData::demo();
+    let inverted = fra(6, 3);
+    assert_eq!(price(&inverted, &market), Err(PricingError::ZeroMaturity));
+    // Risk and capital paths see a zero accrual rather than underflowing
+    assert_eq!(dv01(&inverted, &market), 0.0);
+    assert_eq!(risk_report(&inverted, &market).dv01, Some(0.0));
+    assert_eq!(capital_charge(&inverted, &market), 0.0);
+    assert_eq!(cashflows(&inverted, &market)[0].amount, 0.0);
+    regulatory_report(&inverted, &market);
+}
+
+fn treasury(coupon_rate: f64, maturity_years: u32) -> Instrument {
+    Instrument::Bond(Bond {
+        issuer: "US-TREASURY".to_string(),
+        currency: Currency::USD,
+        face_value: 1_000.0,
+        coupon_rate,
+        maturity_years,
+        callable: None,
+    })
+}
+
+#[test]
+fn price_returns_errors_instead_of_garbage() {
+    let market = MarketData::demo();
+    assert_eq!(
+        price(&treasury(0.05, 0), &market),
+        Err(PricingError::ZeroMaturity)
+    );
+    assert_eq!(
+        price(&treasury(-0.01, 5), &market),
+        Err(PricingError::NegativeRate(-0.01))
+    );
+    let expected: f64 = (1..=5).map(|t| 60.0 / 1.05f64.powi(t)).sum::<f64>()
+        + 1_000.0 / 1.05f64.powi(5);
+    let px = price(&treasury(0.06, 5), &market).expect("valid bond");
+    assert!((px - expected).abs() < 1e-9);
+}
+
+#[test]
+fn discount_rate_comes_from_the_market() {
+    let bond = treasury(0.05, 10);
+    let at = |risk_free| {
+        let market = MarketData {
+            risk_free,
+            ..MarketData::demo()
+        };
+        price(&bond, &market).expect("valid bond")
+    };
+    let (low, high) = (at(0.03), at(0.07));
+    assert!(low > 1_000.0 && high < 1_000.0);
+    let Instrument::Bond(b) = &bond else {
+        unreachable!()
+    };
+    assert!((low - bond_pv(b, 0.03)).abs() < 1e-9);
+    assert!((high - bond_pv(b, 0.07)).abs() < 1e-9);
+}
+
+fn option(strike: f64, is_call: bool) -> Option {
+    Option {
+        underlying: "SPX".to_string(),
+        currency: Currency::USD,
+        strike,
+        spot: 100.0,
+        is_call,
+        vol: 0.20,
+        time_to_expiry: 1.0,
+    }
+}
+
+#[test]
+fn black_scholes_matches_reference_values() {
+    // Hull's textbook ATM case: S = K = 100, r = 5%, vol = 20%, T = 1
+    let call = black_scholes(&option(100.0, true), 0.05);
+    let put = black_scholes(&option(100.0, false), 0.05);
+    assert!((call - 10.4506).abs() < 1e-3, "call {call}");
+    assert!((put - 5.5735).abs() < 1e-3, "put {put}");
+    // Put-call parity: C - P = S - K e^{-rT}
+    assert!((call - put - (100.0 - 100.0 * (-0.05f64).exp())).abs() < 1e-6);
+
+    // Time value keeps an in-the-money call above intrinsic
+    let itm = black_scholes(&option(90.0, true), 0.05);
+    assert!(itm > 10.0);
+}
+
+#[test]
+fn bond_cashflows_are_coupons_plus_principal() {
+    let flows = cashflows(&treasury(0.05, 10), &MarketData::demo());
+    assert_eq!(flows.len(), 11);
+    assert!(flows[..10].iter().all(|f| f.amount == 50.0));
+    let periods: Vec<u32> = flows[..10].iter().map(|f| f.period).collect();
+    assert_eq!(periods, (1..=10).collect::<Vec<_>>());
+    assert_eq!(flows[10], CashFlow { period: 10, amount: 1_000.0 });
+
+    let swap = Instrument::Swap(Swap {
+        currency: Currency::USD,
+        notional: 1_000_000.0,
+        fixed_rate: 0.045,
+        tenor_years: 3,
+    });
+    let swap_flows = cashflows(&swap, &MarketData::demo());
+    assert_eq!(swap_flows.len(), 3);
+    assert!(swap_flows.iter().all(|f| (f.amount - 5_000.0).abs() < 1e-9));
+}
+
+#[test]
+fn portfolio_aggregates_sum_per_instrument_results() {
+    let market = MarketData::demo();
+    let portfolio = vec![
+        treasury(0.05, 10),
+        treasury(0.06, 5),
+        Instrument::Option(option(100.0, true)),
+    ];
+    let summed: f64 = portfolio.iter().map(|i| price(i, &market).unwrap()).sum();
+    let total = portfolio_value(&portfolio, &market).expect("all valid");
+    assert!((total - summed).abs() < 1e-9);
+    // Bond DV01 is face * 0.9 * maturity per bp; the option adds none
+    assert!((total_dv01(&portfolio, &market) - (0.9 + 0.45)).abs() < 1e-9);
+
+    let with_bad = [treasury(0.05, 10), treasury(0.05, 0)];
+    assert_eq!(
+        portfolio_value(&with_bad, &market),
+        Err(PricingError::ZeroMaturity)
+    );
+}
+
+#[test]
+fn stress_scenarios_move_prices_the_expected_way() {
+    let market = MarketData::demo();
+    let bond = treasury(0.05, 10);
+    let call = Instrument::Option(option(100.0, true));
+
+    let base_bond = price(&bond, &market).expect("valid bond");
+    assert!((stress_price(&bond, &market, 0.0, 0.0).unwrap() - base_bond).abs() < 1e-9);
+    assert!(stress_price(&bond, &market, 100.0, 0.0).unwrap() < base_bond);
+
+    let base_call = price(&call, &market).expect("valid option");
+    assert!(stress_price(&call, &market, 0.0, -10.0).unwrap() < base_call);
+    // A rate shock alone leaves the spot-driven option model unchanged
+    assert!((stress_price(&call, &market, 100.0, 0.0).unwrap() - base_call).abs() < 1e-9);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn mixed_portfolio_survives_a_json_round_trip() {
+    let portfolio = vec![
+        treasury(0.05, 5),
+        fra(3, 6),
+        Instrument::Option(option(100.0, true)),
+        Instrument::Cds(Cds {
+            reference_entity: "ACME".to_string(),
+            currency: Currency::EUR,
+            notional: 10_000_000.0,
+            spread_bps: 120.0,
+            tenor_years: 5,
+            recovery_rate: 0.4,
+        }),
+        Instrument::Equity(Equity {
+            ticker: "AAPL".to_string(),
+            currency: Currency::USD,
+            shares: 100,
+            price: 50.0,
+        }),
+    ];
+    let json = serde_json::to_string(&portfolio).expect("serializes");
+    assert!(json.contains("\"type\":\"Cds\""));
+    let back: Vec<Instrument> = serde_json::from_str(&json).expect("deserializes");
+    assert_eq!(back, portfolio);
+}
+
+#[test]
+fn csv_loader_builds_one_instrument_per_row() {
+    let text = "\
+# type,fields...
+Bond,USD,US-TREASURY,1000,0.05,5
+
+Swap,USD,1000000,0.04,5
+Fra,USD,1000000,0.045,3,6
+Option,USD,SPX,100,100,Put,0.2,1
+Future,USD,ES,4500,50,-3
+Cds,EUR,ACME,10000000,120,5,0.4
+FxForward,USD,EUR,1000000,1.085,6
+CapFloor,USD,Floor,5000000,0.03,4
+Equity,GBP,VOD,100,50
+Warrant,USD,XYZ,20,25,0.3,2,1000000,250000
+";
+    let portfolio = parse_portfolio_csv(text).expect("valid csv");
+    let expected = vec![
+        treasury(0.05, 5),
+        Instrument::Swap(Swap {
+            currency: Currency::USD,
+            notional: 1_000_000.0,
+            fixed_rate: 0.04,
+            tenor_years: 5,
+        }),
+        fra(3, 6),
+        Instrument::Option(option(100.0, false)),
+        Instrument::Future(Future {
+            underlying: "ES".to_string(),
+            currency: Currency::USD,
+            contract_price: 4_500.0,
+            contract_size: 50.0,
+            num_contracts: -3,
+        }),
+        Instrument::Cds(Cds {
+            reference_entity: "ACME".to_string(),
+            currency: Currency::EUR,
+            notional: 10_000_000.0,
+            spread_bps: 120.0,
+            tenor_years: 5,
+            recovery_rate: 0.4,
+        }),
+        Instrument::FxForward(FxForward {
+            base_ccy: Currency::EUR,
+            quote_ccy: Currency::USD,
+            notional: 1_000_000.0,
+            forward_rate: 1.085,
+            settle_months: 6,
+        }),
+        Instrument::CapFloor(CapFloor {
+            currency: Currency::USD,
+            notional: 5_000_000.0,
+            strike_rate: 0.03,
+            tenor_years: 4,
+            is_cap: false,
+        }),
+        Instrument::Equity(Equity {
+            ticker: "VOD".to_string(),
+            currency: Currency::GBP,
+            shares: 100,
+            price: 50.0,
+        }),
+        Instrument::Warrant(Warrant {
+            underlying: "XYZ".to_string(),
+            currency: Currency::USD,
+            strike: 20.0,
+            spot: 25.0,
+            vol: 0.3,
+            time_to_expiry: 2.0,
+            shares_outstanding: 1_000_000,
+            warrants_issued: 250_000,
+        }),
+    ];
+    assert_eq!(portfolio, expected);
+}
+
+#[test]
+fn csv_loader_reports_the_offending_row_and_column() {
+    let err = parse_portfolio_csv("Bond,USD,ACME,abc,0.05,5").unwrap_err();
+    assert!(matches!(err, LoadError::InvalidValue { row: 1, column: 4, .. }));
+
+    let err = parse_portfolio_csv("Equity,USD,AAPL,100,50\nCdo,USD").unwrap_err();
+    assert!(matches!(err, LoadError::UnknownType { row: 2, .. }));
+
+    let err = parse_portfolio_csv("Equity,USD,AAPL").unwrap_err();
+    assert!(matches!(err, LoadError::MissingColumn { row: 1, column: 4 }));
+}
+
+#[test]
+fn bonds_filter_out_and_credit_groups_by_name() {
+    let portfolio = vec![
+        treasury(0.05, 5),
+        Instrument::Option(option(100.0, true)),
+        treasury(0.03, 10),
+        Instrument::Cds(Cds {
+            reference_entity: "US-TREASURY".to_string(),
+            currency: Currency::USD,
+            notional: 1_000_000.0,
+            spread_bps: 20.0,
+            tenor_years: 5,
+            recovery_rate: 0.4,
+        }),
+        fra(3, 6),
+    ];
+    let bonds = filter_bonds(&portfolio);
+    assert_eq!(bonds.len(), 2);
+    assert_eq!(bonds[1].maturity_years, 10);
+
+    let groups = group_by_issuer(&portfolio);
+    assert_eq!(groups.len(), 3);
+    assert_eq!(groups["US-TREASURY"].len(), 3);
+    assert_eq!(groups["OPT:SPX"].len(), 1);
+    assert_eq!(groups["FRA"].len(), 1);
+}
+
+#[test]
+fn short_future_marks_at_its_signed_notional() {
+    let fut = Future {
+        underlying: "NQ".to_string(),
+        currency: Currency::USD,
+        contract_price: 15_800.0,
+        contract_size: 20.0,
+        num_contracts: -2,
+    };
+    assert_eq!(fut.point_value(), -40.0);
+    let inst = Instrument::Future(fut);
+    let market = MarketData::demo();
+    assert_eq!(price(&inst, &market).unwrap(), -632_000.0);
+    assert_eq!(risk_report(&inst, &market).delta, Some(-40.0));
+    // Margin is charged on the size of the position, not its sign
+    assert!((signed_capital_charge(&inst, &market) + 37_920.0).abs() < 1e-6);
+    assert!((capital_charge(&inst, &market) - 37_920.0).abs() < 1e-6);
+}
+
+#[test]
+fn gilt_converts_into_dollars_at_the_market_fx_rate() {
+    let gilt = Instrument::Bond(Bond {
+        issuer: "UK-GILT".to_string(),
+        currency: Currency::GBP,
+        face_value: 1_000.0,
+        coupon_rate: 0.05,
+        maturity_years: 5,
+        callable: None,
+    });
+    let mut market = MarketData::demo().with_fx(Currency::GBP, 1.25, 0.045);
+    assert!((price_in(&gilt, &market, Currency::USD).unwrap() - 1_250.0).abs() < 1e-6);
+    assert!((price_in(&gilt, &market, Currency::GBP).unwrap() - 1_000.0).abs() < 1e-6);
+    market.fx.remove(&Currency::EUR);
+    assert!(matches!(
+        price_in(&gilt, &market, Currency::EUR),
+        Err(PricingError::MissingFxRate(Currency::EUR))
+    ));
+}
+
+#[test]
+fn accrued_interest_builds_up_over_the_coupon_period() {
+    let Instrument::Bond(bond) = treasury(0.06, 5) else {
+        unreachable!()
+    };
+    assert_eq!(accrued_interest(&bond, 0), 0.0);
+    assert!((accrued_interest(&bond, DAYS_PER_COUPON / 2) - 30.0).abs() < 1e-9);
+    // Capped at one full coupon
+    assert!((accrued_interest(&bond, 500) - 60.0).abs() < 1e-9);
+
+    let market = MarketData::demo();
+    let inst = Instrument::Bond(bond);
+    let clean = price(&inst, &market).unwrap();
+    assert!((dirty_price(&inst, &market, 180).unwrap() - clean - 30.0).abs() < 1e-9);
+    // Only bonds accrue
+    let other = fra(3, 6);
+    assert_eq!(dirty_price(&other, &market, 180).unwrap(), price(&other, &market).unwrap());
+}
+
+#[test]
+fn sort_puts_the_most_valuable_first_and_unpriceable_last() {
+    let mut portfolio = vec![
+        treasury(0.05, 0), // matured: fails to price
+        Instrument::Option(option(100.0, true)),
+        treasury(0.05, 5),
+        Instrument::Option(option(150.0, true)),
+    ];
+    sort_by_price_desc(&mut portfolio, &MarketData::demo());
+    assert_eq!(portfolio[0], treasury(0.05, 5));
+    assert_eq!(portfolio[1], Instrument::Option(option(100.0, true)));
+    assert_eq!(portfolio[2], Instrument::Option(option(150.0, true)));
+    assert_eq!(portfolio[3], treasury(0.05, 0));
+}
+
+#[test]
+fn cds_at_the_running_coupon_is_near_zero_and_gains_as_spreads_widen() {
+    let cds = Cds {
+        reference_entity: "ACME".to_string(),
+        currency: Currency::USD,
+        notional: 10_000_000.0,
+        spread_bps: CDS_COUPON_BPS,
+        tenor_years: 5,
+        recovery_rate: 0.4,
+    };
+    let at_coupon = cds_pv(&cds, 0.05, 100.0);
+    assert!(at_coupon.abs() < 0.001 * cds.notional);
+    assert!(cds_pv(&cds, 0.05, 50.0) < at_coupon);
+    assert!(cds_pv(&cds, 0.05, 200.0) > at_coupon);
+
+    let cs01 = risk_report(&Instrument::Cds(cds), &MarketData::demo()).cs01;
+    assert!(cs01.unwrap() > 0.0);
+}
+
+#[test]
+fn count_by_type_tallies_each_variant() {
+    let portfolio = vec![
+        Instrument::Option(option(100.0, true)),
+        treasury(0.05, 5),
+        Instrument::Option(option(90.0, false)),
+        Instrument::Swap(Swap {
+            currency: Currency::USD,
+            notional: 1_000_000.0,
+            fixed_rate: 0.04,
+            tenor_years: 5,
+        }),
+    ];
+    let counts = count_by_type(&portfolio);
+    assert_eq!(counts.len(), 3);
+    assert_eq!(counts["Option"], 2);
+    assert_eq!(counts["Bond"], 1);
+    assert_eq!(counts["Swap"], 1);
+}
+
+#[test]
+fn fx_forward_struck_at_the_implied_forward_is_worth_nothing() {
+    let mut fwd = FxForward {
+        base_ccy: Currency::EUR,
+        quote_ccy: Currency::USD,
+        notional: 1_000_000.0,
+        forward_rate: 0.0,
+        settle_months: 6,
+    };
+    let market = MarketData::demo();
+    let (spot, quote_rate, base_rate) = fx_forward_market(&fwd, &market).unwrap();
+    fwd.forward_rate = implied_forward(spot, quote_rate, base_rate, fwd.years());
+    // USD rates above EUR rates: the forward trades above spot
+    assert!(fwd.forward_rate > spot);
+    assert!(fx_forward_mtm(&fwd, spot, quote_rate, base_rate).abs() < 1e-6);
+
+    assert!(price(&Instrument::FxForward(fwd.clone()), &market).unwrap().abs() < 1e-6);
+
+    // Long base: a richer spot makes the contract worth more
+    assert!(fx_forward_mtm(&fwd, spot * 1.01, quote_rate, base_rate) > 0.0);
+    let richer = MarketData::demo().with_fx(Currency::EUR, 1.10, 0.035);
+    assert!(price(&Instrument::FxForward(fwd), &richer).unwrap() > 0.0);
+}
+
+#[test]
+fn risk_report_fills_in_the_measures_that_apply() {
+    let market = MarketData::demo();
+    let bond = risk_report(&treasury(0.05, 10), &market);
+    assert!((bond.duration.unwrap() - 9.0).abs() < 1e-12);
+    assert!((bond.dv01.unwrap() - 1_000.0 * 9.0 * 0.0001).abs() < 1e-12);
+    assert_eq!(bond.delta, None);
+
+    // ATM call: d1 = (r + vol^2 / 2) / vol = 0.35, N(0.35) = 0.63683
+    let call = risk_report(&Instrument::Option(option(100.0, true)), &market);
+    assert!((call.delta.unwrap() - 0.63683).abs() < 1e-4);
+    assert_eq!(call.dv01, None);
+    let put = risk_report(&Instrument::Option(option(100.0, false)), &market);
+    assert!((put.delta.unwrap() + 0.36317).abs() < 1e-4);
+}
+
+#[test]
+fn netting_offsets_long_and_short_in_the_same_name() {
+    let equity = |shares: i32| {
+        Instrument::Equity(Equity {
+            ticker: "AAPL".to_string(),
+            currency: Currency::USD,
+            shares,
+            price: 50.0,
+        })
+    };
+    // 15% haircut: 750 long, 450 short; the bond is charged 8% of face
+    let portfolio = vec![equity(100), treasury(0.05, 5), equity(-60)];
+
+    let market = MarketData::demo();
+    let gross = total_capital_charge(&portfolio, &market, NettingRule::None);
+    let netted = total_capital_charge(&portfolio, &market, NettingRule::BySymbol);
+    assert!((gross - (750.0 + 450.0 + 80.0)).abs() < 1e-9);
+    assert!((netted - (300.0 + 80.0)).abs() < 1e-9);
+
+    let lines = capital_breakdown(&portfolio, &market, NettingRule::BySymbol);
+    let symbols: Vec<&str> = lines.iter().map(|line| line.symbol.as_str()).collect();
+    assert_eq!(symbols, ["EQ:AAPL", "US-TREASURY"]);
+    assert!((lines[0].gross - 1_200.0).abs() < 1e-9);
+    assert!((lines[0].net - 300.0).abs() < 1e-9);
+}
+
+#[test]
+fn cap_gains_and_floor_loses_as_forwards_rise() {
+    let cap = CapFloor {
+        currency: Currency::USD,
+        notional: 10_000_000.0,
+        strike_rate: 0.04,
+        tenor_years: 5,
+        is_cap: true,
+    };
+    let floor = CapFloor { is_cap: false, ..cap.clone() };
+    let low = cap_floor_pv(&cap, 0.03, 0.05);
+    let atm = cap_floor_pv(&cap, 0.04, 0.05);
+    let high = cap_floor_pv(&cap, 0.05, 0.05);
+    assert!(low < atm && atm < high);
+    assert!(low > 0.0); // later caplets keep time value out of the money
+    assert!(cap_floor_pv(&floor, 0.05, 0.05) < cap_floor_pv(&floor, 0.03, 0.05));
+    // At the money a cap and a floor on the same strike are worth the same
+    assert!((cap_floor_pv(&floor, 0.04, 0.05) - atm).abs() < 1e-6);
+}
+
+#[test]
+fn notional_fold_sums_gross_exposure() {
+    let portfolio = vec![
+        treasury(0.05, 5),
+        fra(3, 6),
+        Instrument::Option(option(100.0, true)),
+        Instrument::Equity(Equity {
+            ticker: "AAPL".to_string(),
+            currency: Currency::USD,
+            shares: -100,
+            price: 50.0,
+        }),
+    ];
+    // Bond face + FRA notional + 100 shares of option underlying + |short equity|
+    let total = fold(&portfolio, 0.0, &NotionalTotal);
+    assert!((total - (1_000.0 + 1_000_000.0 + 10_000.0 + 5_000.0)).abs() < 1e-9);
+    assert_eq!(fold(&[], 0.0, &NotionalTotal), 0.0);
+}
+
+#[test]
+fn bump_spot_moves_only_options_and_warrants() {
+    let warrant = Warrant {
+        underlying: "XYZ".to_string(),
+        currency: Currency::USD,
+        strike: 20.0,
+        spot: 25.0,
+        vol: 0.3,
+        time_to_expiry: 2.0,
+        shares_outstanding: 1_000_000,
+        warrants_issued: 250_000,
+    };
+    let equity = Instrument::Equity(Equity {
+        ticker: "XYZ".to_string(),
+        currency: Currency::USD,
+        shares: 100,
+        price: 25.0,
+    });
+    let mut portfolio = vec![
+        Instrument::Option(option(100.0, true)),
+        Instrument::Warrant(warrant),
+        treasury(0.05, 5),
+        equity.clone(),
+    ];
+    bump_spot(&mut portfolio, 10.0);
+
+    let Instrument::Option(o) = &portfolio[0] else { unreachable!() };
+    assert!((o.spot - 110.0).abs() < 1e-9);
+    let Instrument::Warrant(w) = &portfolio[1] else { unreachable!() };
+    assert!((w.spot - 27.5).abs() < 1e-9);
+    assert_eq!(portfolio[2], treasury(0.05, 5));
+    assert_eq!(portfolio[3], equity);
+}
+
+#[test]
+fn equity_values_at_shares_times_price() {
+    let inst = Instrument::Equity(Equity {
+        ticker: "AAPL".to_string(),
+        currency: Currency::USD,
+        shares: 100,
+        price: 50.0,
+    });
+    let market = MarketData::demo();
+    assert_eq!(price(&inst, &market).unwrap(), 5_000.0);
+
+    let risk = risk_report(&inst, &market);
+    assert_eq!(risk.delta, Some(100.0));
+    assert_eq!(risk.dv01, Some(0.0));
+    assert!((capital_charge(&inst, &market) - 750.0).abs() < 1e-9);
+    assert!(cashflows(&inst, &market).is_empty());
+
+    // A quoted spot in the market re-marks the position
+    let remarked = MarketData::demo().with_spot("AAPL", 55.0);
+    assert_eq!(price(&inst, &remarked).unwrap(), 5_500.0);
+}
+
+#[test]
+fn yield_to_maturity_recovers_the_discount_rate() {
+    let Instrument::Bond(bond) = treasury(0.03, 7) else {
+        unreachable!()
+    };
+    let market_price = bond_pv(&bond, 0.05);
+    let ytm = yield_to_maturity(&bond, market_price).expect("converges");
+    assert!((ytm - 0.05).abs() < 1e-9);
+
+    // A bond with no flows has no yield to solve for
+    let matured = Bond { maturity_years: 0, ..bond };
+    assert!(matches!(
+        yield_to_maturity(&matured, 1_000.0),
+        Err(SolveError::NoConvergence)
+    ));
+}
+
+#[test]
+fn cds_recovery_outside_zero_to_one_is_rejected() {
+    let market = MarketData::demo();
+    let rows = "\
+Cds,USD,ACME,1000000,100,5,1.0
+Cds,USD,ACME,1000000,100,5,1.2
+Cds,USD,ACME,1000000,100,5,-0.1
+Cds,USD,ACME,1000000,100,5,0
+";
+    let portfolio = parse_portfolio_csv(rows).expect("well-formed rows");
+    let prices: Vec<_> = portfolio.iter().map(|inst| price(inst, &market)).collect();
+    assert_eq!(prices[0], Err(PricingError::InvalidRecovery(1.0)));
+    assert_eq!(prices[1], Err(PricingError::InvalidRecovery(1.2)));
+    assert_eq!(prices[2], Err(PricingError::InvalidRecovery(-0.1)));
+    assert!(prices[3].as_ref().is_ok_and(|px| px.is_finite()));
+    assert!(stress_price(&portfolio[0], &market, 100.0, 0.0).is_err());
+}
+
+#[test]
+fn visitor_missing_a_method_is_a_compile_error() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/missing_visit_method.rs");
+}